@@ -55,7 +55,7 @@ kwctl() {
 
 @test "execute a remote policy that is rejected" {
     kwctl run --request-path test-data/privileged-pod.json registry://ghcr.io/kubewarden/policies/pod-privileged:v0.1.9
-    [ "$status" -eq 0 ]
+    [ "$status" -eq 1 ]
     [ $(expr "$output" : '.*"allowed":false.*') -ne 0 ]
 }
 
@@ -67,10 +67,22 @@ kwctl() {
 
 @test "execute a remote policy that is rejected with AdmissionReview object as the root document" {
     kwctl run --request-path test-data/privileged-pod-admission-review.json registry://ghcr.io/kubewarden/policies/pod-privileged:v0.1.9
-    [ "$status" -eq 0 ]
+    [ "$status" -eq 1 ]
     [ $(expr "$output" : '.*"allowed":false.*') -ne 0 ]
 }
 
+@test "execute a remote policy when kwctl fails" {
+    kwctl run --request-path test-data/does-not-exist.json registry://ghcr.io/kubewarden/policies/pod-privileged:v0.1.9
+    [ "$status" -eq 3 ]
+    kwctl --config ${KWCTL_TMPDIR}/config.yaml run --bogus registry://ghcr.io/kubewarden/policies/pod-privileged:v0.1.9
+    [ "$status" -eq 3 ]
+    kwctl --config ${KWCTL_TMPDIR}/does-not-exist.yaml run --request-path test-data/unprivileged-pod-admission-review.json registry://ghcr.io/kubewarden/policies/pod-privileged:v0.1.9
+    [ "$status" -eq 3 ]
+    echo "sources: [" > ${KWCTL_TMPDIR}/invalid-config.yaml
+    kwctl --config ${KWCTL_TMPDIR}/invalid-config.yaml run --request-path test-data/unprivileged-pod-admission-review.json registry://ghcr.io/kubewarden/policies/pod-privileged:v0.1.9
+    [ "$status" -eq 3 ]
+}
+
 @test "remove a policy from the policy store" {
     kwctl pull registry://ghcr.io/kubewarden/policies/pod-privileged:v0.1.9
     kwctl pull https://github.com/kubewarden/pod-privileged-policy/releases/download/v0.1.9/policy.wasm
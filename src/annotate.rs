@@ -1,24 +1,91 @@
 use crate::backend::{Backend, BackendDetector};
 use anyhow::{anyhow, Result};
 use policy_evaluator::{constants::*, policy_metadata::Metadata, ProtocolVersion};
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use validator::Validate;
 
+/// Annotates the Wasm module. When `cargo_toml_path` is given, the
+/// annotations not defined by the metadata file are taken from the
+/// `[package]` section of the `Cargo.toml` of the policy.
 pub(crate) fn write_annotation(
     wasm_path: PathBuf,
     metadata_path: PathBuf,
     destination: PathBuf,
+    cargo_toml_path: Option<&Path>,
 ) -> Result<()> {
     let backend_detector = BackendDetector::default();
-    let metadata = prepare_metadata(wasm_path.clone(), metadata_path, backend_detector)?;
+    let default_annotations = match cargo_toml_path {
+        Some(cargo_toml_path) => cargo_annotations(cargo_toml_path)?,
+        None => BTreeMap::new(),
+    };
+    let metadata = prepare_metadata(
+        wasm_path.clone(),
+        metadata_path,
+        backend_detector,
+        default_annotations,
+    )?;
     write_annotated_wasm_file(wasm_path, destination, metadata)
 }
 
+/// Maps the annotations of the policy to the fields of the `[package]`
+/// section of `Cargo.toml` they are taken from
+const CARGO_ANNOTATIONS: &[(&str, &str)] = &[
+    (KUBEWARDEN_ANNOTATION_POLICY_TITLE, "name"),
+    (KUBEWARDEN_ANNOTATION_POLICY_DESCRIPTION, "description"),
+    (KUBEWARDEN_ANNOTATION_POLICY_AUTHOR, "authors"),
+    (KUBEWARDEN_ANNOTATION_POLICY_URL, "repository"),
+    (KUBEWARDEN_ANNOTATION_POLICY_LICENSE, "license"),
+];
+
+// Reads the annotations of the policy from the package metadata of its
+// `Cargo.toml`. The authors are joined by commas, the fields that are not
+// strings (e.g. inherited from the workspace) are ignored.
+fn cargo_annotations(cargo_toml_path: &Path) -> Result<BTreeMap<String, String>> {
+    let contents = std::fs::read_to_string(cargo_toml_path).map_err(|e| {
+        anyhow!(
+            "Error reading Cargo manifest {}: {}",
+            cargo_toml_path.display(),
+            e
+        )
+    })?;
+    let manifest: toml::Value = toml::from_str(&contents).map_err(|e| {
+        anyhow!(
+            "Error parsing Cargo manifest {}: {}",
+            cargo_toml_path.display(),
+            e
+        )
+    })?;
+    let package = manifest.get("package").ok_or_else(|| {
+        anyhow!(
+            "The Cargo manifest {} doesn't have a [package] section",
+            cargo_toml_path.display()
+        )
+    })?;
+
+    Ok(CARGO_ANNOTATIONS
+        .iter()
+        .filter_map(|(annotation, field)| {
+            let value = match package.get(field)? {
+                toml::Value::String(value) => value.clone(),
+                toml::Value::Array(values) => values
+                    .iter()
+                    .filter_map(|value| value.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", "),
+                _ => return None,
+            };
+            Some((String::from(*annotation), value)).filter(|(_, value)| !value.is_empty())
+        })
+        .collect())
+}
+
 fn prepare_metadata(
     wasm_path: PathBuf,
     metadata_path: PathBuf,
     backend_detector: BackendDetector,
+    default_annotations: BTreeMap<String, String>,
 ) -> Result<Metadata> {
     let metadata_file =
         File::open(metadata_path).map_err(|e| anyhow!("Error opening metadata file: {}", e))?;
@@ -36,6 +103,10 @@ fn prepare_metadata(
     };
 
     let mut annotations = metadata.annotations.unwrap_or_default();
+    // the annotations of the metadata file take precedence
+    for (annotation, value) in default_annotations {
+        annotations.entry(annotation).or_insert(value);
+    }
     annotations.insert(
         String::from(KUBEWARDEN_ANNOTATION_KWCTL_VERSION),
         String::from(env!("CARGO_PKG_VERSION")),
@@ -118,6 +189,7 @@ mod tests {
             PathBuf::from("irrelevant.wasm"),
             file_path,
             backend_detector,
+            BTreeMap::new(),
         )?;
         let annotations = metadata.annotations.unwrap();
 
@@ -167,6 +239,7 @@ mod tests {
             PathBuf::from("irrelevant.wasm"),
             file_path,
             backend_detector,
+            BTreeMap::new(),
         )?;
         let annotations = metadata.annotations.unwrap();
 
@@ -212,6 +285,7 @@ mod tests {
             PathBuf::from("irrelevant.wasm"),
             file_path,
             backend_detector,
+            BTreeMap::new(),
         )?;
         let annotations = metadata.annotations.unwrap();
 
@@ -252,6 +326,7 @@ mod tests {
             PathBuf::from("irrelevant.wasm"),
             file_path,
             backend_detector,
+            BTreeMap::new(),
         );
         assert!(metadata.is_ok());
         assert_eq!(
@@ -261,4 +336,75 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_annotations_from_cargo() -> Result<()> {
+        let dir = tempdir()?;
+
+        let cargo_toml_path = dir.path().join("Cargo.toml");
+        std::fs::write(
+            &cargo_toml_path,
+            r#"
+[package]
+name = "psp-capabilities"
+version = "0.1.6"
+authors = ["Jane Doe <jane@example.com>", "John Doe"]
+description = "Replacement for the Kubernetes Pod Security Policy capabilities"
+repository = "https://github.com/kubewarden/psp-capabilities"
+license = "Apache-2.0"
+edition.workspace = true
+"#,
+        )?;
+        let metadata_path = dir.path().join("metadata.yml");
+        std::fs::write(
+            &metadata_path,
+            r#"
+        rules:
+        - apiGroups: [""]
+          apiVersions: ["v1"]
+          resources: ["pods"]
+          operations: ["CREATE", "UPDATE"]
+        mutating: false
+        annotations:
+          io.kubewarden.policy.title: capabilities
+        "#,
+        )?;
+
+        let backend_detector = BackendDetector::new(
+            mock_rego_policy_detector_false,
+            mock_protocol_version_detector_v1,
+        );
+        let metadata = prepare_metadata(
+            PathBuf::from("irrelevant.wasm"),
+            metadata_path,
+            backend_detector,
+            cargo_annotations(&cargo_toml_path)?,
+        )?;
+        let annotations = metadata.annotations.unwrap();
+
+        // the metadata file wins over Cargo.toml
+        assert_eq!(
+            annotations.get(KUBEWARDEN_ANNOTATION_POLICY_TITLE),
+            Some(&String::from("capabilities"))
+        );
+        assert_eq!(
+            annotations.get(KUBEWARDEN_ANNOTATION_POLICY_AUTHOR),
+            Some(&String::from("Jane Doe <jane@example.com>, John Doe"))
+        );
+        assert_eq!(
+            annotations.get(KUBEWARDEN_ANNOTATION_POLICY_URL),
+            Some(&String::from(
+                "https://github.com/kubewarden/psp-capabilities"
+            ))
+        );
+        assert_eq!(
+            annotations.get(KUBEWARDEN_ANNOTATION_POLICY_LICENSE),
+            Some(&String::from("Apache-2.0"))
+        );
+        assert!(annotations.contains_key(KUBEWARDEN_ANNOTATION_POLICY_DESCRIPTION));
+
+        std::fs::write(&cargo_toml_path, "[workspace]\nmembers = []\n")?;
+        assert!(cargo_annotations(&cargo_toml_path).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,122 @@
+use anyhow::{anyhow, Result};
+use std::{collections::BTreeSet, fmt, path::Path};
+
+/// A capability of the host required by a policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Capability {
+    Filesystem,
+    Network,
+    EnvironmentVariables,
+    Clock,
+    Random,
+    /// waPC host callbacks, used to access the Kubewarden host functions
+    /// (OCI registry, DNS lookups, sigstore verification,...)
+    HostCallbacks,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Capability::Filesystem => "filesystem",
+            Capability::Network => "network",
+            Capability::EnvironmentVariables => "environment variables",
+            Capability::Clock => "clock",
+            Capability::Random => "random",
+            Capability::HostCallbacks => "host callbacks",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Looks at the functions imported by the Wasm module pointed by
+/// `wasm_path`, and returns the host capabilities they give access to
+pub(crate) fn required_capabilities(wasm_path: &Path) -> Result<BTreeSet<Capability>> {
+    let wasm = std::fs::read(wasm_path)
+        .map_err(|e| anyhow!("Cannot read policy {}: {}", wasm_path.display(), e))?;
+    capabilities_from_imports(&wasm)
+}
+
+fn capabilities_from_imports(wasm: &[u8]) -> Result<BTreeSet<Capability>> {
+    let mut capabilities = BTreeSet::new();
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        if let wasmparser::Payload::ImportSection(imports) = payload? {
+            for import in imports {
+                let import = import?;
+                if let Some(capability) = import_capability(import.module, import.field) {
+                    capabilities.insert(capability);
+                }
+            }
+        }
+    }
+
+    Ok(capabilities)
+}
+
+fn import_capability(module: &str, field: Option<&str>) -> Option<Capability> {
+    let field = field.unwrap_or_default();
+    match module {
+        "wasi_snapshot_preview1" | "wasi_unstable" => {
+            // `fd_*` functions are used also to write to stdout and stderr,
+            // only preopened directories give access to the filesystem
+            if field.starts_with("path_") || field.starts_with("fd_prestat_") {
+                Some(Capability::Filesystem)
+            } else if field.starts_with("sock_") {
+                Some(Capability::Network)
+            } else if field.starts_with("environ_") || field.starts_with("args_") {
+                Some(Capability::EnvironmentVariables)
+            } else if field.starts_with("clock_") {
+                Some(Capability::Clock)
+            } else if field == "random_get" {
+                Some(Capability::Random)
+            } else {
+                None
+            }
+        }
+        "wapc" if field == "__host_call" => Some(Capability::HostCallbacks),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds a Wasm module importing the given functions
+    fn wasm_with_imports(imports: &[(&str, &str)]) -> Vec<u8> {
+        let imports: String = imports
+            .iter()
+            .map(|(module, field)| format!(r#"(import "{}" "{}" (func))"#, module, field))
+            .collect();
+        wat::parse_str(format!("(module {})", imports)).unwrap()
+    }
+
+    #[test]
+    fn test_capabilities_from_imports() -> Result<()> {
+        let wasm = wasm_with_imports(&[
+            ("wasi_snapshot_preview1", "fd_write"),
+            ("wasi_snapshot_preview1", "path_open"),
+            ("wasi_snapshot_preview1", "random_get"),
+            ("wasi_snapshot_preview1", "proc_exit"),
+            ("wapc", "__host_call"),
+            ("wapc", "__guest_request"),
+        ]);
+
+        let capabilities: Vec<Capability> = capabilities_from_imports(&wasm)?.into_iter().collect();
+        assert_eq!(
+            capabilities,
+            vec![
+                Capability::Filesystem,
+                Capability::Random,
+                Capability::HostCallbacks
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_capabilities() -> Result<()> {
+        let wasm = wasm_with_imports(&[("env", "opa_abort")]);
+        assert!(capabilities_from_imports(&wasm)?.is_empty());
+        Ok(())
+    }
+}
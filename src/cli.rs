@@ -26,9 +26,25 @@ pub fn build_cli() -> Command<'static> {
         .author(crate_authors!())
         .about(crate_description!())
         .arg(Arg::new("verbose").short('v').help("Increase verbosity"))
+        .arg(
+            Arg::new("config")
+            .long("config")
+            .takes_value(true)
+            .global(true)
+            .value_name("PATH")
+            .help("YAML file holding the defaults of the sources, docker config and verification config paths. Defaults to the config.yaml file of the kwctl config directory")
+        )
         .subcommand(
             Command::new("policies")
                 .about("Lists all downloaded policies")
+                .arg(
+                    Arg::new("output")
+                    .long("output")
+                    .short('o')
+                    .takes_value(true)
+                    .possible_values(&["yaml", "json"])
+                    .help("Output format. The machine readable formats include the location of each policy inside of the local store")
+                )
         )
         .subcommand(
             Command::new("pull")
@@ -39,6 +55,14 @@ pub fn build_cli() -> Command<'static> {
                     .takes_value(true)
                     .help("Path to a Docker config.json-like path. Can be used to indicate registry authentication details")
                 )
+                .arg(
+                    Arg::new("registry-auth")
+                    .long("registry-auth")
+                    .takes_value(true)
+                    .value_name("USER:PASSWORD")
+                    .conflicts_with("docker-config-json-path")
+                    .help("Credentials to be used to authenticate against the registry of the policy, instead of a Docker config.json file. A registry token can be given as password")
+                )
                 .arg(
                     Arg::new("sources-path")
                     .long("sources-path")
@@ -55,6 +79,7 @@ pub fn build_cli() -> Command<'static> {
                     Arg::new("verification-key")
                     .short('k')
                     .long("verification-key")
+                    .visible_alias("verify-key")
                     .multiple_occurrences(true)
                     .number_of_values(1)
                     .takes_value(true)
@@ -122,11 +147,40 @@ pub fn build_cli() -> Command<'static> {
                     .takes_value(true)
                     .help("Output file. If not provided will be downloaded to the Kubewarden store")
                 )
+                .arg(
+                    Arg::new("jobs")
+                    .long("jobs")
+                    .short('j')
+                    .takes_value(true)
+                    .default_value("4")
+                    .help("Number of policies to be pulled concurrently, when pulling multiple policies")
+                )
+                .arg(
+                    Arg::new("sbom-output")
+                    .long("sbom-output")
+                    .takes_value(true)
+                    .help("Download the SBOM attached to the policy, and write it to the given file. Fails if no SBOM is attached to the policy")
+                )
+                .arg(
+                    Arg::new("force")
+                    .long("force")
+                    .help("Download the policy even when the local copy is up to date")
+                )
+                .arg(
+                    Arg::new("rewrite-prefix")
+                    .long("rewrite-prefix")
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .value_name("FROM=TO")
+                    .conflicts_with("output-path")
+                    .help("Store the policies whose registry:// URI starts with FROM as if they were pulled from TO, e.g. `ghcr.io/kubewarden=registry.internal/kubewarden`. Can be repeated multiple times, the first matching rewrite is used")
+                )
                 .arg(
                     Arg::new("uri")
                         .required(true)
                         .index(1)
-                        .help("Policy URI. Supported schemes: registry://, https://, file://")
+                        .help("Policy URI. Supported schemes: registry://, https://, file://. The tag of a registry:// URI can be a glob pattern (e.g. `v0.1.*`) to pull all the matching tags")
                 )
         )
         .subcommand(
@@ -138,6 +192,14 @@ pub fn build_cli() -> Command<'static> {
                     .takes_value(true)
                     .help("Path to a Docker config.json-like path. Can be used to indicate registry authentication details")
                 )
+                .arg(
+                    Arg::new("registry-auth")
+                    .long("registry-auth")
+                    .takes_value(true)
+                    .value_name("USER:PASSWORD")
+                    .conflicts_with("docker-config-json-path")
+                    .help("Credentials to be used to authenticate against the registry of the policy, instead of a Docker config.json file. A registry token can be given as password")
+                )
                 .arg(
                     Arg::new("sources-path")
                     .long("sources-path")
@@ -230,6 +292,14 @@ pub fn build_cli() -> Command<'static> {
                     .takes_value(true)
                     .help("Path to a Docker config.json-like path. Can be used to indicate registry authentication details")
                 )
+                .arg(
+                    Arg::new("registry-auth")
+                    .long("registry-auth")
+                    .takes_value(true)
+                    .value_name("USER:PASSWORD")
+                    .conflicts_with("docker-config-json-path")
+                    .help("Credentials to be used to authenticate against the registry of the policy, instead of a Docker config.json file. A registry token can be given as password")
+                )
                 .arg(
                     Arg::new("sources-path")
                     .long("sources-path")
@@ -242,6 +312,20 @@ pub fn build_cli() -> Command<'static> {
                     .long("force")
                     .help("Push also a policy that is not annotated")
                 )
+                .arg(
+                    Arg::new("dry-run")
+                    .long("dry-run")
+                    .help("Print what would be pushed (target reference, digests, media types and sizes) without writing to the registry")
+                )
+                .arg(
+                    Arg::new("annotation")
+                    .long("annotation")
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .value_name("KEY=VALUE")
+                    .help("OCI annotation to add to the manifest of the pushed policy (e.g. org.opencontainers.image.source=https://...). Can be repeated multiple times")
+                )
                 .arg(
                     Arg::new("output")
                     .long("output")
@@ -264,6 +348,65 @@ pub fn build_cli() -> Command<'static> {
                         .help("Policy URI. Supported schemes: registry://")
                 )
         )
+        .subcommand(
+            Command::new("sign")
+                .about("Signs a Kubewarden policy stored inside of an OCI registry, following the cosign conventions")
+                .arg(
+                    Arg::new("docker-config-json-path")
+                    .long("docker-config-json-path")
+                    .takes_value(true)
+                    .help("Path to a Docker config.json-like path. Can be used to indicate registry authentication details")
+                )
+                .arg(
+                    Arg::new("registry-auth")
+                    .long("registry-auth")
+                    .takes_value(true)
+                    .value_name("USER:PASSWORD")
+                    .conflicts_with("docker-config-json-path")
+                    .help("Credentials to be used to authenticate against the registry of the policy, instead of a Docker config.json file. A registry token can be given as password")
+                )
+                .arg(
+                    Arg::new("sources-path")
+                    .long("sources-path")
+                    .takes_value(true)
+                    .help("YAML file holding source information (https, registry insecure hosts, custom CA's...)")
+                )
+                .arg(
+                    Arg::new("key")
+                    .long("key")
+                    .takes_value(true)
+                    .conflicts_with("identity-token")
+                    .help("Path to the PEM encoded PKCS#8 ECDSA P-256 private key used to sign the policy, like the ones generated by `cosign generate-key-pair`. The password of encrypted keys is read from the COSIGN_PASSWORD environment variable, or asked interactively")
+                )
+                .arg(
+                    Arg::new("identity-token")
+                    .long("identity-token")
+                    .takes_value(true)
+                    .env("SIGSTORE_ID_TOKEN")
+                    .hide_env_values(true)
+                    .help("OIDC identity token used to perform a keyless signature: the signing certificate is issued by Fulcio for the identity of the token")
+                )
+                .arg(
+                    Arg::new("fulcio-url")
+                    .long("fulcio-url")
+                    .takes_value(true)
+                    .default_value("https://fulcio.sigstore.dev")
+                    .help("URL of the Fulcio instance issuing the certificates of keyless signatures")
+                )
+                .arg(
+                    Arg::new("rekor-url")
+                    .long("rekor-url")
+                    .takes_value(true)
+                    .default_value("https://rekor.sigstore.dev")
+                    .help("URL of the Rekor instance recording keyless signatures")
+                )
+                .arg(
+                    Arg::new("uri")
+                        .required(true)
+                        .index(1)
+                        .help("Policy URI. Supported schemes: registry://")
+                )
+        )
         .subcommand(
             Command::new("rm")
                 .about("Removes a Kubewarden policy from the store")
@@ -274,6 +417,32 @@ pub fn build_cli() -> Command<'static> {
                         .help("Policy URI")
                 )
         )
+        .subcommand(
+            Command::new("store")
+                .about("Manage the local policy store")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("verify")
+                        .about("Verify the integrity of all the policies of the store, reporting the corrupted ones and the ones with invalid metadata")
+                        .arg(
+                            Arg::new("prune")
+                            .long("prune")
+                            .help("Remove the corrupted policies from the store, including the temporary files left by pulls interrupted more than one hour ago")
+                        )
+                        .arg(
+                            Arg::new("sources-path")
+                            .long("sources-path")
+                            .takes_value(true)
+                            .help("YAML file holding source information (https, registry insecure hosts, custom CA's...)")
+                        )
+                        .arg(
+                            Arg::new("docker-config-json-path")
+                            .long("docker-config-json-path")
+                            .takes_value(true)
+                            .help("Path to a Docker config.json-like path. Can be used to indicate registry authentication details")
+                        )
+                )
+        )
         .subcommand(
             Command::new("run")
                 .about("Runs a Kubewarden policy from a given URI")
@@ -283,6 +452,14 @@ pub fn build_cli() -> Command<'static> {
                     .takes_value(true)
                     .help("Path to a Docker config.json-like path. Can be used to indicate registry authentication details")
                 )
+                .arg(
+                    Arg::new("registry-auth")
+                    .long("registry-auth")
+                    .takes_value(true)
+                    .value_name("USER:PASSWORD")
+                    .conflicts_with("docker-config-json-path")
+                    .help("Credentials to be used to authenticate against the registry of the policy, instead of a Docker config.json file. A registry token can be given as password")
+                )
                 .arg(
                     Arg::new("sources-path")
                     .long("sources-path")
@@ -299,16 +476,69 @@ pub fn build_cli() -> Command<'static> {
                     Arg::new("request-path")
                     .long("request-path")
                     .short('r')
-                    .required(true)
+                    .required_unless_present("object")
                     .takes_value(true)
-                    .help("File containing the Kubernetes admission request object in JSON format")
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .help("File containing the Kubernetes admission request object. Use - to read it from stdin. Can be repeated to evaluate many requests, in which case a summary of the evaluations is printed at the end")
+                )
+                .arg(
+                    Arg::new("object")
+                    .long("object")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .conflicts_with_all(&["request-path", "request-format", "watch"])
+                    .help("File containing a raw Kubernetes object, like a Pod, in JSON or YAML format. The object is wrapped into an AdmissionReview asking to create it, inferring its group, version and kind")
+                )
+                .arg(
+                    Arg::new("operation")
+                    .long("operation")
+                    .takes_value(true)
+                    .possible_values(&["CREATE", "UPDATE", "DELETE", "CONNECT"])
+                    .requires("object")
+                    .help("Operation of the request built from the object given via --object. Defaults to CREATE")
+                )
+                .arg(
+                    Arg::new("output")
+                    .long("output")
+                    .takes_value(true)
+                    .possible_values(&["json", "yaml"])
+                    .default_value("json")
+                    .help("Format of the summary printed when many requests are evaluated")
+                )
+                .arg(
+                    Arg::new("watch")
+                    .long("watch")
+                    .help("Evaluate the request again every time the request file or the settings files change. The policy is loaded only once. Press Ctrl-C to stop")
+                )
+                .arg(
+                    Arg::new("show-responses")
+                    .long("show-responses")
+                    .help("Print the response of each request when many requests are evaluated. By default only the summary is printed")
+                )
+                .arg(
+                    Arg::new("request-format")
+                    .long("request-format")
+                    .takes_value(true)
+                    .possible_values(&["json", "yaml"])
+                    .default_value("json")
+                    .help("Format of the Kubernetes admission request object. When not provided, files with a .yaml or .yml extension are read as YAML")
+                )
+                .arg(
+                    Arg::new("crd")
+                    .long("crd")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("YAML file holding the CustomResourceDefinition of the evaluated object. The object is validated against the CRD schema, and the group, version, kind and resource of the request are taken from the CRD")
                 )
                 .arg(
                     Arg::new("settings-path")
                     .long("settings-path")
                     .short('s')
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
                     .takes_value(true)
-                    .help("File containing the settings for this policy")
+                    .help("File containing the settings for this policy. Can be repeated multiple times: the files are deep merged in order, later ones override the earlier ones")
                 )
                 .arg(
                     Arg::new("settings-json")
@@ -380,6 +610,84 @@ pub fn build_cli() -> Command<'static> {
                     .takes_value(true)
                     .help("GitHub repository expected in the certificates generated in CD pipelines")
                 )
+                .arg(
+                    Arg::new("output-file")
+                    .long("output-file")
+                    .takes_value(true)
+                    .help("Write the evaluation response to this file instead of printing it to stdout. The file is written atomically")
+                )
+                .arg(
+                    Arg::new("show-validation-only")
+                    .long("show-validation-only")
+                    .conflicts_with("show-mutation-only")
+                    .help("Report only the validation outcome of the evaluation, leaving out the mutation patch")
+                )
+                .arg(
+                    Arg::new("show-mutation-only")
+                    .long("show-mutation-only")
+                    .help("Report only the mutation patch produced by the evaluation, decoded")
+                )
+                .arg(
+                    Arg::new("template")
+                    .long("template")
+                    .takes_value(true)
+                    .value_name("TEMPLATE")
+                    .conflicts_with_all(&["quiet", "show-validation-only", "show-mutation-only"])
+                    .help("Print the evaluation response using a Go-like template, e.g. '{{ .allowed }} {{ .status.message }}'. See the README for the available fields")
+                )
+                .arg(
+                    Arg::new("save-evaluator-tracing-logs")
+                    .long("save-evaluator-tracing-logs")
+                    .takes_value(true)
+                    .min_values(0)
+                    .require_equals(true)
+                    .default_missing_value("-")
+                    .value_name("FILE")
+                    .help("Capture the tracing events of the policy evaluator, including the log messages sent by the policy through the host callbacks, while evaluating the requests. The logs are printed to the standard error of kwctl, or appended to FILE when given via --save-evaluator-tracing-logs=FILE. What the policy writes to its WASI standard output and standard error is not captured")
+                )
+                .arg(
+                    Arg::new("namespace-labels")
+                    .long("namespace-labels")
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .value_name("KEY=VALUE")
+                    .help("Label of the namespace of the request, added to the request inside of the `namespaceObject` field. Can be repeated multiple times")
+                )
+                .arg(
+                    Arg::new("user")
+                    .long("user")
+                    .takes_value(true)
+                    .value_name("USERNAME")
+                    .help("Name of the user making the request, set inside of the `userInfo` field of the request")
+                )
+                .arg(
+                    Arg::new("group")
+                    .long("group")
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .value_name("GROUP")
+                    .help("Group of the user making the request, set inside of the `userInfo` field of the request. Can be repeated multiple times")
+                )
+                .arg(
+                    Arg::new("expect-mutation")
+                    .long("expect-mutation")
+                    .conflicts_with("expect-no-mutation")
+                    .help("Fail when the policy doesn't mutate the request")
+                )
+                .arg(
+                    Arg::new("expect-no-mutation")
+                    .long("expect-no-mutation")
+                    .help("Fail when the policy mutates the request")
+                )
+                .arg(
+                    Arg::new("quiet")
+                    .long("quiet")
+                    .short('q')
+                    .conflicts_with_all(&["show-validation-only", "show-mutation-only"])
+                    .help("Print only the decision: ALLOWED, or DENIED followed by the rejection message")
+                )
                 .arg(
                     Arg::new("execution-mode")
                     .long("execution-mode")
@@ -395,6 +703,64 @@ pub fn build_cli() -> Command<'static> {
                         .help("Policy URI. Supported schemes: registry://, https://, file://. If schema is omitted, file:// is assumed, rooted on the current directory")
                 )
         )
+        .subcommand(
+            Command::new("test")
+                .about("Runs the fixtures of a directory against a policy, comparing the actual responses with the expected ones")
+                .arg(
+                    Arg::new("docker-config-json-path")
+                    .long("docker-config-json-path")
+                    .takes_value(true)
+                    .help("Path to a Docker config.json-like path. Can be used to indicate registry authentication details")
+                )
+                .arg(
+                    Arg::new("registry-auth")
+                    .long("registry-auth")
+                    .takes_value(true)
+                    .value_name("USER:PASSWORD")
+                    .conflicts_with("docker-config-json-path")
+                    .help("Credentials to be used to authenticate against the registry of the policy, instead of a Docker config.json file. A registry token can be given as password")
+                )
+                .arg(
+                    Arg::new("sources-path")
+                    .long("sources-path")
+                    .takes_value(true)
+                    .help("YAML file holding source information (https, registry insecure hosts, custom CA's...)")
+                )
+                .arg(
+                    Arg::new("execution-mode")
+                    .long("execution-mode")
+                    .short('e')
+                    .takes_value(true)
+                    .possible_values(&["opa","gatekeeper", "kubewarden"])
+                    .help("The runtime to use to execute this policy")
+                )
+                .arg(
+                    Arg::new("output")
+                    .long("output")
+                    .short('o')
+                    .takes_value(true)
+                    .possible_values(&["pretty", "json"])
+                    .default_value("pretty")
+                    .help("Output format of the report")
+                )
+                .arg(
+                    Arg::new("no-color")
+                    .long("no-color")
+                    .help("Do not use colors when printing the report")
+                )
+                .arg(
+                    Arg::new("dir")
+                        .required(true)
+                        .index(1)
+                        .help("Directory holding the fixtures: pairs of <name>.request.json and <name>.response.json files (YAML is accepted too), plus an optional settings.yaml file with the settings of the policy")
+                )
+                .arg(
+                    Arg::new("uri")
+                        .required(true)
+                        .index(2)
+                        .help("Policy URI. Supported schemes: registry://, https://, file://. If schema is omitted, file:// is assumed, rooted on the current directory.")
+                )
+        )
         .subcommand(
             Command::new("annotate")
                 .about("Add Kubewarden metadata to a WebAssembly module")
@@ -414,6 +780,16 @@ pub fn build_cli() -> Command<'static> {
                     .takes_value(true)
                     .help("Output file")
                 )
+                .arg(
+                    Arg::new("annotate-from-cargo")
+                    .long("annotate-from-cargo")
+                    .takes_value(true)
+                    .min_values(0)
+                    .require_equals(true)
+                    .default_missing_value("Cargo.toml")
+                    .value_name("CARGO_TOML")
+                    .help("Take the title, description, author, url and license annotations not defined by the metadata file from the [package] section of the Cargo.toml of the policy. Defaults to the Cargo.toml of the current directory, use --annotate-from-cargo=PATH to read another one")
+                )
                 .arg(
                     Arg::new("wasm-path")
                     .required(true)
@@ -429,12 +805,188 @@ pub fn build_cli() -> Command<'static> {
                     .long("output")
                     .short('o')
                     .takes_value(true)
-                    .possible_values(&["yaml"])
+                    .possible_values(&["yaml", "json"])
                     .help("Output format")
                 )
+                .arg(
+                    Arg::new("bundle")
+                    .long("bundle")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .conflicts_with("uri")
+                    .help("YAML file listing the policies to be inspected, under the `policies` key. Prints a summary of all of them")
+                )
+                .arg(
+                    Arg::new("annotation")
+                    .long("annotation")
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .help("Print only the value of the given metadata annotation (e.g. io.kubewarden.policy.title). Can be repeated multiple times")
+                )
+                .arg(
+                    Arg::new("accept-media-type")
+                    .long("accept-media-type")
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .help("Additional media type to accept when fetching the signatures manifest from the registry, like the OCI artifact manifest one. Manifests of other types must have the structure of an image manifest. Can be repeated multiple times")
+                )
+                .arg(
+                    Arg::new("raw-metadata")
+                    .long("raw-metadata")
+                    .conflicts_with_all(&["bundle", "annotation", "show-signers", "strict-signatures"])
+                    .help("Print the contents of the custom section of the Wasm module holding the Kubewarden metadata, as they are stored")
+                )
+                .arg(
+                    Arg::new("artifact-type")
+                    .long("artifact-type")
+                    .takes_value(true)
+                    .value_name("TYPE")
+                    .conflicts_with_all(&["bundle", "raw-metadata", "annotation", "show-signers", "strict-signatures"])
+                    .help("List the artifacts of the given type referring to the policy, discovered via the OCI Referrers API. Accepts a media type or one of the aliases: signature, attestation, sbom, cyclonedx, notation")
+                )
+                .arg(
+                    Arg::new("dump-wasm")
+                    .long("dump-wasm")
+                    .takes_value(true)
+                    .value_name("PATH")
+                    .conflicts_with_all(&["bundle", "raw-metadata", "annotation", "show-signers", "strict-signatures"])
+                    .help("Copy the WebAssembly module of the policy to the given path")
+                )
+                .arg(
+                    Arg::new("force")
+                    .long("force")
+                    .requires("dump-wasm")
+                    .help("Overwrite the destination of --dump-wasm when it already exists")
+                )
+                .arg(
+                    Arg::new("compat")
+                    .long("compat")
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .value_name("MODE")
+                    .possible_values(&["kubewarden-wapc", "opa", "gatekeeper"])
+                    .conflicts_with_all(&["bundle", "raw-metadata", "annotation", "show-signers", "strict-signatures", "dump-wasm", "template", "artifact-type", "check-rules", "size-breakdown"])
+                    .help("Fail unless the execution mode of the policy is the given one. Can be repeated to accept multiple execution modes")
+                )
+                .arg(
+                    Arg::new("check-rules")
+                    .long("check-rules")
+                    .conflicts_with_all(&["bundle", "raw-metadata", "annotation", "show-signers", "strict-signatures"])
+                    .help("Report invalid entries inside of the rules of the policy (apiGroups, apiVersions, resources and operations), which would never match a request")
+                )
+                .arg(
+                    Arg::new("size-breakdown")
+                    .long("size-breakdown")
+                    .conflicts_with_all(&["bundle", "raw-metadata", "annotation", "show-signers", "strict-signatures"])
+                    .help("Print the media type and the size of each layer of the manifest of the policy, plus the total size. Available only for policies stored inside of a registry")
+                )
+                .arg(
+                    Arg::new("template")
+                    .long("template")
+                    .takes_value(true)
+                    .value_name("TEMPLATE")
+                    .conflicts_with_all(&["output", "bundle", "raw-metadata", "annotation", "size-breakdown", "show-signers", "strict-signatures"])
+                    .help("Print the metadata of the policy using a Go-like template, e.g. '{{ .annotations.title }} {{ .mutating }}'. See the README for the available fields")
+                )
+                .arg(
+                    Arg::new("allow-remote-resources")
+                    .long("allow-remote-resources")
+                    .conflicts_with_all(&["output", "bundle", "raw-metadata"])
+                    .help("Fetch the remote resources, like images, referenced by the usage documentation of the policy, to render them in capable terminals. This causes network requests to the hosts chosen by the policy author")
+                )
+                .arg(
+                    Arg::new("show-signers")
+                    .long("show-signers")
+                    .help("Print a summary of the identities that signed the policy, decoded from the certificates of keyless signatures")
+                )
+                .arg(
+                    Arg::new("allow-missing-annotations")
+                    .long("allow-missing-annotations")
+                    .requires("annotation")
+                    .help("Report the selected annotations that are not defined by the policy with an empty value, instead of failing")
+                )
+                .arg(
+                    Arg::new("strict-signatures")
+                    .long("strict-signatures")
+                    .conflicts_with("bundle")
+                    .help("Fail when the policy is not signed, or when its signatures cannot be fetched. When verification options are given, the signatures must also satisfy them")
+                )
+                .arg(
+                    Arg::new("verification-config-path")
+                    .long("verification-config-path")
+                    .takes_value(true)
+                    .help("YAML file holding verification config information (signatures, public keys...)")
+                )
+                .arg(
+                    Arg::new("verification-key")
+                    .short('k')
+                    .long("verification-key")
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .help("Path to key used to verify the policy. Can be repeated multiple times")
+                )
+                .arg(
+                    Arg::new("fulcio-cert-path")
+                    .long("fulcio-cert-path")
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .help("Path to the Fulcio certificate. Can be repeated multiple times")
+                )
+                .arg(
+                    Arg::new("rekor-public-key-path")
+                    .long("rekor-public-key-path")
+                    .takes_value(true)
+                    .help("Path to the Rekor public key")
+                )
+                .arg(
+                    Arg::new("verification-annotation")
+                    .short('a')
+                    .long("verification-annotation")
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .help("Annotation in key=value format. Can be repeated multiple times")
+                )
+                .arg(
+                    Arg::new("cert-email")
+                    .long("cert-email")
+                    .multiple_occurrences(false)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .help("Expected email in Fulcio certificate")
+                )
+                .arg(
+                    Arg::new("cert-oidc-issuer")
+                    .long("cert-oidc-issuer")
+                    .multiple_occurrences(false)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .help("Expected OIDC issuer in Fulcio certificates")
+                )
+                .arg(
+                    Arg::new("github-owner")
+                    .long("github-owner")
+                    .multiple_occurrences(false)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .help("GitHub owner expected in the certificates generated in CD pipelines")
+                )
+                .arg(
+                    Arg::new("github-repo")
+                    .long("github-repo")
+                    .multiple_occurrences(false)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .help("GitHub repository expected in the certificates generated in CD pipelines")
+                )
                 .arg(
                     Arg::new("uri")
-                        .required(true)
+                        .required_unless_present("bundle")
                         .index(1)
                         .help("Policy URI. Supported schemes: registry://, https://, file://")
                 )
@@ -450,6 +1002,32 @@ pub fn build_cli() -> Command<'static> {
                         .takes_value(true)
                         .help("Path to a Docker config.json-like path. Can be used to indicate registry authentication details")
                 )
+                .arg(
+                    Arg::new("registry-auth")
+                    .long("registry-auth")
+                    .takes_value(true)
+                    .value_name("USER:PASSWORD")
+                    .conflicts_with("docker-config-json-path")
+                    .help("Credentials to be used to authenticate against the registry of the policy, instead of a Docker config.json file. A registry token can be given as password")
+                )
+        )
+        .subcommand(
+            Command::new("validate-metadata")
+                .about("Validate the metadata of a Kubewarden policy, ensuring the required annotations are defined")
+                .arg(
+                    Arg::new("required-annotation")
+                    .long("required-annotation")
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .takes_value(true)
+                    .help("Annotation that must be defined with a non-empty value. Both full (io.kubewarden.policy.title) and short (title) names are accepted. Can be repeated multiple times. Defaults to: title, description, author, url, license")
+                )
+                .arg(
+                    Arg::new("uri")
+                        .required(true)
+                        .index(1)
+                        .help("Policy URI. Supported schemes: registry://, https://, file://. If schema is omitted, file:// is assumed, rooted on the current directory")
+                )
         )
         .subcommand(
             Command::new("scaffold")
@@ -532,6 +1110,14 @@ pub fn build_cli() -> Command<'static> {
                         .takes_value(true)
                         .help("Path to a Docker config.json-like path. Can be used to indicate registry authentication details")
                 )
+                .arg(
+                    Arg::new("registry-auth")
+                        .long("registry-auth")
+                        .takes_value(true)
+                        .value_name("USER:PASSWORD")
+                        .conflicts_with("docker-config-json-path")
+                        .help("Credentials to be used to authenticate against the registry of the policy, instead of a Docker config.json file. A registry token can be given as password")
+                )
         )
         .long_version(VERSION_AND_BUILTINS.as_str())
         .subcommand_required(true)
@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Result};
+use policy_evaluator::policy_fetcher::store::DEFAULT_ROOT;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+const KWCTL_CONFIG: &str = "config.yaml";
+
+/// Defaults for the options shared by many commands. The values provided
+/// via CLI flags take precedence over the ones of the config file, which
+/// take precedence over the files found at the default locations.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Default value of `--sources-path`
+    pub sources_path: Option<PathBuf>,
+    /// Default value of `--docker-config-json-path`
+    pub docker_config_json_path: Option<PathBuf>,
+    /// Default value of `--verification-config-path`
+    pub verification_config_path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Loads the config file at `path`. When no path is given, the
+    /// `config.yaml` file of the kwctl config directory is loaded, if it
+    /// exists.
+    pub(crate) fn load(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::from_file(path),
+            None => {
+                let default_path = DEFAULT_ROOT.config_dir().join(KWCTL_CONFIG);
+                if default_path.exists() {
+                    info!(path = ?default_path, "Default config file present, using it");
+                    Self::from_file(&default_path)
+                } else {
+                    Ok(Config::default())
+                }
+            }
+        }
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Cannot read config file {}: {}", path.display(), e))?;
+        Self::from_yaml(&contents)
+            .map_err(|e| anyhow!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    fn from_yaml(contents: &str) -> Result<Self> {
+        if contents.trim().is_empty() {
+            return Ok(Config::default());
+        }
+        serde_yaml::from_str(contents).map_err(|e| anyhow!("{}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_yaml() -> Result<()> {
+        let config = Config::from_yaml(
+            r#"
+sources_path: /etc/kwctl/sources.yaml
+docker_config_json_path: /etc/kwctl/docker-config.json
+"#,
+        )?;
+        assert_eq!(
+            config,
+            Config {
+                sources_path: Some(PathBuf::from("/etc/kwctl/sources.yaml")),
+                docker_config_json_path: Some(PathBuf::from("/etc/kwctl/docker-config.json")),
+                verification_config_path: None,
+            }
+        );
+
+        assert_eq!(Config::from_yaml("")?, Config::default());
+        assert!(Config::from_yaml("sources: /etc/kwctl/sources.yaml").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_config_file() {
+        assert!(Config::load(Some(Path::new("/does/not/exist/config.yaml"))).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{anyhow, Result};
+use policy_evaluator::{policy_evaluator::PolicyExecutionMode, policy_metadata::Metadata};
+use std::{collections::BTreeSet, path::Path};
+
+/// Function exported by the Wasm modules of waPC policies
+const WAPC_ENTRYPOINT: &str = "__guest_call";
+
+/// Function exported by the Wasm modules built from Rego
+const OPA_ENTRYPOINT: &str = "opa_eval_ctx_new";
+
+/// Compares the metadata of the policy with the functions exported by its
+/// Wasm module, returning a warning for each inconsistency found. These are
+/// policies that have been annotated with the wrong metadata, and that
+/// would behave unexpectedly once deployed.
+pub(crate) fn consistency_warnings(metadata: &Metadata, wasm_path: &Path) -> Result<Vec<String>> {
+    let wasm = std::fs::read(wasm_path)
+        .map_err(|e| anyhow!("Cannot read policy {}: {}", wasm_path.display(), e))?;
+    Ok(warnings(metadata, &exported_functions(&wasm)?))
+}
+
+fn exported_functions(wasm: &[u8]) -> Result<BTreeSet<String>> {
+    let mut exports = BTreeSet::new();
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        if let wasmparser::Payload::ExportSection(section) = payload? {
+            for export in section {
+                let export = export?;
+                if export.kind == wasmparser::ExternalKind::Function {
+                    exports.insert(String::from(export.field));
+                }
+            }
+        }
+    }
+
+    Ok(exports)
+}
+
+fn warnings(metadata: &Metadata, exports: &BTreeSet<String>) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    let entrypoint = match metadata.execution_mode {
+        PolicyExecutionMode::KubewardenWapc => ("waPC", WAPC_ENTRYPOINT),
+        PolicyExecutionMode::Opa | PolicyExecutionMode::OpaGatekeeper => ("OPA", OPA_ENTRYPOINT),
+    };
+    if !exports.contains(entrypoint.1) {
+        warnings.push(format!(
+            "execution mode is {}, but the module doesn't export the {} entrypoint `{}`",
+            metadata.execution_mode, entrypoint.0, entrypoint.1
+        ));
+    }
+
+    if metadata.mutating && !can_mutate(&metadata.execution_mode) {
+        warnings.push(format!(
+            "the policy claims to be mutating, but policies of execution mode {} cannot mutate requests",
+            metadata.execution_mode
+        ));
+    }
+
+    warnings
+}
+
+// Whether the policies of the given execution mode can mutate requests.
+// Rego policies can only validate them. waPC policies return the mutated
+// object from the `validate` function they register at runtime, which is
+// not an export of the module: whether a waPC policy mutates requests, or
+// not, cannot be told by looking at its module.
+fn can_mutate(execution_mode: &PolicyExecutionMode) -> bool {
+    match execution_mode {
+        PolicyExecutionMode::KubewardenWapc => true,
+        PolicyExecutionMode::Opa | PolicyExecutionMode::OpaGatekeeper => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exports(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| String::from(*name)).collect()
+    }
+
+    #[test]
+    fn test_consistent_policy() {
+        let metadata = Metadata {
+            mutating: true,
+            execution_mode: PolicyExecutionMode::KubewardenWapc,
+            ..Default::default()
+        };
+        assert!(warnings(&metadata, &exports(&["__guest_call"])).is_empty());
+    }
+
+    #[test]
+    fn test_inconsistent_policies() {
+        let metadata = Metadata {
+            mutating: true,
+            execution_mode: PolicyExecutionMode::Opa,
+            ..Default::default()
+        };
+        assert_eq!(
+            warnings(&metadata, &exports(&["__guest_call"])).len(),
+            2,
+            "a waPC module annotated as a mutating Rego policy"
+        );
+
+        let metadata = Metadata {
+            mutating: true,
+            execution_mode: PolicyExecutionMode::OpaGatekeeper,
+            ..Default::default()
+        };
+        assert_eq!(
+            warnings(&metadata, &exports(&["opa_eval_ctx_new"])),
+            vec![format!(
+                "the policy claims to be mutating, but policies of execution mode {} cannot mutate requests",
+                PolicyExecutionMode::OpaGatekeeper
+            )]
+        );
+    }
+
+    #[test]
+    fn test_wapc_policies_can_be_mutating_or_not() {
+        for mutating in &[true, false] {
+            let metadata = Metadata {
+                mutating: *mutating,
+                execution_mode: PolicyExecutionMode::KubewardenWapc,
+                ..Default::default()
+            };
+            assert!(warnings(&metadata, &exports(&["__guest_call"])).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_exported_functions() -> Result<()> {
+        let wasm = wat::parse_str(r#"(module (func (export "__guest_call")))"#)?;
+
+        assert_eq!(exported_functions(&wasm)?, exports(&["__guest_call"]));
+        Ok(())
+    }
+}
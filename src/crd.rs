@@ -0,0 +1,430 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+/// The parts of a `CustomResourceDefinition` used to build and validate the
+/// requests evaluated by `kwctl run`
+#[derive(Debug)]
+pub(crate) struct CustomResourceDefinition {
+    pub group: String,
+    pub kind: String,
+    /// The name of the resource, like `crontabs`
+    pub plural: String,
+    pub versions: Vec<CustomResourceVersion>,
+}
+
+#[derive(Debug)]
+pub(crate) struct CustomResourceVersion {
+    pub name: String,
+    pub served: bool,
+    /// The `openAPIV3Schema` of the version
+    pub schema: Option<Value>,
+}
+
+impl CustomResourceDefinition {
+    /// Loads a `apiextensions.k8s.io/v1` `CustomResourceDefinition`
+    pub(crate) fn from_yaml(crd: &str) -> Result<Self> {
+        let crd: Value =
+            serde_yaml::from_str(crd).map_err(|e| anyhow!("Invalid CRD YAML: {}", e))?;
+        if crd.get("kind").and_then(Value::as_str) != Some("CustomResourceDefinition") {
+            return Err(anyhow!("The given file is not a CustomResourceDefinition"));
+        }
+
+        let spec = crd
+            .get("spec")
+            .ok_or_else(|| anyhow!("Invalid CRD: spec not defined"))?;
+        let group = spec
+            .get("group")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Invalid CRD: spec.group not defined"))?;
+        let kind = spec
+            .pointer("/names/kind")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Invalid CRD: spec.names.kind not defined"))?;
+        let plural = spec
+            .pointer("/names/plural")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Invalid CRD: spec.names.plural not defined"))?;
+        let versions = spec
+            .get("versions")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("Invalid CRD: spec.versions not defined"))?
+            .iter()
+            .map(|version| {
+                let name = version
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| anyhow!("Invalid CRD: version without name"))?;
+                Ok(CustomResourceVersion {
+                    name: String::from(name),
+                    served: version
+                        .get("served")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(true),
+                    schema: version.pointer("/schema/openAPIV3Schema").cloned(),
+                })
+            })
+            .collect::<Result<Vec<CustomResourceVersion>>>()?;
+
+        Ok(CustomResourceDefinition {
+            group: String::from(group),
+            kind: String::from(kind),
+            plural: String::from(plural),
+            versions,
+        })
+    }
+
+    /// Sets the group, version, kind and resource of the admission request
+    /// to the ones of the custom resource, after ensuring the objects of the
+    /// request are valid according to the schema of the CRD. The version is
+    /// the one of the objects.
+    pub(crate) fn complete_request(&self, request: &mut Value) -> Result<()> {
+        if let Some(request_kind) = request.get("kind") {
+            let group = request_kind
+                .get("group")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let kind = request_kind
+                .get("kind")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            if group != self.group || kind != self.kind {
+                return Err(anyhow!(
+                    "The request is about '{}' of group '{}', while the CRD defines '{}' of group '{}'",
+                    kind,
+                    group,
+                    self.kind,
+                    self.group
+                ));
+            }
+        }
+
+        // the object is not set by DELETE requests, the old one by CREATE
+        let mut version = None;
+        for field in &["object", "oldObject"] {
+            if let Some(object) = request.get(field).filter(|object| !object.is_null()) {
+                version = Some(self.validate_object(object)?);
+            }
+        }
+        let version = version
+            .ok_or_else(|| anyhow!("The request holds neither an object nor an oldObject"))?;
+
+        let group_version_kind =
+            json!({"group": self.group, "version": version, "kind": self.kind});
+        let group_version_resource =
+            json!({"group": self.group, "version": version, "resource": self.plural});
+        let request = request
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("The admission request is not an object"))?;
+        request.insert(String::from("kind"), group_version_kind.clone());
+        request.insert(String::from("requestKind"), group_version_kind);
+        request.insert(String::from("resource"), group_version_resource.clone());
+        request.insert(String::from("requestResource"), group_version_resource);
+        Ok(())
+    }
+
+    // Validates the object against the schema of its version, returning
+    // the name of the version
+    fn validate_object(&self, object: &Value) -> Result<String> {
+        let api_version = object
+            .get("apiVersion")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let kind = object
+            .get("kind")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if kind != self.kind {
+            return Err(anyhow!(
+                "The object is of kind '{}', while the CRD defines '{}'",
+                kind,
+                self.kind
+            ));
+        }
+
+        let version = api_version
+            .strip_prefix(&format!("{}/", self.group))
+            .and_then(|version| self.versions.iter().find(|v| v.served && v.name == version))
+            .ok_or_else(|| {
+                anyhow!(
+                    "The apiVersion '{}' of the object is not served by the CRD",
+                    api_version
+                )
+            })?;
+
+        let schema = match &version.schema {
+            Some(schema) => schema,
+            None => return Ok(version.name.clone()),
+        };
+        let mut errors: Vec<String> = Vec::new();
+        validate_schema(object, schema, "", true, &mut errors);
+        if errors.is_empty() {
+            Ok(version.name.clone())
+        } else {
+            Err(anyhow!(
+                "The object is not valid according to the CRD schema:\n{}",
+                errors.join("\n")
+            ))
+        }
+    }
+}
+
+// Validates `value` against the given OpenAPI v3 structural schema, adding
+// the violations found to `errors`. Only the subset of OpenAPI used by CRDs
+// is supported.
+fn validate_schema(
+    value: &Value,
+    schema: &Value,
+    path: &str,
+    root: bool,
+    errors: &mut Vec<String>,
+) {
+    let field = if path.is_empty() { "<root>" } else { path };
+
+    if value.is_null() {
+        if !schema
+            .get("nullable")
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+        {
+            errors.push(format!("{}: must not be null", field));
+        }
+        return;
+    }
+
+    if schema
+        .get("x-kubernetes-int-or-string")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+    {
+        if !value.is_i64() && !value.is_u64() && !value.is_string() {
+            errors.push(format!("{}: must be an integer or a string", field));
+        }
+        return;
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            errors.push(format!(
+                "{}: {} is not one of the allowed values",
+                field, value
+            ));
+        }
+    }
+
+    match schema.get("type").and_then(Value::as_str) {
+        Some("object") => validate_object_schema(value, schema, path, root, errors),
+        Some("array") => match value.as_array() {
+            Some(items) => {
+                if let Some(items_schema) = schema.get("items") {
+                    for (index, item) in items.iter().enumerate() {
+                        validate_schema(
+                            item,
+                            items_schema,
+                            &format!("{}[{}]", path, index),
+                            false,
+                            errors,
+                        );
+                    }
+                }
+            }
+            None => errors.push(format!("{}: must be an array", field)),
+        },
+        Some("string") if !value.is_string() => errors.push(format!("{}: must be a string", field)),
+        Some("integer") if !value.is_i64() && !value.is_u64() => {
+            errors.push(format!("{}: must be an integer", field))
+        }
+        Some("number") if !value.is_number() => errors.push(format!("{}: must be a number", field)),
+        Some("boolean") if !value.is_boolean() => {
+            errors.push(format!("{}: must be a boolean", field))
+        }
+        _ => {}
+    }
+}
+
+fn validate_object_schema(
+    value: &Value,
+    schema: &Value,
+    path: &str,
+    root: bool,
+    errors: &mut Vec<String>,
+) {
+    let field = if path.is_empty() { "<root>" } else { path };
+    let object = match value.as_object() {
+        Some(object) => object,
+        None => {
+            errors.push(format!("{}: must be an object", field));
+            return;
+        }
+    };
+
+    if let Some(required) = schema.get("required").and_then(Value::as_array) {
+        for key in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(key) {
+                errors.push(format!("{}: required field '{}' is missing", field, key));
+            }
+        }
+    }
+
+    let preserve_unknown_fields = schema
+        .get("x-kubernetes-preserve-unknown-fields")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let properties = schema.get("properties").and_then(Value::as_object);
+    let additional_properties = schema.get("additionalProperties");
+
+    for (key, field_value) in object.iter() {
+        let field_path = if path.is_empty() {
+            String::from(key)
+        } else {
+            format!("{}.{}", path, key)
+        };
+        match properties.and_then(|properties| properties.get(key)) {
+            Some(field_schema) => {
+                validate_schema(field_value, field_schema, &field_path, false, errors)
+            }
+            None => match additional_properties {
+                Some(Value::Bool(true)) => {}
+                Some(Value::Bool(false)) => errors.push(format!("{}: unknown field", field_path)),
+                Some(field_schema) => {
+                    validate_schema(field_value, field_schema, &field_path, false, errors)
+                }
+                None => {
+                    // apiVersion, kind and metadata are implicitly allowed
+                    // at the top level of the resource
+                    let implicit_field =
+                        root && ["apiVersion", "kind", "metadata"].contains(&key.as_str());
+                    if !preserve_unknown_fields && !implicit_field {
+                        errors.push(format!("{}: unknown field", field_path));
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRD: &str = r#"
+apiVersion: apiextensions.k8s.io/v1
+kind: CustomResourceDefinition
+metadata:
+  name: crontabs.stable.example.com
+spec:
+  group: stable.example.com
+  names:
+    kind: CronTab
+    plural: crontabs
+  scope: Namespaced
+  versions:
+    - name: v1
+      served: true
+      storage: true
+      schema:
+        openAPIV3Schema:
+          type: object
+          properties:
+            spec:
+              type: object
+              required: ["cronSpec"]
+              properties:
+                cronSpec:
+                  type: string
+                replicas:
+                  type: integer
+                labels:
+                  type: object
+                  additionalProperties:
+                    type: string
+"#;
+
+    fn request(object: Value) -> Value {
+        json!({
+            "uid": "1299d386-525b-4032-98ae-1949f69f9cfc",
+            "kind": {"group": "stable.example.com", "version": "v1", "kind": "CronTab"},
+            "operation": "CREATE",
+            "object": object,
+        })
+    }
+
+    #[test]
+    fn test_valid_object() -> Result<()> {
+        let crd = CustomResourceDefinition::from_yaml(CRD)?;
+        let mut request = request(json!({
+            "apiVersion": "stable.example.com/v1",
+            "kind": "CronTab",
+            "metadata": {"name": "my-cron"},
+            "spec": {"cronSpec": "* * * * */5", "replicas": 2, "labels": {"app": "cron"}}
+        }));
+        crd.complete_request(&mut request)?;
+
+        let resource =
+            json!({"group": "stable.example.com", "version": "v1", "resource": "crontabs"});
+        assert_eq!(request["resource"], resource);
+        assert_eq!(request["requestResource"], resource);
+        assert_eq!(request["requestKind"], request["kind"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_request_without_kind() -> Result<()> {
+        let crd = CustomResourceDefinition::from_yaml(CRD)?;
+        // like the requests built from an object being deleted
+        let mut request = json!({
+            "operation": "DELETE",
+            "object": null,
+            "oldObject": {
+                "apiVersion": "stable.example.com/v1",
+                "kind": "CronTab",
+                "spec": {"cronSpec": "* * * * */5"}
+            },
+        });
+        crd.complete_request(&mut request)?;
+
+        assert_eq!(
+            request["kind"],
+            json!({"group": "stable.example.com", "version": "v1", "kind": "CronTab"})
+        );
+        assert_eq!(request["resource"]["resource"], "crontabs");
+        assert!(crd
+            .complete_request(&mut json!({"operation": "CREATE"}))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_object() -> Result<()> {
+        let crd = CustomResourceDefinition::from_yaml(CRD)?;
+        let error = crd
+            .complete_request(&mut request(json!({
+                "apiVersion": "stable.example.com/v1",
+                "kind": "CronTab",
+                "metadata": {"name": "my-cron"},
+                "spec": {"replicas": "two", "image": "busybox", "labels": {"app": 1}}
+            })))
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.contains("spec: required field 'cronSpec' is missing"));
+        assert!(error.contains("spec.replicas: must be an integer"));
+        assert!(error.contains("spec.image: unknown field"));
+        assert!(error.contains("spec.labels.app: must be a string"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_of_another_kind() -> Result<()> {
+        let crd = CustomResourceDefinition::from_yaml(CRD)?;
+        let mut pod_request = json!({
+            "kind": {"group": "", "version": "v1", "kind": "Pod"},
+            "object": {"apiVersion": "v1", "kind": "Pod"},
+        });
+        assert!(crd.complete_request(&mut pod_request).is_err());
+
+        let mut unserved_version_request =
+            request(json!({"apiVersion": "stable.example.com/v2", "kind": "CronTab"}));
+        assert!(crd.complete_request(&mut unserved_version_request).is_err());
+        Ok(())
+    }
+}
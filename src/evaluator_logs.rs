@@ -0,0 +1,172 @@
+use anyhow::{anyhow, Result};
+use std::{
+    cell::RefCell,
+    fmt::{self, Write as _},
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+use tracing::{
+    field::{Field, Visit},
+    Event, Metadata, Subscriber,
+};
+use tracing_subscriber::{filter, layer::Context, registry::LookupSpan, Layer};
+
+/// Value of the `--save-evaluator-tracing-logs` flag meaning the logs are printed
+/// to the standard error of kwctl
+pub(crate) const STDERR_DESTINATION: &str = "-";
+
+/// Target of the log events the policies send to the host through the
+/// `kubewarden/tracing/log` waPC callback
+const POLICY_LOG_TARGET: &str = "policy_log";
+
+thread_local! {
+    // The logs of the evaluation running on the current thread, when they
+    // are being captured
+    static CAPTURED_LOGS: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Where the log messages emitted by the policy evaluator are saved
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum LogsDestination {
+    Stderr,
+    /// The logs of all the evaluations are appended to this file, which is
+    /// truncated when the destination is created
+    File(PathBuf),
+}
+
+impl LogsDestination {
+    pub(crate) fn new(value: &str) -> Result<Self> {
+        if value == STDERR_DESTINATION {
+            return Ok(LogsDestination::Stderr);
+        }
+        File::create(value)
+            .map_err(|e| anyhow!("Cannot create evaluator logs file {}: {}", value, e))?;
+        Ok(LogsDestination::File(PathBuf::from(value)))
+    }
+
+    pub(crate) fn save(&self, logs: &[u8]) -> Result<()> {
+        if logs.is_empty() {
+            return Ok(());
+        }
+        match self {
+            LogsDestination::Stderr => io::stderr().write_all(logs)?,
+            LogsDestination::File(path) => OpenOptions::new()
+                .append(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(logs))
+                .map_err(|e| anyhow!("Cannot write evaluator logs to {}: {}", path.display(), e))?,
+        }
+        Ok(())
+    }
+}
+
+/// Runs `f` capturing the log events emitted meanwhile by the policy
+/// evaluator on the current thread, like the ones the policy sends through
+/// its host callbacks. The events are recorded by the layer returned by
+/// `layer`, which must be part of the tracing subscriber.
+pub(crate) fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<u8>) {
+    CAPTURED_LOGS.with(|logs| *logs.borrow_mut() = Some(String::new()));
+    let result = f();
+    let logs = CAPTURED_LOGS
+        .with(|logs| logs.borrow_mut().take())
+        .unwrap_or_default();
+    (result, logs.into_bytes())
+}
+
+/// The tracing layer recording the events of the policy evaluator while
+/// `capture` is running. The events of kwctl, and the ones emitted by other
+/// threads, like the one of the callback handler, are ignored.
+pub(crate) fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    CaptureLayer.with_filter(filter::filter_fn(is_evaluator_event))
+}
+
+fn is_evaluator_event(metadata: &Metadata<'_>) -> bool {
+    metadata.target() == POLICY_LOG_TARGET || metadata.target().starts_with("policy_evaluator")
+}
+
+struct CaptureLayer;
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        CAPTURED_LOGS.with(|logs| {
+            if let Some(logs) = logs.borrow_mut().as_mut() {
+                let metadata = event.metadata();
+                let _ = write!(logs, "{} {}:", metadata.level(), metadata.target());
+                event.record(&mut LineVisitor(logs));
+                logs.push('\n');
+            }
+        });
+    }
+}
+
+// Writes the fields of an event on a single line, the message first
+struct LineVisitor<'a>(&'a mut String);
+
+impl Visit for LineVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn test_capture() {
+        let subscriber = tracing_subscriber::registry().with(layer());
+        let (result, logs) = tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("not captured: outside of the evaluation");
+            capture(|| {
+                tracing::info!(target: POLICY_LOG_TARGET, policy_id = "psp", "policy message");
+                tracing::debug!(target: "policy_evaluator::policy_evaluator", "evaluator message");
+                tracing::info!("not captured: emitted by kwctl");
+                42
+            })
+        });
+        assert_eq!(result, 42);
+        assert_eq!(
+            String::from_utf8(logs).unwrap(),
+            "INFO policy_log: policy message policy_id=\"psp\"\nDEBUG policy_evaluator::policy_evaluator: evaluator message\n"
+        );
+    }
+
+    #[test]
+    fn test_save_to_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("evaluator.log");
+        fs::write(&path, "previous run\n")?;
+
+        let destination = LogsDestination::new(path.to_str().unwrap())?;
+        destination.save(b"first evaluation\n")?;
+        destination.save(b"")?;
+        destination.save(b"second evaluation\n")?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "first evaluation\nsecond evaluation\n"
+        );
+        assert_eq!(
+            LogsDestination::new(STDERR_DESTINATION)?,
+            LogsDestination::Stderr
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+use anyhow::{anyhow, Result};
+use prettytable::{format, Cell, Row, Table};
+use serde::Serialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::run::{RequestFormat, RequestInput};
+
+/// Name of the optional file, inside of the fixtures directory, holding
+/// the settings of the policy
+pub(crate) const SETTINGS_FILE: &str = "settings.yaml";
+
+/// A request to be evaluated, together with the response the policy is
+/// expected to return
+pub(crate) struct Fixture {
+    pub name: String,
+    pub request: RequestInput,
+    pub expected_response: serde_json::Value,
+}
+
+/// The outcome of the evaluation of a fixture
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct FixtureResult {
+    name: String,
+    passed: bool,
+    /// The fields of the actual response not matching the expected ones
+    differences: Vec<String>,
+    /// Set when the request cannot be evaluated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl FixtureResult {
+    pub(crate) fn new(fixture: &Fixture, response: Result<serde_json::Value>) -> Self {
+        match response {
+            Ok(response) => {
+                let mut differences = Vec::new();
+                find_differences(
+                    &fixture.expected_response,
+                    &response,
+                    "response",
+                    &mut differences,
+                );
+                FixtureResult {
+                    name: fixture.name.clone(),
+                    passed: differences.is_empty(),
+                    differences,
+                    error: None,
+                }
+            }
+            Err(e) => FixtureResult {
+                name: fixture.name.clone(),
+                passed: false,
+                differences: Vec::new(),
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// The report of a `kwctl test` run
+#[derive(Debug, Serialize)]
+pub(crate) struct TestReport {
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    fixtures: Vec<FixtureResult>,
+}
+
+impl TestReport {
+    pub(crate) fn new(fixtures: Vec<FixtureResult>) -> Self {
+        let passed = fixtures.iter().filter(|fixture| fixture.passed).count();
+        TestReport {
+            total: fixtures.len(),
+            passed,
+            failed: fixtures.len() - passed,
+            fixtures,
+        }
+    }
+
+    pub(crate) fn print_json(&self) -> Result<()> {
+        println!("{}", serde_json::to_string_pretty(self)?);
+        Ok(())
+    }
+
+    /// Prints a table with the outcome of each fixture, followed by the
+    /// totals. Colors are used only when `color` is set, and stdout is a
+    /// terminal
+    pub(crate) fn print_table(&self, color: bool) {
+        if !self.fixtures.is_empty() {
+            let mut table = Table::new();
+            table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+            table.set_titles(row!["Fixture", "Result", "Details"]);
+            for fixture in self.fixtures.iter() {
+                let (result, style) = if fixture.passed {
+                    ("PASS", "Fgb")
+                } else {
+                    ("FAIL", "Frb")
+                };
+                let mut result = Cell::new(result);
+                if color {
+                    result = result.style_spec(style);
+                }
+                let details = match &fixture.error {
+                    Some(error) => format!("cannot be evaluated: {}", error),
+                    None => fixture.differences.join("\n"),
+                };
+                table.add_row(Row::new(vec![
+                    Cell::new(&fixture.name),
+                    result,
+                    Cell::new(&details),
+                ]));
+            }
+            table.printstd();
+        }
+
+        println!(
+            "\n{} fixtures: {} passed, {} failed",
+            self.total, self.passed, self.failed
+        );
+    }
+}
+
+/// Loads the fixtures of `dir`, sorted by name. Each fixture is made of
+/// a `<name>.request.<ext>` file holding the request, and of a
+/// `<name>.response.<ext>` file holding the expected response. Both JSON
+/// (`.json`) and YAML (`.yaml`, `.yml`) files are accepted.
+pub(crate) fn load_fixtures(dir: &Path) -> Result<Vec<Fixture>> {
+    let mut requests: BTreeMap<String, (String, RequestFormat)> = BTreeMap::new();
+    let mut responses: BTreeMap<String, serde_json::Value> = BTreeMap::new();
+    let entries = fs::read_dir(dir)
+        .map_err(|e| anyhow!("Cannot read fixtures directory {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry?.path();
+        let (name, kind, format) = match fixture_file(&path) {
+            Some(fixture_file) => fixture_file,
+            None => continue,
+        };
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| anyhow!("Cannot read fixture {}: {}", path.display(), e))?;
+        match kind {
+            FixtureFileKind::Request => {
+                requests.insert(name, (contents, format));
+            }
+            FixtureFileKind::Response => {
+                let response = match format {
+                    RequestFormat::Json => serde_json::from_str(&contents)
+                        .map_err(|e| anyhow!("Invalid response {}: {}", path.display(), e))?,
+                    RequestFormat::Yaml => serde_yaml::from_str(&contents)
+                        .map_err(|e| anyhow!("Invalid response {}: {}", path.display(), e))?,
+                };
+                responses.insert(name, response);
+            }
+        }
+    }
+
+    if let Some(name) = responses.keys().find(|name| !requests.contains_key(*name)) {
+        return Err(anyhow!("Fixture '{}' doesn't have a request", name));
+    }
+    requests
+        .into_iter()
+        .map(|(name, (contents, format))| {
+            let expected_response = responses
+                .remove(&name)
+                .ok_or_else(|| anyhow!("Fixture '{}' doesn't have an expected response", name))?;
+            Ok(Fixture {
+                request: RequestInput {
+                    source: dir.join(&name).display().to_string(),
+                    contents,
+                    format,
+                },
+                name,
+                expected_response,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+enum FixtureFileKind {
+    Request,
+    Response,
+}
+
+// Splits the file name of a fixture file into the name of the fixture,
+// the kind of file and its format
+fn fixture_file(path: &Path) -> Option<(String, FixtureFileKind, RequestFormat)> {
+    let file_name = path.file_name()?.to_str()?;
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    let format = match extension {
+        "json" => RequestFormat::Json,
+        "yaml" | "yml" => RequestFormat::Yaml,
+        _ => return None,
+    };
+    if let Some(name) = stem.strip_suffix(".request") {
+        Some((String::from(name), FixtureFileKind::Request, format))
+    } else {
+        stem.strip_suffix(".response")
+            .map(|name| (String::from(name), FixtureFileKind::Response, format))
+    }
+}
+
+// Compares the actual response against the expected one. Only the fields
+// defined by the expected response are compared, recursively: this allows
+// to ignore the fields that change at every evaluation, like the `uid`.
+fn find_differences(
+    expected: &serde_json::Value,
+    actual: &serde_json::Value,
+    path: &str,
+    differences: &mut Vec<String>,
+) {
+    match (expected, actual) {
+        (serde_json::Value::Object(expected), serde_json::Value::Object(actual)) => {
+            for (key, expected_value) in expected.iter() {
+                let field_path = format!("{}.{}", path, key);
+                match actual.get(key) {
+                    Some(actual_value) => {
+                        find_differences(expected_value, actual_value, &field_path, differences)
+                    }
+                    None => differences.push(format!(
+                        "{}: expected {}, got nothing",
+                        field_path, expected_value
+                    )),
+                }
+            }
+        }
+        _ if expected != actual => {
+            differences.push(format!("{}: expected {}, got {}", path, expected, actual))
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_find_differences() {
+        let actual = json!({
+            "uid": "1234",
+            "allowed": false,
+            "status": {"message": "the owner label is missing", "code": 400},
+        });
+
+        let mut differences = Vec::new();
+        find_differences(
+            &json!({"allowed": false, "status": {"code": 400}}),
+            &actual,
+            "response",
+            &mut differences,
+        );
+        assert!(differences.is_empty());
+
+        find_differences(
+            &json!({"allowed": true, "warnings": ["deprecated"]}),
+            &actual,
+            "response",
+            &mut differences,
+        );
+        assert_eq!(
+            differences,
+            vec![
+                "response.allowed: expected true, got false",
+                "response.warnings: expected [\"deprecated\"], got nothing",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_fixtures() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("allowed.request.json"), r#"{"uid": "1"}"#)?;
+        fs::write(dir.path().join("allowed.response.yaml"), "allowed: true")?;
+        fs::write(dir.path().join("denied.request.yml"), "uid: '2'")?;
+        fs::write(
+            dir.path().join("denied.response.json"),
+            r#"{"allowed": false}"#,
+        )?;
+        fs::write(dir.path().join(SETTINGS_FILE), "foo: bar")?;
+        fs::write(dir.path().join("README.md"), "fixtures")?;
+
+        let fixtures = load_fixtures(dir.path())?;
+        let names: Vec<&str> = fixtures.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["allowed", "denied"]);
+        assert_eq!(fixtures[0].request.format, RequestFormat::Json);
+        assert_eq!(fixtures[0].expected_response, json!({"allowed": true}));
+        assert_eq!(fixtures[1].request.format, RequestFormat::Yaml);
+
+        fs::write(dir.path().join("orphan.request.json"), r#"{"uid": "3"}"#)?;
+        assert!(load_fixtures(dir.path()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_report() {
+        let fixture = Fixture {
+            name: String::from("denied"),
+            request: RequestInput {
+                source: String::from("denied.request.json"),
+                contents: String::new(),
+                format: RequestFormat::Json,
+            },
+            expected_response: json!({"allowed": false}),
+        };
+        let report = TestReport::new(vec![
+            FixtureResult::new(&fixture, Ok(json!({"uid": "1", "allowed": false}))),
+            FixtureResult::new(&fixture, Ok(json!({"uid": "1", "allowed": true}))),
+            FixtureResult::new(&fixture, Err(anyhow!("invalid request"))),
+        ]);
+        assert_eq!((report.total, report.passed, report.failed), (3, 1, 2));
+        assert_eq!(report.fixtures[2].error.as_deref(), Some("invalid request"));
+    }
+}
@@ -1,8 +1,20 @@
-use crate::{DockerConfig, Registry, Sources};
+use crate::capabilities::{required_capabilities, Capability};
+use crate::consistency::consistency_warnings;
+use crate::oci::{
+    ImageReference, OciClient, Referrer, DOCKER_IMAGE_MANIFEST_MEDIA_TYPE,
+    DOCKER_MANIFEST_LIST_MEDIA_TYPE, OCI_ARTIFACT_MANIFEST_MEDIA_TYPE, OCI_IMAGE_INDEX_MEDIA_TYPE,
+    OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+};
+use crate::rules::lint_rules;
+use crate::template::Template;
+use crate::validate_metadata::KUBEWARDEN_ANNOTATION_PREFIX;
+use crate::{DockerConfig, Sources};
 use anyhow::{anyhow, Result};
+use futures::stream::{self, StreamExt};
 use mdcat::{ResourceAccess, TerminalCapabilities, TerminalSize};
 use policy_evaluator::policy_fetcher::{
-    oci_distribution::manifest::{OciImageManifest, OciManifest},
+    oci_distribution::manifest::{OciDescriptor, OciImageManifest},
+    registry::Registry,
     sigstore::{
         cosign::{ClientBuilder, CosignCapabilities},
         registry::{Auth, ClientConfig},
@@ -11,16 +23,64 @@ use policy_evaluator::policy_fetcher::{
 use policy_evaluator::{
     constants::*, policy_evaluator::PolicyExecutionMode, policy_metadata::Metadata,
 };
+use pretty_bytes::converter::convert;
 use prettytable::{format::FormatBuilder, Table};
 use pulldown_cmark::{Options, Parser};
-use std::convert::{TryFrom, TryInto};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    convert::TryFrom,
+    fs,
+    path::Path,
+};
 use syntect::parsing::SyntaxSet;
+use tracing::warn;
+use x509_parser::{extensions::GeneralName, extensions::ParsedExtension, pem::parse_x509_pem};
+
+/// Number of policies of a bundle that are inspected concurrently
+const BUNDLE_INSPECT_CONCURRENCY: usize = 8;
+
+const POLICY_VERSION_ANNOTATION: &str = "io.kubewarden.policy.version";
+
+/// Annotation of a cosign signature layer holding the PEM encoded
+/// certificate of a keyless signature
+const COSIGN_CERTIFICATE_ANNOTATION: &str = "dev.sigstore.cosign/certificate";
 
+/// Aliases of the artifact types commonly attached to policies, accepted by
+/// `--artifact-type`
+const ARTIFACT_TYPE_ALIASES: &[(&str, &str)] = &[
+    (
+        "signature",
+        "application/vnd.dev.cosign.artifact.sig.v1+json",
+    ),
+    ("attestation", "application/vnd.dsse.envelope.v1+json"),
+    ("sbom", "application/spdx+json"),
+    ("cyclonedx", "application/vnd.cyclonedx+json"),
+    ("notation", "application/vnd.cncf.notary.signature"),
+];
+
+/// Fulcio certificate extension holding the OIDC issuer of the signer
+const FULCIO_ISSUER_OID: &str = "1.3.6.1.4.1.57264.1.1";
+
+/// Restricts the output of `inspect` to the given metadata annotations
+pub(crate) struct AnnotationsSelection {
+    pub keys: Vec<String>,
+    /// Report missing annotations with an empty value, instead of failing
+    pub allow_missing: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn inspect(
     uri: &str,
     output: OutputType,
     sources: Option<Sources>,
     docker_config: Option<DockerConfig>,
+    annotations_selection: Option<AnnotationsSelection>,
+    accept_media_types: Vec<String>,
+    show_signers: bool,
+    strict_signatures: bool,
+    allow_remote_resources: bool,
+    verbose: bool,
 ) -> Result<()> {
     let uri = crate::utils::map_path_to_uri(uri)?;
     let wasm_path = crate::utils::wasm_path(uri.as_str())?;
@@ -28,46 +88,567 @@ pub(crate) async fn inspect(
 
     let metadata = Metadata::from_path(&wasm_path)
         .map_err(|e| anyhow!("Error parsing policy metadata: {}", e))?;
-
-    let signatures = fetch_signatures_manifest(uri.as_str(), sources, docker_config).await;
-
-    match metadata {
-        Some(metadata) => metadata_printer.print(&metadata)?,
+    let metadata = match metadata {
+        Some(metadata) => metadata,
         None => return Err(anyhow!(
             "No Kubewarden metadata found inside of '{}'.\nPolicies can be annotated with the `kwctl annotate` command.",
             uri
         )),
     };
 
-    match signatures {
-        Ok(signatures) => {
-            if let Some(signatures) = signatures {
-                println!();
-                println!("Sigstore signatures");
-                println!();
-                let sigstore_printer = SignaturesPrinter::from(&output);
-                sigstore_printer.print(&signatures);
+    let signatures =
+        fetch_signatures_manifest(uri.as_str(), sources, docker_config, &accept_media_types).await;
+
+    if let Some(annotations_selection) = annotations_selection {
+        if strict_signatures {
+            ensure_signed(uri.as_str(), &signatures)?;
+        }
+        return metadata_printer.print_annotations(&metadata, &annotations_selection);
+    }
+
+    let capabilities = required_capabilities(&wasm_path)?;
+    let warnings = if verbose {
+        consistency_warnings(&metadata, &wasm_path)?
+    } else {
+        Vec::new()
+    };
+    // remote resources are fetched only when explicitly requested by the
+    // user, the usage of a policy is written by a third party
+    let resource_access = if allow_remote_resources {
+        ResourceAccess::RemoteAllowed
+    } else {
+        ResourceAccess::LocalOnly
+    };
+    metadata_printer.print(&metadata, &capabilities, &warnings, resource_access)?;
+
+    match &signatures {
+        Ok(Some(signatures)) => {
+            println!();
+            println!("Sigstore signatures");
+            println!();
+            let sigstore_printer = SignaturesPrinter::from(&output);
+            if show_signers {
+                sigstore_printer.print_signers(signatures)?;
+            } else {
+                sigstore_printer.print(signatures);
             }
         }
+        Ok(None) => {
+            println!();
+            println!("No sigstore signatures found");
+        }
         Err(error) => {
             println!();
-            if error
-                .to_string()
-                .as_str()
-                .starts_with("OCI API error: manifest unknown on")
-            {
-                println!("No sigstore signatures found");
-            } else {
-                println!("Cannot determine if the policy has been signed. There was an error while attempting to fetch its signatures from the remote registry: {} ", error)
+            println!("Cannot determine if the policy has been signed. There was an error while attempting to fetch its signatures from the remote registry: {} ", error);
+        }
+    }
+
+    if strict_signatures {
+        ensure_signed(uri.as_str(), &signatures)?;
+    }
+    Ok(())
+}
+
+// Fails when the policy is not signed, or when its signatures cannot be
+// fetched. Used by `--strict-signatures`
+fn ensure_signed(uri: &str, signatures: &Result<Option<OciImageManifest>>) -> Result<()> {
+    match signatures {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(anyhow!("The policy {} is not signed", uri)),
+        Err(error) => Err(anyhow!(
+            "Cannot fetch the signatures of the policy {}: {}",
+            uri,
+            error
+        )),
+    }
+}
+
+/// A file listing the policies to be inspected at once
+#[derive(Deserialize)]
+struct Bundle {
+    policies: Vec<String>,
+}
+
+/// The summary of a policy of a bundle
+#[derive(Serialize)]
+struct BundlePolicySummary {
+    uri: String,
+    title: Option<String>,
+    version: Option<String>,
+    mutating: Option<bool>,
+    /// `None` when it cannot be determined whether the policy is signed
+    signed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub(crate) async fn inspect_bundle(
+    bundle_path: &Path,
+    output: OutputType,
+    sources: Option<Sources>,
+    docker_config: Option<DockerConfig>,
+) -> Result<()> {
+    let bundle: Bundle = serde_yaml::from_str(
+        &fs::read_to_string(bundle_path)
+            .map_err(|e| anyhow!("Cannot read bundle {}: {}", bundle_path.display(), e))?,
+    )
+    .map_err(|e| anyhow!("Invalid bundle {}: {}", bundle_path.display(), e))?;
+
+    let sources = sources.as_ref();
+    let docker_config = docker_config.as_ref();
+    let summaries: Vec<BundlePolicySummary> = stream::iter(bundle.policies.iter())
+        .map(|uri| async move { bundle_policy_summary(uri, sources, docker_config).await })
+        .buffered(BUNDLE_INSPECT_CONCURRENCY)
+        .collect()
+        .await;
+
+    match output {
+        OutputType::Yaml => println!("{}", serde_yaml::to_string(&summaries)?),
+        OutputType::Json => println!("{}", serde_json::to_string_pretty(&summaries)?),
+        OutputType::Pretty => {
+            let mut table = Table::new();
+            table.set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+            table.set_titles(row!["Policy", "Title", "Version", "Mutating", "Signed"]);
+            let yes_no = |value: Option<bool>| match value {
+                Some(true) => "yes",
+                Some(false) => "no",
+                None => "unknown",
+            };
+            for summary in summaries.iter() {
+                table.add_row(row![
+                    summary.uri,
+                    summary.title.as_deref().unwrap_or_default(),
+                    summary.version.as_deref().unwrap_or_default(),
+                    yes_no(summary.mutating),
+                    yes_no(summary.signed),
+                ]);
+            }
+            table.printstd();
+        }
+    }
+
+    let failed: Vec<String> = summaries
+        .iter()
+        .filter_map(|summary| {
+            summary
+                .error
+                .as_ref()
+                .map(|error| format!("{}: {}", summary.uri, error))
+        })
+        .collect();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Cannot inspect the following policies:\n{}",
+            failed.join("\n")
+        ))
+    }
+}
+
+async fn bundle_policy_summary(
+    uri: &str,
+    sources: Option<&Sources>,
+    docker_config: Option<&DockerConfig>,
+) -> BundlePolicySummary {
+    let mut summary = BundlePolicySummary {
+        uri: String::from(uri),
+        title: None,
+        version: None,
+        mutating: None,
+        signed: None,
+        error: None,
+    };
+
+    // `oci://` references and the ones pinned by digest are registry
+    // references too
+    let uri = match crate::utils::map_path_to_uri(uri) {
+        Ok(uri) => uri,
+        Err(e) => {
+            summary.error = Some(e.to_string());
+            return summary;
+        }
+    };
+
+    let metadata = crate::utils::wasm_path(&uri).and_then(|wasm_path| {
+        Metadata::from_path(&wasm_path).map_err(|e| anyhow!("Error parsing policy metadata: {}", e))
+    });
+    match metadata {
+        Ok(Some(metadata)) => {
+            let annotations = metadata.annotations.clone().unwrap_or_default();
+            summary.title = annotations.get(KUBEWARDEN_ANNOTATION_POLICY_TITLE).cloned();
+            summary.version = annotations.get(POLICY_VERSION_ANNOTATION).cloned();
+            summary.mutating = Some(metadata.mutating);
+        }
+        Ok(None) => summary.error = Some(String::from("no Kubewarden metadata found")),
+        Err(e) => summary.error = Some(e.to_string()),
+    }
+
+    if uri.starts_with("registry://") {
+        match fetch_signatures_manifest(&uri, sources.cloned(), docker_config.cloned(), &[]).await {
+            Ok(signatures) => summary.signed = Some(signatures.is_some()),
+            Err(e) => {
+                let error = format!("cannot fetch the signatures: {}", e);
+                summary.error = Some(match summary.error.take() {
+                    Some(metadata_error) => format!("{}; {}", metadata_error, error),
+                    None => error,
+                });
             }
         }
     }
 
+    summary
+}
+
+/// Prints the contents of the Kubewarden metadata custom section of the
+/// policy, before any parsing. The contents are printed verbatim, unless
+/// they have to be converted to YAML.
+pub(crate) fn inspect_raw_metadata(uri: &str, output: OutputType) -> Result<()> {
+    let uri = crate::utils::map_path_to_uri(uri)?;
+    let wasm_path = crate::utils::wasm_path(uri.as_str())?;
+    let wasm = fs::read(&wasm_path)
+        .map_err(|e| anyhow!("Cannot read policy {}: {}", wasm_path.display(), e))?;
+
+    let raw_metadata = crate::utils::raw_metadata(&wasm)?.ok_or_else(|| {
+        anyhow!(
+            "The policy '{}' does not have a '{}' custom section",
+            uri,
+            KUBEWARDEN_CUSTOM_SECTION_METADATA
+        )
+    })?;
+
+    match output {
+        OutputType::Yaml => {
+            let metadata: serde_yaml::Value = serde_json::from_slice(raw_metadata)
+                .map_err(|e| anyhow!("The metadata is not valid JSON: {}", e))?;
+            println!("{}", serde_yaml::to_string(&metadata)?);
+        }
+        OutputType::Json | OutputType::Pretty => {
+            println!("{}", String::from_utf8_lossy(raw_metadata));
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the metadata of the policy rendered with the given template.
+/// The fields of the metadata are available with the names they have
+/// inside of `metadata.yml`, the Kubewarden annotations are available
+/// also without their `io.kubewarden.policy.` prefix.
+pub(crate) fn inspect_template(uri: &str, template: &Template) -> Result<()> {
+    let uri = crate::utils::map_path_to_uri(uri)?;
+    let wasm_path = crate::utils::wasm_path(uri.as_str())?;
+    let metadata = Metadata::from_path(&wasm_path)
+        .map_err(|e| anyhow!("Error parsing policy metadata: {}", e))?
+        .ok_or_else(|| anyhow!("No Kubewarden metadata found inside of '{}'", uri))?;
+
+    println!("{}", template.render(&template_context(&metadata)?)?);
+    Ok(())
+}
+
+fn template_context(metadata: &Metadata) -> Result<serde_json::Value> {
+    let mut context = serde_json::to_value(metadata)?;
+    if let Some(annotations) = context
+        .get_mut("annotations")
+        .and_then(|annotations| annotations.as_object_mut())
+    {
+        let short_names: Vec<(String, serde_json::Value)> = annotations
+            .iter()
+            .filter_map(|(key, value)| {
+                key.strip_prefix(KUBEWARDEN_ANNOTATION_PREFIX)
+                    .map(|name| (String::from(name), value.clone()))
+            })
+            .collect();
+        for (name, value) in short_names {
+            annotations.entry(name).or_insert(value);
+        }
+    }
+
+    Ok(context)
+}
+
+/// The size of a blob referenced by the manifest of a policy
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BlobSize {
+    media_type: String,
+    digest: String,
+    size: i64,
+}
+
+impl From<&OciDescriptor> for BlobSize {
+    fn from(descriptor: &OciDescriptor) -> Self {
+        BlobSize {
+            media_type: descriptor.media_type.clone(),
+            digest: descriptor.digest.clone(),
+            size: descriptor.size,
+        }
+    }
+}
+
+/// The sizes of the blobs making a policy artifact
+#[derive(Debug, PartialEq, Serialize)]
+struct SizeBreakdown {
+    config: BlobSize,
+    layers: Vec<BlobSize>,
+    /// The size of the config plus the ones of all the layers
+    total: i64,
+}
+
+impl From<&OciImageManifest> for SizeBreakdown {
+    fn from(manifest: &OciImageManifest) -> Self {
+        let config = BlobSize::from(&manifest.config);
+        let layers: Vec<BlobSize> = manifest.layers.iter().map(BlobSize::from).collect();
+        let total = config.size + layers.iter().map(|layer| layer.size).sum::<i64>();
+        SizeBreakdown {
+            config,
+            layers,
+            total,
+        }
+    }
+}
+
+/// Prints the media type and the size of the config and of each layer of
+/// the manifest of the policy, plus their total. The sizes are the ones
+/// declared by the manifest stored inside of the registry.
+pub(crate) async fn inspect_size_breakdown(
+    uri: &str,
+    output: OutputType,
+    sources: Option<Sources>,
+    docker_config: Option<DockerConfig>,
+) -> Result<()> {
+    let uri = crate::utils::map_path_to_uri(uri)?;
+    if !uri.starts_with("registry://") {
+        return Err(anyhow!(
+            "The size breakdown is available only for policies stored inside of a registry, like registry://ghcr.io/kubewarden/policies/psp-capabilities:v0.1.6"
+        ));
+    }
+    let image = ImageReference::parse(&uri)?;
+    let reference = image
+        .digest
+        .clone()
+        .or_else(|| image.tag.clone())
+        .unwrap_or_else(|| String::from("latest"));
+    let client = OciClient::new(&image, sources.as_ref(), docker_config.as_ref())?;
+    let (_, manifest) = client
+        .fetch_manifest(
+            &image,
+            &reference,
+            &[
+                OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+                DOCKER_IMAGE_MANIFEST_MEDIA_TYPE,
+            ],
+        )
+        .await?
+        .ok_or_else(|| anyhow!("Cannot find the manifest of the policy {}", uri))?;
+    let manifest: OciImageManifest = serde_json::from_slice(&manifest)
+        .map_err(|e| anyhow!("Invalid manifest of the policy {}: {}", uri, e))?;
+    let breakdown = SizeBreakdown::from(&manifest);
+
+    match output {
+        OutputType::Yaml => println!("{}", serde_yaml::to_string(&breakdown)?),
+        OutputType::Json => println!("{}", serde_json::to_string_pretty(&breakdown)?),
+        OutputType::Pretty => {
+            let mut table = Table::new();
+            table.set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+            table.set_titles(row!["Blob", "Media type", "Digest", "Size"]);
+            let blobs = std::iter::once((String::from("config"), &breakdown.config)).chain(
+                breakdown
+                    .layers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, layer)| (format!("layer {}", i), layer)),
+            );
+            for (name, blob) in blobs {
+                table.add_row(row![
+                    name,
+                    blob.media_type,
+                    blob.digest,
+                    r -> convert(blob.size as f64)
+                ]);
+            }
+            table.add_row(row!["total", "", "", r -> convert(breakdown.total as f64)]);
+            table.printstd();
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the artifacts referring to the policy (signatures, SBOMs,
+/// attestations,...), discovered via the Referrers API. Only the artifacts
+/// of `artifact_type`, a media type or one of the known aliases, are printed.
+pub(crate) async fn inspect_referrers(
+    uri: &str,
+    artifact_type: &str,
+    output: OutputType,
+    sources: Option<Sources>,
+    docker_config: Option<DockerConfig>,
+) -> Result<()> {
+    let uri = crate::utils::map_path_to_uri(uri)?;
+    if !uri.starts_with("registry://") {
+        return Err(anyhow!(
+            "Referrers can be discovered only for policies stored inside of a registry"
+        ));
+    }
+    let artifact_type = resolve_artifact_type(artifact_type);
+
+    let image = ImageReference::parse(&uri)?;
+    let digest = match &image.digest {
+        Some(digest) => digest.clone(),
+        None => {
+            Registry::new(docker_config.as_ref())
+                .manifest_digest(&uri, sources.as_ref())
+                .await?
+        }
+    };
+    let client = OciClient::new(&image, sources.as_ref(), docker_config.as_ref())?;
+    let referrers = client
+        .list_referrers(&image, &digest, Some(artifact_type))
+        .await?;
+
+    match output {
+        OutputType::Yaml => println!("{}", serde_yaml::to_string(&referrers)?),
+        OutputType::Json => println!("{}", serde_json::to_string_pretty(&referrers)?),
+        OutputType::Pretty => print_referrers(&referrers, artifact_type),
+    }
+
+    Ok(())
+}
+
+fn resolve_artifact_type(artifact_type: &str) -> &str {
+    ARTIFACT_TYPE_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == artifact_type)
+        .map(|(_, media_type)| *media_type)
+        .unwrap_or(artifact_type)
+}
+
+fn print_referrers(referrers: &[Referrer], artifact_type: &str) {
+    if referrers.is_empty() {
+        println!("No artifact of type {} found", artifact_type);
+        return;
+    }
+
+    let mut table = Table::new();
+    table.set_format(FormatBuilder::new().padding(0, 1).build());
+    for referrer in referrers {
+        table.add_row(row![Fmbl -> "Referrer"]);
+        table.add_row(row![Fgbl -> "digest:", d -> referrer.digest]);
+        table.add_row(row![Fgbl -> "media type:", d -> referrer.media_type]);
+        table.add_row(row![Fgbl -> "size:", d -> referrer.size]);
+        for (annotation, value) in referrer.annotations.iter() {
+            table.add_row(row![Fgbl -> format!("{}:", annotation), d -> value]);
+        }
+        table.add_row(row![]);
+    }
+    table.printstd();
+}
+
+/// Copies the Wasm module of the policy to `destination`. An existing
+/// destination is overwritten only when `force` is set.
+pub(crate) fn dump_wasm(uri: &str, destination: &Path, force: bool) -> Result<()> {
+    let uri = crate::utils::map_path_to_uri(uri)?;
+    let wasm_path = crate::utils::wasm_path(uri.as_str())?;
+    if destination.exists() {
+        // copying a file onto itself truncates it
+        if fs::canonicalize(&wasm_path)? == fs::canonicalize(destination)? {
+            return Err(anyhow!(
+                "{} is the policy itself, it cannot be overwritten",
+                destination.display()
+            ));
+        }
+        if !force {
+            return Err(anyhow!(
+                "{} already exists, use --force to overwrite it",
+                destination.display()
+            ));
+        }
+    }
+
+    fs::copy(&wasm_path, destination).map_err(|e| {
+        anyhow!(
+            "Cannot copy policy {} to {}: {}",
+            wasm_path.display(),
+            destination.display(),
+            e
+        )
+    })?;
+    Ok(())
+}
+
+/// Ensures the execution mode of the policy is one of `supported_modes`,
+/// failing otherwise
+pub(crate) fn check_compat(uri: &str, supported_modes: &[PolicyExecutionMode]) -> Result<()> {
+    let uri = crate::utils::map_path_to_uri(uri)?;
+    let wasm_path = crate::utils::wasm_path(uri.as_str())?;
+    let metadata = Metadata::from_path(&wasm_path)
+        .map_err(|e| anyhow!("Error parsing policy metadata: {}", e))?
+        .ok_or_else(|| anyhow!(
+            "No Kubewarden metadata found inside of '{}'.\nPolicies can be annotated with the `kwctl annotate` command.",
+            uri
+        ))?;
+
+    ensure_compatible_execution_mode(&metadata.execution_mode, supported_modes)?;
+    println!(
+        "{}: execution mode {} is supported",
+        uri, metadata.execution_mode
+    );
+    Ok(())
+}
+
+fn ensure_compatible_execution_mode(
+    execution_mode: &PolicyExecutionMode,
+    supported_modes: &[PolicyExecutionMode],
+) -> Result<()> {
+    if supported_modes.contains(execution_mode) {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "The execution mode of the policy, {}, is not one of the supported ones: {}",
+            execution_mode,
+            supported_modes
+                .iter()
+                .map(|mode| mode.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ))
+    }
+}
+
+/// Lints the rules of the policy, printing a warning for each invalid
+/// entry found. The rules are read from the raw metadata, so that entries
+/// rejected by the metadata parser are reported too.
+pub(crate) fn check_rules(uri: &str) -> Result<()> {
+    let uri = crate::utils::map_path_to_uri(uri)?;
+    let wasm_path = crate::utils::wasm_path(uri.as_str())?;
+    let wasm = fs::read(&wasm_path)
+        .map_err(|e| anyhow!("Cannot read policy {}: {}", wasm_path.display(), e))?;
+
+    let raw_metadata = crate::utils::raw_metadata(&wasm)?.ok_or_else(|| {
+        anyhow!(
+            "No Kubewarden metadata found inside of '{}'.\nPolicies can be annotated with the `kwctl annotate` command.",
+            uri
+        )
+    })?;
+    let metadata: serde_json::Value = serde_json::from_slice(raw_metadata)
+        .map_err(|e| anyhow!("The metadata is not valid JSON: {}", e))?;
+
+    let warnings = lint_rules(&metadata);
+    if warnings.is_empty() {
+        println!("No problems found inside of the rules");
+    } else {
+        for warning in warnings.iter() {
+            println!("warning: {}", warning);
+        }
+    }
+
     Ok(())
 }
 
 pub(crate) enum OutputType {
     Yaml,
+    Json,
     Pretty,
 }
 
@@ -77,6 +658,7 @@ impl TryFrom<Option<&str>> for OutputType {
     fn try_from(value: Option<&str>) -> Result<Self, Self::Error> {
         match value {
             Some("yaml") => Ok(Self::Yaml),
+            Some("json") => Ok(Self::Json),
             None => Ok(Self::Pretty),
             Some(unknown) => Err(anyhow!("Invalid output format '{}'", unknown)),
         }
@@ -85,6 +667,7 @@ impl TryFrom<Option<&str>> for OutputType {
 
 enum MetadataPrinter {
     Yaml,
+    Json,
     Pretty,
 }
 
@@ -92,27 +675,93 @@ impl From<&OutputType> for MetadataPrinter {
     fn from(output_type: &OutputType) -> Self {
         match output_type {
             OutputType::Yaml => Self::Yaml,
+            OutputType::Json => Self::Json,
             OutputType::Pretty => Self::Pretty,
         }
     }
 }
 
 impl MetadataPrinter {
-    fn print(&self, metadata: &Metadata) -> Result<()> {
+    fn print(
+        &self,
+        metadata: &Metadata,
+        capabilities: &BTreeSet<Capability>,
+        warnings: &[String],
+        resource_access: ResourceAccess,
+    ) -> Result<()> {
         match self {
             MetadataPrinter::Yaml => {
                 let metadata_yaml = serde_yaml::to_string(metadata)?;
                 println!("{}", metadata_yaml);
                 Ok(())
             }
+            MetadataPrinter::Json => {
+                println!("{}", serde_json::to_string_pretty(metadata)?);
+                Ok(())
+            }
             MetadataPrinter::Pretty => {
-                self.print_metadata_generic_info(metadata)?;
+                self.print_metadata_generic_info(metadata, capabilities, warnings)?;
                 println!();
                 self.print_metadata_rules(metadata)?;
                 println!();
-                self.print_metadata_usage(metadata)
+                self.print_metadata_usage(metadata, resource_access)
+            }
+        }
+    }
+
+    fn print_annotations(
+        &self,
+        metadata: &Metadata,
+        annotations_selection: &AnnotationsSelection,
+    ) -> Result<()> {
+        let annotations = metadata.annotations.clone().unwrap_or_default();
+        let mut selected: Vec<(&str, &str)> = Vec::new();
+        let mut missing: Vec<&str> = Vec::new();
+        for key in annotations_selection.keys.iter() {
+            match annotations.get(key) {
+                Some(value) => selected.push((key, value)),
+                None => {
+                    missing.push(key);
+                    selected.push((key, ""));
+                }
+            }
+        }
+        if !missing.is_empty() && !annotations_selection.allow_missing {
+            return Err(anyhow!(
+                "The policy metadata does not have the following annotations: {}",
+                missing.join(", ")
+            ));
+        }
+
+        match self {
+            MetadataPrinter::Yaml => {
+                let mut mapping = serde_yaml::Mapping::new();
+                for (key, value) in selected {
+                    mapping.insert(
+                        serde_yaml::Value::String(String::from(key)),
+                        serde_yaml::Value::String(String::from(value)),
+                    );
+                }
+                println!("{}", serde_yaml::to_string(&mapping)?);
+            }
+            MetadataPrinter::Json => {
+                let object: serde_json::Map<String, serde_json::Value> = selected
+                    .into_iter()
+                    .map(|(key, value)| (String::from(key), serde_json::Value::from(value)))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&object)?);
+            }
+            MetadataPrinter::Pretty => {
+                let mut table = Table::new();
+                table.set_format(FormatBuilder::new().padding(0, 1).build());
+                for (key, value) in selected {
+                    table.add_row(row![Fgbl -> format!("{}:", key), d -> value]);
+                }
+                table.printstd();
             }
         }
+
+        Ok(())
     }
 
     fn annotation_to_row_key(&self, text: &str) -> String {
@@ -121,11 +770,23 @@ impl MetadataPrinter {
         String::from(out.trim_start_matches("io.kubewarden.policy."))
     }
 
-    fn print_metadata_generic_info(&self, metadata: &Metadata) -> Result<()> {
-        let protocol_version = metadata
-            .protocol_version
-            .clone()
-            .ok_or_else(|| anyhow!("Invalid policy: protocol_version not defined"))?;
+    fn print_metadata_generic_info(
+        &self,
+        metadata: &Metadata,
+        capabilities: &BTreeSet<Capability>,
+        warnings: &[String],
+    ) -> Result<()> {
+        // the protocol version is meaningful only for waPC policies
+        let protocol_version = if metadata.execution_mode == PolicyExecutionMode::KubewardenWapc {
+            Some(
+                metadata
+                    .protocol_version
+                    .clone()
+                    .ok_or_else(|| anyhow!("Invalid policy: protocol_version not defined"))?,
+            )
+        } else {
+            None
+        };
 
         let pretty_annotations = vec![
             KUBEWARDEN_ANNOTATION_POLICY_TITLE,
@@ -150,9 +811,22 @@ impl MetadataPrinter {
         table.add_row(row![Fgbl -> "mutating:", metadata.mutating]);
         table.add_row(row![Fgbl -> "context aware:", metadata.context_aware]);
         table.add_row(row![Fgbl -> "execution mode:", metadata.execution_mode]);
-        if metadata.execution_mode == PolicyExecutionMode::KubewardenWapc {
+        if let Some(protocol_version) = protocol_version {
             table.add_row(row![Fgbl -> "protocol version:", protocol_version]);
         }
+        let capabilities = if capabilities.is_empty() {
+            String::from("none")
+        } else {
+            capabilities
+                .iter()
+                .map(|capability| capability.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        table.add_row(row![Fgbl -> "required capabilities:", capabilities]);
+        for warning in warnings {
+            table.add_row(row![Fybl -> "warning:", d -> warning]);
+        }
 
         let _usage = annotations.remove(KUBEWARDEN_ANNOTATION_POLICY_USAGE);
         if !annotations.is_empty() {
@@ -178,10 +852,14 @@ impl MetadataPrinter {
         table.printstd();
 
         let text = format!("```yaml\n{}```", rules_yaml);
-        self.render_markdown(&text)
+        self.render_markdown(&text, ResourceAccess::LocalOnly)
     }
 
-    fn print_metadata_usage(&self, metadata: &Metadata) -> Result<()> {
+    fn print_metadata_usage(
+        &self,
+        metadata: &Metadata,
+        resource_access: ResourceAccess,
+    ) -> Result<()> {
         let usage = match metadata.annotations.clone() {
             None => None,
             Some(annotations) => annotations
@@ -200,16 +878,16 @@ impl MetadataPrinter {
         table.add_row(row![Fmbl -> "Usage"]);
         table.printstd();
 
-        self.render_markdown(&usage.unwrap())
+        self.render_markdown(&usage.unwrap(), resource_access)
     }
 
-    fn render_markdown(&self, text: &str) -> Result<()> {
+    fn render_markdown(&self, text: &str, resource_access: ResourceAccess) -> Result<()> {
         let size = TerminalSize::detect().unwrap_or_default();
         let columns = size.columns;
         let settings = mdcat::Settings {
             terminal_capabilities: TerminalCapabilities::detect(),
             terminal_size: TerminalSize { columns, ..size },
-            resource_access: ResourceAccess::LocalOnly,
+            resource_access,
             syntax_set: SyntaxSet::load_defaults_newlines(),
         };
         let parser = Parser::new_ext(
@@ -232,6 +910,7 @@ impl MetadataPrinter {
 
 enum SignaturesPrinter {
     Yaml,
+    Json,
     Pretty,
 }
 
@@ -239,6 +918,7 @@ impl From<&OutputType> for SignaturesPrinter {
     fn from(output_type: &OutputType) -> Self {
         match output_type {
             OutputType::Yaml => Self::Yaml,
+            OutputType::Json => Self::Json,
             OutputType::Pretty => Self::Pretty,
         }
     }
@@ -253,6 +933,11 @@ impl SignaturesPrinter {
                     println!("{}", signatures_yaml)
                 }
             }
+            SignaturesPrinter::Json => {
+                if let Ok(signatures_json) = serde_json::to_string_pretty(signatures) {
+                    println!("{}", signatures_json)
+                }
+            }
             SignaturesPrinter::Pretty => {
                 for layer in &signatures.layers {
                     let mut table = Table::new();
@@ -272,14 +957,111 @@ impl SignaturesPrinter {
             }
         }
     }
+
+    fn print_signers(&self, signatures: &OciImageManifest) -> Result<()> {
+        let signers: Vec<LayerSigner> = signatures
+            .layers
+            .iter()
+            .map(|layer| LayerSigner {
+                digest: layer.digest.clone(),
+                signer: layer
+                    .annotations
+                    .as_ref()
+                    .and_then(|annotations| annotations.get(COSIGN_CERTIFICATE_ANNOTATION))
+                    .and_then(|certificate| match Signer::from_pem(certificate) {
+                        Ok(signer) => Some(signer),
+                        Err(e) => {
+                            warn!(
+                                layer = layer.digest.as_str(),
+                                error = e.to_string().as_str(),
+                                "cannot decode signature certificate"
+                            );
+                            None
+                        }
+                    }),
+            })
+            .collect();
+
+        match self {
+            SignaturesPrinter::Yaml => {
+                println!("{}", serde_yaml::to_string(&signers)?);
+            }
+            SignaturesPrinter::Json => {
+                println!("{}", serde_json::to_string_pretty(&signers)?);
+            }
+            SignaturesPrinter::Pretty => {
+                for layer_signer in signers {
+                    let mut table = Table::new();
+                    table.set_format(FormatBuilder::new().padding(0, 1).build());
+                    table.add_row(row![Fmbl -> "Digest: ", layer_signer.digest]);
+                    match layer_signer.signer {
+                        Some(signer) => {
+                            table.add_row(row![Fmbl -> "Signed by: ", signer.subject.unwrap_or_else(|| String::from("unknown"))]);
+                            table.add_row(row![Fmbl -> "Issuer: ", signer.issuer.unwrap_or_else(|| String::from("unknown"))]);
+                        }
+                        None => {
+                            table.add_row(row![Fmbl -> "Signed by: ", "no identity information, signed with a key"]);
+                        }
+                    }
+                    table.printstd();
+                    println!();
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct LayerSigner {
+    digest: String,
+    signer: Option<Signer>,
+}
+
+/// The identity of whoever produced a keyless signature, as certified
+/// by Fulcio
+#[derive(Debug, PartialEq, Serialize)]
+struct Signer {
+    /// The email or the URI found inside of the Subject Alternative Name
+    /// of the certificate
+    subject: Option<String>,
+    /// The OIDC issuer that authenticated the signer
+    issuer: Option<String>,
+}
+
+impl Signer {
+    fn from_pem(certificate: &str) -> Result<Self> {
+        let (_, pem) = parse_x509_pem(certificate.as_bytes())
+            .map_err(|e| anyhow!("invalid PEM certificate: {}", e))?;
+        let certificate = pem
+            .parse_x509()
+            .map_err(|e| anyhow!("invalid x509 certificate: {}", e))?;
+
+        let mut subject: Option<String> = None;
+        let mut issuer: Option<String> = None;
+        for (oid, extension) in certificate.tbs_certificate.extensions().iter() {
+            if let ParsedExtension::SubjectAlternativeName(san) = extension.parsed_extension() {
+                subject = san.general_names.iter().find_map(|name| match name {
+                    GeneralName::RFC822Name(email) => Some(String::from(*email)),
+                    GeneralName::URI(uri) => Some(String::from(*uri)),
+                    _ => None,
+                });
+            } else if oid.to_id_string() == FULCIO_ISSUER_OID {
+                issuer = std::str::from_utf8(extension.value).ok().map(String::from);
+            }
+        }
+
+        Ok(Signer { subject, issuer })
+    }
 }
 
 async fn fetch_signatures_manifest(
     uri: &str,
     sources: Option<Sources>,
     docker_config: Option<DockerConfig>,
+    extra_media_types: &[String],
 ) -> Result<Option<OciImageManifest>> {
-    let registry = Registry::new(docker_config.as_ref());
     let client_config: ClientConfig = sources.clone().unwrap_or_default().into();
     let mut client = ClientBuilder::default()
         .with_oci_client_config(client_config)
@@ -288,33 +1070,354 @@ async fn fetch_signatures_manifest(
         .strip_prefix("registry://")
         .ok_or_else(|| anyhow!("invalid uri"))?;
 
-    let auth: Auth = match docker_config {
-        Some(docker_config) => {
-            let sigstore_auth: Option<Result<Auth>> = docker_config
-                .auth(image_name)
-                .map_err(|e| anyhow!("Cannot build Auth object for image '{}': {:?}", uri, e))?
-                .map(|ra| {
-                    let a: Result<Auth> = TryInto::<Auth>::try_into(ra);
-                    a
-                });
-
-            match sigstore_auth {
-                None => Auth::Anonymous,
-                Some(sa) => sa?,
-            }
-        }
-        None => Auth::Anonymous,
-    };
+    let auth: Auth = crate::oci::sigstore_auth(image_name, docker_config.as_ref())?;
 
     let (cosign_signature_image, _source_image_digest) =
         client.triangulate(image_name, &auth).await?;
 
-    let manifest = registry
-        .manifest(cosign_signature_image.as_str(), sources.as_ref())
-        .await?;
+    let signature_image = ImageReference::parse(&cosign_signature_image)?;
+    let reference = signature_image
+        .tag
+        .clone()
+        .ok_or_else(|| anyhow!("invalid signature image: {}", cosign_signature_image))?;
+    let oci_client = OciClient::new(&signature_image, sources.as_ref(), docker_config.as_ref())?;
+
+    let mut accepted_media_types = vec![
+        OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+        DOCKER_IMAGE_MANIFEST_MEDIA_TYPE,
+    ];
+    accepted_media_types.extend(extra_media_types.iter().map(String::as_str));
+
+    let (media_type, manifest) = match oci_client
+        .fetch_manifest(&signature_image, &reference, &accepted_media_types)
+        .await?
+    {
+        Some(manifest) => manifest,
+        None => return Ok(None),
+    };
+
+    let media_type = manifest_media_type(&media_type, &manifest);
+    if !accepted_media_types.contains(&media_type.as_str()) {
+        warn!(
+            manifest = cosign_signature_image.as_str(),
+            media_type = media_type.as_str(),
+            "signatures manifest has an unhandled media type"
+        );
+        return Err(anyhow!(
+            "the signatures manifest {} has the unhandled media type '{}'. Additional media types can be accepted via the `--accept-media-type` flag",
+            cosign_signature_image,
+            media_type
+        ));
+    }
+
+    parse_signatures_manifest(&media_type, &manifest)
+        .map(Some)
+        .map_err(|e| {
+            anyhow!(
+                "invalid signatures manifest {}: {}",
+                cosign_signature_image,
+                e
+            )
+        })
+}
+
+/// The parts of an OCI artifact manifest holding cosign signatures
+#[derive(Deserialize)]
+struct ArtifactManifest {
+    #[serde(default)]
+    blobs: Vec<OciDescriptor>,
+    annotations: Option<HashMap<String, String>>,
+}
+
+// Parses the signatures manifest according to its media type. The blobs of
+// artifact manifests are handled like the layers of image manifests, while
+// image indexes cannot hold signatures. Other media types, accepted via
+// `--accept-media-type`, must have the structure of an image manifest.
+fn parse_signatures_manifest(media_type: &str, manifest: &[u8]) -> Result<OciImageManifest> {
+    match media_type {
+        OCI_ARTIFACT_MANIFEST_MEDIA_TYPE => {
+            let artifact: ArtifactManifest = serde_json::from_slice(manifest)?;
+            Ok(OciImageManifest {
+                media_type: Some(String::from(media_type)),
+                layers: artifact.blobs,
+                annotations: artifact.annotations,
+                ..Default::default()
+            })
+        }
+        OCI_IMAGE_INDEX_MEDIA_TYPE | DOCKER_MANIFEST_LIST_MEDIA_TYPE => Err(anyhow!(
+            "it is an image index of type '{}', while signatures are stored inside of image manifests",
+            media_type
+        )),
+        OCI_IMAGE_MANIFEST_MEDIA_TYPE | DOCKER_IMAGE_MANIFEST_MEDIA_TYPE => {
+            Ok(serde_json::from_slice(manifest)?)
+        }
+        _ => serde_json::from_slice(manifest).map_err(|e| {
+            anyhow!(
+                "its media type '{}' is not handled by kwctl, and it cannot be read as an image manifest: {}",
+                media_type,
+                e
+            )
+        }),
+    }
+}
+
+// Computes the media type of a manifest. The one declared by the registry
+// via the `Content-Type` header takes precedence over the `mediaType`
+// field of the manifest.
+fn manifest_media_type(content_type: &str, manifest: &[u8]) -> String {
+    let content_type = content_type.split(';').next().unwrap_or_default().trim();
+    if !content_type.is_empty() && content_type != "application/json" {
+        return String::from(content_type);
+    }
+
+    serde_json::from_slice::<serde_json::Value>(manifest)
+        .ok()
+        .and_then(|manifest| {
+            manifest
+                .get("mediaType")
+                .and_then(|media_type| media_type.as_str())
+                .map(String::from)
+        })
+        .unwrap_or_else(|| String::from(content_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bundle_policy_summary_of_missing_policy() {
+        let summary = bundle_policy_summary("/does/not/exist/policy.wasm", None, None).await;
+
+        assert_eq!(summary.uri, "/does/not/exist/policy.wasm");
+        assert!(summary.title.is_none());
+        assert!(summary.mutating.is_none());
+        assert!(summary.signed.is_none());
+        assert!(summary.error.is_some());
+    }
 
-    match manifest {
-        OciManifest::Image(img) => Ok(Some(img)),
-        _ => Ok(None),
+    #[tokio::test]
+    async fn test_bundle_policy_summary_of_unreachable_registry() {
+        let summary = bundle_policy_summary("oci://localhost:1/psp:v0.1.0", None, None).await;
+
+        assert_eq!(summary.uri, "oci://localhost:1/psp:v0.1.0");
+        assert!(summary.signed.is_none());
+        assert!(summary
+            .error
+            .unwrap()
+            .contains("cannot fetch the signatures"));
+    }
+
+    #[test]
+    fn test_size_breakdown() {
+        let manifest = OciImageManifest {
+            config: OciDescriptor {
+                media_type: String::from("application/vnd.wasm.config.v1+json"),
+                size: 2,
+                ..Default::default()
+            },
+            layers: vec![
+                OciDescriptor {
+                    media_type: String::from("application/vnd.wasm.content.layer.v1+wasm"),
+                    size: 1024,
+                    ..Default::default()
+                },
+                OciDescriptor {
+                    media_type: String::from("application/vnd.oci.image.layer.v1.tar"),
+                    size: 512,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let breakdown = SizeBreakdown::from(&manifest);
+        assert_eq!(breakdown.config.size, 2);
+        assert_eq!(
+            breakdown
+                .layers
+                .iter()
+                .map(|layer| layer.size)
+                .collect::<Vec<i64>>(),
+            vec![1024, 512]
+        );
+        assert_eq!(breakdown.total, 1538);
+    }
+
+    #[test]
+    fn test_resolve_artifact_type() {
+        assert_eq!(
+            resolve_artifact_type("signature"),
+            "application/vnd.dev.cosign.artifact.sig.v1+json"
+        );
+        assert_eq!(
+            resolve_artifact_type("application/vnd.example.report+json"),
+            "application/vnd.example.report+json"
+        );
+    }
+
+    #[test]
+    fn test_ensure_compatible_execution_mode() {
+        let supported_modes = vec![
+            PolicyExecutionMode::KubewardenWapc,
+            PolicyExecutionMode::OpaGatekeeper,
+        ];
+        assert!(ensure_compatible_execution_mode(
+            &PolicyExecutionMode::KubewardenWapc,
+            &supported_modes
+        )
+        .is_ok());
+        assert!(
+            ensure_compatible_execution_mode(&PolicyExecutionMode::Opa, &supported_modes).is_err()
+        );
+    }
+
+    #[test]
+    fn test_dump_wasm() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let policy = dir.path().join("policy.wasm");
+        fs::write(&policy, b"\0asm\x01\0\0\0")?;
+        let uri = policy.to_str().unwrap();
+        let destination = dir.path().join("copy.wasm");
+
+        dump_wasm(uri, &destination, false)?;
+        assert_eq!(fs::read(&destination)?, fs::read(&policy)?);
+        assert!(dump_wasm(uri, &destination, false).is_err());
+        dump_wasm(uri, &destination, true)?;
+
+        assert!(dump_wasm(uri, &policy, true).is_err());
+        assert!(dump_wasm(uri, &dir.path().join(".").join("policy.wasm"), true).is_err());
+        assert_eq!(fs::read(&policy)?, b"\0asm\x01\0\0\0");
+        Ok(())
+    }
+
+    #[test]
+    fn test_template_context() -> Result<()> {
+        let metadata = Metadata {
+            mutating: true,
+            annotations: Some(
+                [
+                    ("io.kubewarden.policy.title", "psp-capabilities"),
+                    ("org.example.team", "security"),
+                ]
+                .iter()
+                .map(|(key, value)| (String::from(*key), String::from(*value)))
+                .collect(),
+            ),
+            ..Default::default()
+        };
+        let template = Template::parse(
+            r#"{{ .annotations.title }} {{ index .annotations "org.example.team" }} {{ .mutating }}"#,
+        )?;
+        assert_eq!(
+            template.render(&template_context(&metadata)?)?,
+            "psp-capabilities security true"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_print_metadata_generic_info_without_protocol_version() {
+        let printer = MetadataPrinter::Pretty;
+        let capabilities = BTreeSet::new();
+
+        let rego_metadata = Metadata {
+            protocol_version: None,
+            execution_mode: PolicyExecutionMode::Opa,
+            ..Default::default()
+        };
+        assert!(printer
+            .print_metadata_generic_info(&rego_metadata, &capabilities, &[])
+            .is_ok());
+
+        let wapc_metadata = Metadata {
+            protocol_version: None,
+            execution_mode: PolicyExecutionMode::KubewardenWapc,
+            ..Default::default()
+        };
+        assert!(printer
+            .print_metadata_generic_info(&wapc_metadata, &capabilities, &[])
+            .is_err());
+    }
+
+    #[test]
+    fn test_manifest_media_type() {
+        let manifest =
+            br#"{"schemaVersion":2,"mediaType":"application/vnd.oci.image.manifest.v1+json"}"#;
+
+        assert_eq!(
+            manifest_media_type(
+                "application/vnd.docker.distribution.manifest.v2+json; charset=utf-8",
+                manifest
+            ),
+            DOCKER_IMAGE_MANIFEST_MEDIA_TYPE
+        );
+        assert_eq!(
+            manifest_media_type("application/json", manifest),
+            OCI_IMAGE_MANIFEST_MEDIA_TYPE
+        );
+        assert_eq!(manifest_media_type("", br#"{"schemaVersion":2}"#), "");
+    }
+
+    #[test]
+    fn test_signer_from_pem() {
+        let certificate = "-----BEGIN CERTIFICATE-----\nMIIBnTCCAUKgAwIBAgIUNdqXP/snUyED7Pq7aNkugMqQzgIwCgYIKoZIzj0EAwIw\nFzEVMBMGA1UECgwMc2lnc3RvcmUuZGV2MB4XDTI2MTAxNTA4NTQ0MloXDTM2MTAx\nMjA4NTQ0MlowFzEVMBMGA1UECgwMc2lnc3RvcmUuZGV2MFkwEwYHKoZIzj0CAQYI\nKoZIzj0DAQcDQgAEMam2b+mIK7ifcdAWutgCUI3PLS3nPYEHAs5xbYPtcfVb3Z6G\nzXKCFlweznmLWSeJhVmJTTP8CIMi6fwyWN27hKNsMGowGwYDVR0RBBQwEoEQamFu\nZUBleGFtcGxlLmNvbTAsBgorBgEEAYO/MAEBBB5odHRwczovL2dpdGh1Yi5jb20v\nbG9naW4vb2F1dGgwHQYDVR0OBBYEFECumnayLg3wHXOXGPLmVjEV2x3fMAoGCCqG\nSM49BAMCA0kAMEYCIQCogGr/bS30HtVzOKPhGfIE2urbrXtDawgSc9OeSCSLuAIh\nAMYwjN+PpMCMJ7po24UxCQYhzGJnCaocfQiiqukem6dG\n-----END CERTIFICATE-----";
+
+        let signer = Signer::from_pem(certificate).unwrap();
+        assert_eq!(
+            signer,
+            Signer {
+                subject: Some(String::from("jane@example.com")),
+                issuer: Some(String::from("https://github.com/login/oauth")),
+            }
+        );
+    }
+
+    #[test]
+    fn test_signer_from_invalid_pem() {
+        assert!(Signer::from_pem("not a certificate").is_err());
+    }
+
+    #[test]
+    fn test_parse_signatures_manifest() -> Result<()> {
+        let layer = serde_json::json!({
+            "mediaType": "application/vnd.dev.cosign.simplesigning.v1+json",
+            "digest": "sha256:1234",
+            "size": 10,
+        });
+
+        let image_manifest = serde_json::to_vec(&serde_json::json!({
+            "schemaVersion": 2,
+            "config": {"mediaType": "application/vnd.oci.image.config.v1+json", "digest": "sha256:5678", "size": 2},
+            "layers": [layer],
+        }))?;
+        let manifest = parse_signatures_manifest(OCI_IMAGE_MANIFEST_MEDIA_TYPE, &image_manifest)?;
+        assert_eq!(manifest.layers[0].digest, "sha256:1234");
+        // a vendor media type with the structure of an image manifest
+        let manifest =
+            parse_signatures_manifest("application/vnd.example.manifest+json", &image_manifest)?;
+        assert_eq!(manifest.layers.len(), 1);
+
+        let artifact_manifest = serde_json::to_vec(&serde_json::json!({
+            "mediaType": OCI_ARTIFACT_MANIFEST_MEDIA_TYPE,
+            "artifactType": "application/vnd.dev.cosign.artifact.sig.v1+json",
+            "blobs": [layer],
+        }))?;
+        let manifest =
+            parse_signatures_manifest(OCI_ARTIFACT_MANIFEST_MEDIA_TYPE, &artifact_manifest)?;
+        assert_eq!(manifest.layers[0].digest, "sha256:1234");
+
+        let index = serde_json::to_vec(&serde_json::json!({
+            "schemaVersion": 2,
+            "manifests": [],
+        }))?;
+        let error = parse_signatures_manifest(OCI_IMAGE_INDEX_MEDIA_TYPE, &index)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("image index"), "unexpected error: {}", error);
+        assert!(
+            parse_signatures_manifest("application/vnd.example.manifest+json", &index).is_err()
+        );
+        Ok(())
     }
 }
@@ -9,10 +9,12 @@ extern crate serde_yaml;
 
 use anyhow::{anyhow, Result};
 use clap::ArgMatches;
+use config::Config;
 use directories::UserDirs;
+use futures::stream::{self, StreamExt};
 use lazy_static::lazy_static;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     convert::TryFrom,
     fs,
     io::{self, Read},
@@ -23,7 +25,7 @@ use std::{
 use tokio::task::spawn_blocking;
 use verify::VerificationAnnotations;
 
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::{
     filter::{EnvFilter, LevelFilter},
@@ -32,7 +34,7 @@ use tracing_subscriber::{
 
 use policy_evaluator::policy_evaluator::PolicyExecutionMode;
 use policy_evaluator::policy_fetcher::{
-    registry::config::{read_docker_config_json_file, DockerConfig},
+    registry::config::DockerConfig,
     registry::Registry,
     sigstore,
     sources::{read_sources_file, Certificate, Sources},
@@ -44,20 +46,34 @@ use policy_evaluator::policy_fetcher::{
     PullDestination,
 };
 
-use crate::utils::new_policy_execution_mode_from_str;
+use crate::utils::{new_policy_execution_mode_from_str, normalize_scheme};
 
 mod annotate;
 mod backend;
+mod capabilities;
 mod cli;
 mod completions;
+mod config;
+mod consistency;
+mod crd;
+mod evaluator_logs;
+mod fixtures;
 mod inspect;
+mod oci;
+mod oci_layout;
 mod policies;
 mod pull;
 mod push;
 mod rm;
+mod rules;
 mod run;
+mod sbom;
 mod scaffold;
+mod sign;
+mod store;
+mod template;
 mod utils;
+mod validate_metadata;
 mod verify;
 
 pub(crate) const KWCTL_VERIFICATION_CONFIG: &str = "verification-config.yml";
@@ -74,10 +90,22 @@ lazy_static! {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let matches = cli::build_cli().get_matches();
+    let matches = match cli::build_cli().try_get_matches() {
+        Ok(matches) => matches,
+        Err(e) => {
+            // `run` has its own exit codes, usage errors are reported
+            // as kwctl errors
+            if e.use_stderr() && subcommand_from_args().as_deref() == Some("run") {
+                e.print()?;
+                std::process::exit(run::KWCTL_ERROR_EXIT_CODE);
+            }
+            e.exit()
+        }
+    };
 
     // setup logging
-    let level_filter = if matches.is_present("verbose") {
+    let verbose = matches.is_present("verbose");
+    let level_filter = if verbose {
         LevelFilter::DEBUG
     } else {
         LevelFilter::INFO
@@ -88,67 +116,166 @@ async fn main() -> Result<()> {
         .add_directive("cranelift_wasm=off".parse().unwrap()) // this crate generates lots of tracing events we don't care about
         .add_directive("hyper=off".parse().unwrap()) // this crate generates lots of tracing events we don't care about
         .add_directive("regalloc=off".parse().unwrap()); // this crate generates lots of tracing events we don't care about
+
+    // the filter applies only to the messages printed to the standard
+    // error, the evaluator tracing logs are captured regardless of the verbosity
     tracing_subscriber::registry()
-        .with(filter_layer)
-        .with(fmt::layer().with_writer(std::io::stderr))
+        .with(
+            fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(filter_layer),
+        )
+        .with(evaluator_logs::layer())
         .init();
 
+    let config = match Config::load(matches.value_of("config").map(Path::new)) {
+        Ok(config) => config,
+        // like usage errors, an unusable config file is a kwctl error
+        Err(e) if matches.subcommand_name() == Some("run") => {
+            eprintln!("Error: {:?}", e);
+            std::process::exit(run::KWCTL_ERROR_EXIT_CODE);
+        }
+        Err(e) => return Err(e),
+    };
+
     match matches.subcommand_name() {
-        Some("policies") => policies::list(),
+        Some("policies") => {
+            if let Some(matches) = matches.subcommand_matches("policies") {
+                let output = inspect::OutputType::try_from(matches.value_of("output"))?;
+                policies::list(output)?;
+            }
+            Ok(())
+        }
         Some("pull") => {
             if let Some(matches) = matches.subcommand_matches("pull") {
-                let uri = matches.value_of("uri").unwrap();
-                let destination = matches
+                let uri: &str = &normalize_scheme(matches.value_of("uri").unwrap());
+                let output_path = matches
                     .value_of("output-path")
                     .map(|output| PathBuf::from_str(output).unwrap());
-                let destination = match destination {
-                    Some(destination) => PullDestination::LocalFile(destination),
-                    None => PullDestination::MainStore,
-                };
 
-                let (sources, docker_config) = remote_server_options(matches)?;
+                let (sources, docker_config) = remote_server_options(matches, &config)?;
 
-                let verification_options = verification_options(matches)?;
-                let mut verified_manifest_digest: Option<String> = None;
-                if verification_options.is_some() {
-                    let fulcio_and_rekor_data = build_fulcio_and_rekor_data(matches).await?;
-                    // verify policy prior to pulling if keys listed, and keep the
-                    // verified manifest digest:
-                    verified_manifest_digest = Some(
-                        verify::verify(
-                            uri,
-                            docker_config.as_ref(),
-                            sources.as_ref(),
-                            verification_options.as_ref().unwrap(),
-                            &fulcio_and_rekor_data,
-                        )
-                        .await
-                        .map_err(|e| anyhow!("Policy {} cannot be validated\n{:?}", uri, e))?,
-                    );
+                let uris =
+                    pull::expand_tag_glob(uri, docker_config.as_ref(), sources.as_ref()).await?;
+                if uris.len() > 1 && output_path.is_some() {
+                    return Err(anyhow!(
+                        "'output-path' cannot be used when pulling multiple policies"
+                    ));
+                }
+                let sbom_output = matches.value_of("sbom-output").map(PathBuf::from);
+                if uris.len() > 1 && sbom_output.is_some() {
+                    return Err(anyhow!(
+                        "'sbom-output' cannot be used when pulling multiple policies"
+                    ));
                 }
 
-                let policy =
-                    pull::pull(uri, docker_config.as_ref(), sources.as_ref(), destination).await?;
+                let jobs: usize = matches
+                    .value_of("jobs")
+                    .unwrap()
+                    .parse()
+                    .ok()
+                    .filter(|jobs| *jobs > 0)
+                    .ok_or_else(|| anyhow!("'jobs' must be a positive number"))?;
+
+                let force = matches.is_present("force");
+                let rewrites: Vec<pull::PrefixRewrite> = matches
+                    .values_of("rewrite-prefix")
+                    .map(|rewrites| {
+                        rewrites
+                            .map(pull::PrefixRewrite::from_str)
+                            .collect::<Result<_>>()
+                    })
+                    .transpose()?
+                    .unwrap_or_default();
+
+                let verification_options = verification_options(matches, &config)?;
+                let fulcio_and_rekor_data = if verification_options.is_some() {
+                    Some(build_fulcio_and_rekor_data(matches).await?)
+                } else {
+                    None
+                };
+                let verification = verification_options
+                    .as_ref()
+                    .zip(fulcio_and_rekor_data.as_ref());
 
-                if verification_options.is_some() {
-                    let fulcio_and_rekor_data = build_fulcio_and_rekor_data(matches).await?;
-                    verify::verify_local_checksum(
-                        &policy,
+                if uris.len() == 1 {
+                    let destination = match output_path {
+                        Some(destination) => PullDestination::LocalFile(destination),
+                        None => pull::store_destination(
+                            &uris[0],
+                            &pull::stored_uri(&uris[0], &rewrites),
+                        )?,
+                    };
+                    return pull_policy(
+                        &uris[0],
+                        destination,
                         docker_config.as_ref(),
                         sources.as_ref(),
-                        &verified_manifest_digest.unwrap(),
-                        &fulcio_and_rekor_data,
+                        verification,
+                        sbom_output.as_deref(),
+                        force,
                     )
-                    .await?
+                    .await;
+                }
+
+                // Pull multiple policies concurrently, reporting all the
+                // failures at the end
+                let docker_config = docker_config.as_ref();
+                let sources = sources.as_ref();
+                let rewrites = &rewrites;
+                let results: Vec<(&str, Result<()>)> = stream::iter(uris.iter())
+                    .map(|uri| async move {
+                        let result =
+                            match pull::store_destination(uri, &pull::stored_uri(uri, rewrites)) {
+                                Ok(destination) => {
+                                    pull_policy(
+                                        uri,
+                                        destination,
+                                        docker_config,
+                                        sources,
+                                        verification,
+                                        None,
+                                        force,
+                                    )
+                                    .await
+                                }
+                                Err(e) => Err(e),
+                            };
+                        match &result {
+                            Ok(_) => info!(policy = uri.as_str(), "policy pulled"),
+                            Err(e) => error!(
+                                policy = uri.as_str(),
+                                error = e.to_string().as_str(),
+                                "cannot pull policy"
+                            ),
+                        }
+                        (uri.as_str(), result)
+                    })
+                    .buffered(jobs)
+                    .collect()
+                    .await;
+
+                let failed: Vec<&str> = results
+                    .iter()
+                    .filter(|(_, result)| result.is_err())
+                    .map(|(uri, _)| *uri)
+                    .collect();
+                if !failed.is_empty() {
+                    return Err(anyhow!(
+                        "{} of {} policies could not be pulled: {}",
+                        failed.len(),
+                        results.len(),
+                        failed.join(", ")
+                    ));
                 }
             };
             Ok(())
         }
         Some("verify") => {
             if let Some(matches) = matches.subcommand_matches("verify") {
-                let uri = matches.value_of("uri").unwrap();
-                let (sources, docker_config) = remote_server_options(matches)?;
-                let verification_options = verification_options(matches)?
+                let uri: &str = &normalize_scheme(matches.value_of("uri").unwrap());
+                let (sources, docker_config) = remote_server_options(matches, &config)?;
+                let verification_options = verification_options(matches, &config)?
                     .ok_or_else(|| anyhow!("could not retrieve sigstore options"))?;
                 let fulcio_and_rekor_data = build_fulcio_and_rekor_data(matches).await?;
                 verify::verify(
@@ -165,14 +292,15 @@ async fn main() -> Result<()> {
         }
         Some("push") => {
             if let Some(matches) = matches.subcommand_matches("push") {
-                let (sources, docker_config) = remote_server_options(matches)?;
+                let (sources, docker_config) = remote_server_options(matches, &config)?;
                 let wasm_uri = crate::utils::map_path_to_uri(matches.value_of("policy").unwrap())?;
                 let wasm_path = crate::utils::wasm_path(wasm_uri.as_str())?;
                 let uri = matches
                     .value_of("uri")
+                    .map(normalize_scheme)
                     .map(|u| {
                         if u.starts_with("registry://") {
-                            String::from(u)
+                            u
                         } else {
                             format!("registry://{}", u)
                         }
@@ -186,13 +314,44 @@ async fn main() -> Result<()> {
                 );
 
                 let force = matches.is_present("force");
+                let annotations = match matches.values_of("annotation") {
+                    Some(values) => push::parse_annotations(values)?,
+                    None => BTreeMap::new(),
+                };
+
+                if matches.is_present("dry-run") {
+                    let plan = push::plan(wasm_path.to_path_buf(), &uri, force, annotations)?;
+                    match matches.value_of("output") {
+                        Some("json") => serde_json::to_writer(std::io::stdout(), &plan)?,
+                        _ => {
+                            println!("Dry run, nothing has been pushed");
+                            println!("reference: {}", plan.reference);
+                            println!("manifest digest: {}", plan.manifest_digest);
+                            println!(
+                                "config: {} {} ({} bytes)",
+                                plan.config.media_type, plan.config.digest, plan.config.size
+                            );
+                            for layer in plan.layers.iter() {
+                                println!(
+                                    "layer: {} {} ({} bytes)",
+                                    layer.media_type, layer.digest, layer.size
+                                );
+                            }
+                            for (key, value) in plan.annotations.iter() {
+                                println!("annotation: {}={}", key, value);
+                            }
+                        }
+                    }
+                    return Ok(());
+                }
 
                 let immutable_ref = push::push(
-                    wasm_path,
+                    wasm_path.to_path_buf(),
                     &uri,
                     docker_config.as_ref(),
                     sources.as_ref(),
                     force,
+                    annotations,
                 )
                 .await?;
 
@@ -209,90 +368,82 @@ async fn main() -> Result<()> {
             };
             Ok(())
         }
+        Some("sign") => {
+            if let Some(matches) = matches.subcommand_matches("sign") {
+                let uri: &str = &normalize_scheme(matches.value_of("uri").unwrap());
+                let (sources, docker_config) = remote_server_options(matches, &config)?;
+                let signing_mode =
+                    match (matches.value_of("key"), matches.value_of("identity-token")) {
+                        (Some(key), _) => sign::SigningMode::Key(PathBuf::from(key)),
+                        (None, Some(identity_token)) => sign::SigningMode::Keyless {
+                            identity_token: String::from(identity_token),
+                            fulcio_url: String::from(matches.value_of("fulcio-url").unwrap()),
+                            rekor_url: String::from(matches.value_of("rekor-url").unwrap()),
+                        },
+                        (None, None) => {
+                            return Err(anyhow!(
+                            "Either a key or an identity token must be provided to sign the policy"
+                        ))
+                        }
+                    };
+
+                let signature =
+                    sign::sign(uri, docker_config.as_ref(), sources.as_ref(), &signing_mode)
+                        .await?;
+                println!("Policy successfully signed: {}", signature);
+            }
+            Ok(())
+        }
         Some("rm") => {
             if let Some(matches) = matches.subcommand_matches("rm") {
-                let uri = matches.value_of("uri").unwrap();
+                let uri: &str = &normalize_scheme(matches.value_of("uri").unwrap());
                 rm::rm(uri)?;
             }
             Ok(())
         }
-        Some("run") => {
-            if let Some(matches) = matches.subcommand_matches("run") {
-                let uri = matches.value_of("uri").unwrap();
-                let request = match matches.value_of("request-path").unwrap() {
-                    "-" => {
-                        let mut buffer = String::new();
-                        io::stdin()
-                            .read_to_string(&mut buffer)
-                            .map_err(|e| anyhow!("Error reading request from stdin: {}", e))?;
-                        buffer
-                    }
-                    request_path => fs::read_to_string(request_path).map_err(|e| {
-                        anyhow!(
-                            "Error opening request file {}; {}",
-                            matches.value_of("request-path").unwrap(),
-                            e
-                        )
-                    })?,
-                };
-                if matches.is_present("settings-path") && matches.is_present("settings-json") {
+        Some("test") => {
+            if let Some(matches) = matches.subcommand_matches("test") {
+                let report = run_fixtures(matches, &config).await?;
+                if matches.value_of("output") == Some("json") {
+                    report.print_json()?;
+                } else {
+                    report.print_table(!matches.is_present("no-color"));
+                }
+                if report.failed > 0 {
                     return Err(anyhow!(
-                        "'settings-path' and 'settings-json' cannot be used at the same time"
+                        "{} of {} fixtures failed",
+                        report.failed,
+                        report.total
                     ));
                 }
-                let settings = if matches.is_present("settings-path") {
-                    matches
-                        .value_of("settings-path")
-                        .map(|settings| -> Result<String> {
-                            fs::read_to_string(settings).map_err(|e| {
-                                anyhow!("Error reading settings from {}: {}", settings, e)
-                            })
-                        })
-                        .transpose()?
-                } else if matches.is_present("settings-json") {
-                    Some(String::from(matches.value_of("settings-json").unwrap()))
-                } else {
-                    None
-                };
-                let (sources, docker_config) = remote_server_options(matches)
-                    .map_err(|e| anyhow!("Error getting remote server options: {}", e))?;
-                let execution_mode: Option<PolicyExecutionMode> =
-                    if let Some(mode_name) = matches.value_of("execution-mode") {
-                        Some(new_policy_execution_mode_from_str(mode_name)?)
-                    } else {
-                        None
-                    };
-
-                let verification_options = verification_options(matches)?;
-                let mut verified_manifest_digest: Option<String> = None;
-                let fulcio_and_rekor_data = build_fulcio_and_rekor_data(matches).await?;
-                if verification_options.is_some() {
-                    // verify policy prior to pulling if keys listed, and keep the
-                    // verified manifest digest:
-                    verified_manifest_digest = Some(
-                        verify::verify(
-                            uri,
-                            docker_config.as_ref(),
-                            sources.as_ref(),
-                            verification_options.as_ref().unwrap(),
-                            &fulcio_and_rekor_data,
-                        )
-                        .await
-                        .map_err(|e| anyhow!("Policy {} cannot be validated\n{:?}", uri, e))?,
-                    );
+            }
+            Ok(())
+        }
+        Some("store") => {
+            if let Some(matches) = matches.subcommand_matches("store") {
+                if let Some(matches) = matches.subcommand_matches("verify") {
+                    let sources = sources_option(matches, &config)?;
+                    let docker_config = docker_config_option(matches, &config, None)?;
+                    store::verify(
+                        docker_config.as_ref(),
+                        sources.as_ref(),
+                        matches.is_present("prune"),
+                    )
+                    .await?;
                 }
-
-                run::pull_and_run(
-                    uri,
-                    execution_mode,
-                    docker_config.as_ref(),
-                    sources.as_ref(),
-                    &request,
-                    settings,
-                    &verified_manifest_digest,
-                    &fulcio_and_rekor_data,
-                )
-                .await?;
+            }
+            Ok(())
+        }
+        Some("run") => {
+            if let Some(matches) = matches.subcommand_matches("run") {
+                let exit_code = match run_policy(matches, &config).await {
+                    Ok(outcome) => outcome.exit_code(),
+                    Err(e) => {
+                        eprintln!("Error: {:?}", e);
+                        run::KWCTL_ERROR_EXIT_CODE
+                    }
+                };
+                std::process::exit(exit_code);
             }
             Ok(())
         }
@@ -310,20 +461,127 @@ async fn main() -> Result<()> {
                     .value_of("output-path")
                     .map(|output| PathBuf::from_str(output).unwrap())
                     .unwrap();
-                annotate::write_annotation(wasm_path, metadata_file, destination)?;
+                let cargo_toml_path = matches.value_of("annotate-from-cargo").map(Path::new);
+                annotate::write_annotation(wasm_path, metadata_file, destination, cargo_toml_path)?;
             }
             Ok(())
         }
         Some("inspect") => {
             if let Some(matches) = matches.subcommand_matches("inspect") {
-                let uri = matches.value_of("uri").unwrap();
                 let output = inspect::OutputType::try_from(matches.value_of("output"))?;
-                let (sources, docker_config) = remote_server_options(matches)?;
+                let (sources, docker_config) = remote_server_options(matches, &config)?;
 
-                inspect::inspect(uri, output, sources, docker_config).await?;
+                if let Some(bundle_path) = matches.value_of("bundle") {
+                    return inspect::inspect_bundle(
+                        Path::new(bundle_path),
+                        output,
+                        sources,
+                        docker_config,
+                    )
+                    .await;
+                }
+                let uri = matches.value_of("uri").unwrap();
+                if matches.is_present("raw-metadata") {
+                    return inspect::inspect_raw_metadata(uri, output);
+                }
+                if let Some(template) = matches.value_of("template") {
+                    let template = template::Template::parse(template)?;
+                    return inspect::inspect_template(uri, &template);
+                }
+                if let Some(artifact_type) = matches.value_of("artifact-type") {
+                    return inspect::inspect_referrers(
+                        uri,
+                        artifact_type,
+                        output,
+                        sources,
+                        docker_config,
+                    )
+                    .await;
+                }
+                if let Some(destination) = matches.value_of("dump-wasm") {
+                    inspect::dump_wasm(uri, Path::new(destination), matches.is_present("force"))?;
+                    println!("Policy written to {}", destination);
+                    return Ok(());
+                }
+                if let Some(modes) = matches.values_of("compat") {
+                    let supported_modes = modes
+                        .map(new_policy_execution_mode_from_str)
+                        .collect::<Result<Vec<PolicyExecutionMode>>>()?;
+                    return inspect::check_compat(uri, &supported_modes);
+                }
+                if matches.is_present("check-rules") {
+                    return inspect::check_rules(uri);
+                }
+                if matches.is_present("size-breakdown") {
+                    return inspect::inspect_size_breakdown(uri, output, sources, docker_config)
+                        .await;
+                }
+
+                let annotations_selection =
+                    matches
+                        .values_of("annotation")
+                        .map(|keys| inspect::AnnotationsSelection {
+                            keys: keys.map(String::from).collect(),
+                            allow_missing: matches.is_present("allow-missing-annotations"),
+                        });
+
+                let accept_media_types: Vec<String> = matches
+                    .values_of("accept-media-type")
+                    .map(|media_types| media_types.map(String::from).collect())
+                    .unwrap_or_default();
+
+                let strict_signatures = matches.is_present("strict-signatures");
+
+                inspect::inspect(
+                    uri,
+                    output,
+                    sources.clone(),
+                    docker_config.clone(),
+                    annotations_selection,
+                    accept_media_types,
+                    matches.is_present("show-signers"),
+                    strict_signatures,
+                    matches.is_present("allow-remote-resources"),
+                    verbose,
+                )
+                .await?;
+
+                // signatures that are present but do not satisfy the
+                // verification options are a failure too
+                if strict_signatures {
+                    if let Some(verification_options) = verification_options(matches, &config)? {
+                        let fulcio_and_rekor_data = build_fulcio_and_rekor_data(matches).await?;
+                        verify::verify(
+                            uri,
+                            docker_config.as_ref(),
+                            sources.as_ref(),
+                            &verification_options,
+                            &fulcio_and_rekor_data,
+                        )
+                        .await
+                        .map_err(|e| {
+                            anyhow!("Policy {} signatures cannot be verified\n{:?}", uri, e)
+                        })?;
+                    }
+                }
             };
             Ok(())
         }
+        Some("validate-metadata") => {
+            if let Some(matches) = matches.subcommand_matches("validate-metadata") {
+                let uri = matches.value_of("uri").unwrap();
+                let required_annotations: Vec<String> =
+                    match matches.values_of("required-annotation") {
+                        Some(annotations) => annotations.map(String::from).collect(),
+                        None => validate_metadata::DEFAULT_REQUIRED_ANNOTATIONS
+                            .iter()
+                            .map(|annotation| String::from(*annotation))
+                            .collect(),
+                    };
+                validate_metadata::validate_metadata(uri, &required_annotations)?;
+            }
+            Ok(())
+        }
         Some("scaffold") => {
             if let Some(matches) = matches.subcommand_matches("scaffold") {
                 if let Some(_matches) = matches.subcommand_matches("verification-config") {
@@ -373,8 +631,8 @@ async fn main() -> Result<()> {
         }
         Some("digest") => {
             if let Some(matches) = matches.subcommand_matches("digest") {
-                let uri = matches.value_of("uri").unwrap();
-                let (sources, docker_config) = remote_server_options(matches)?;
+                let uri: &str = &normalize_scheme(matches.value_of("uri").unwrap());
+                let (sources, docker_config) = remote_server_options(matches, &config)?;
                 let registry = Registry::new(docker_config.as_ref());
                 let digest = registry.manifest_digest(uri, sources.as_ref()).await?;
                 println!("{}@{}", uri, digest);
@@ -390,37 +648,453 @@ async fn main() -> Result<()> {
     }
 }
 
-fn remote_server_options(matches: &ArgMatches) -> Result<(Option<Sources>, Option<DockerConfig>)> {
-    let sources = if let Some(sources_path) = matches.value_of("sources-path") {
-        Some(read_sources_file(Path::new(&sources_path))?)
+// Pulls a single policy, verifying it when verification options are provided
+async fn pull_policy(
+    uri: &str,
+    destination: PullDestination,
+    docker_config: Option<&DockerConfig>,
+    sources: Option<&Sources>,
+    verification: Option<(&LatestVerificationConfig, &FulcioAndRekorData)>,
+    sbom_output: Option<&Path>,
+    force: bool,
+) -> Result<()> {
+    if verification.is_some() && oci_layout::is_layout_uri(uri) {
+        return Err(anyhow!(
+            "The signatures of the policies read from an OCI layout cannot be verified"
+        ));
+    }
+    let mut verified_manifest_digest: Option<String> = None;
+    if let Some((verification_options, fulcio_and_rekor_data)) = verification {
+        // verify policy prior to pulling if keys listed, and keep the
+        // verified manifest digest:
+        verified_manifest_digest = Some(
+            verify::verify(
+                uri,
+                docker_config,
+                sources,
+                verification_options,
+                fulcio_and_rekor_data,
+            )
+            .await
+            .map_err(|e| anyhow!("Policy {} cannot be validated\n{:?}", uri, e))?,
+        );
+    }
+
+    let up_to_date_policy = if force {
+        None
     } else {
-        let sources_path = DEFAULT_ROOT.config_dir().join("sources.yaml");
-        if Path::exists(&sources_path) {
-            Some(read_sources_file(&sources_path)?)
+        // the policy is downloaded again when its freshness cannot be
+        // determined, like when the registry cannot be reached
+        pull::up_to_date_policy(uri, docker_config, sources, &destination)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(
+                    policy = uri,
+                    error = e.to_string().as_str(),
+                    "cannot check whether the local policy is up to date, downloading it"
+                );
+                None
+            })
+    };
+    match up_to_date_policy {
+        Some(policy) => {
+            println!("{}: up to date", uri);
+            if let Some((_, fulcio_and_rekor_data)) = verification {
+                verify::verify_local_checksum(
+                    &policy,
+                    docker_config,
+                    sources,
+                    verified_manifest_digest.as_ref().unwrap(),
+                    fulcio_and_rekor_data,
+                )
+                .await?
+            }
+        }
+        None => match verification {
+            // the policy is written to its destination only after its
+            // checksum has been verified too
+            Some((_, fulcio_and_rekor_data)) => {
+                pull::pull_verified(
+                    uri,
+                    docker_config,
+                    sources,
+                    destination,
+                    verified_manifest_digest.as_ref().unwrap(),
+                    fulcio_and_rekor_data,
+                )
+                .await?;
+            }
+            None => {
+                pull::pull(uri, docker_config, sources, destination).await?;
+            }
+        },
+    }
+
+    if let Some(sbom_output) = sbom_output {
+        sbom::pull_sbom(uri, docker_config, sources, sbom_output).await?;
+    }
+
+    Ok(())
+}
+
+// Evaluates a request against a policy, as requested by the `run` command
+async fn run_policy(matches: &ArgMatches, config: &Config) -> Result<run::EvaluationOutcome> {
+    let uri: &str = &normalize_scheme(matches.value_of("uri").unwrap());
+    let requests = match matches.value_of("object") {
+        Some(object_path) => {
+            let object = fs::read_to_string(object_path)
+                .map_err(|e| anyhow!("Error opening object file {}; {}", object_path, e))?;
+            vec![run::object_request(
+                object_path,
+                &object,
+                matches.value_of("operation").unwrap_or("CREATE"),
+            )?]
+        }
+        None => matches
+            .values_of("request-path")
+            .unwrap()
+            .map(|request_path| read_request(matches, request_path))
+            .collect::<Result<Vec<run::RequestInput>>>()?,
+    };
+    if matches.is_present("settings-path") && matches.is_present("settings-json") {
+        return Err(anyhow!(
+            "'settings-path' and 'settings-json' cannot be used at the same time"
+        ));
+    }
+    let settings = if let Some(settings_paths) = matches.values_of("settings-path") {
+        // multiple settings files are overlays, merged in order
+        let documents = settings_paths
+            .map(|settings| -> Result<String> {
+                fs::read_to_string(settings)
+                    .map_err(|e| anyhow!("Error reading settings from {}: {}", settings, e))
+            })
+            .collect::<Result<Vec<String>>>()?;
+        if documents.len() == 1 {
+            documents.into_iter().next()
         } else {
-            None
+            Some(run::merge_settings(&documents)?)
         }
+    } else if matches.is_present("settings-json") {
+        Some(String::from(matches.value_of("settings-json").unwrap()))
+    } else {
+        None
     };
+    let (sources, docker_config) = remote_server_options(matches, config)
+        .map_err(|e| anyhow!("Error getting remote server options: {}", e))?;
+    let execution_mode: Option<PolicyExecutionMode> =
+        if let Some(mode_name) = matches.value_of("execution-mode") {
+            Some(new_policy_execution_mode_from_str(mode_name)?)
+        } else {
+            None
+        };
 
-    let docker_config =
-        if let Some(docker_config_json_path) = matches.value_of("docker-config-json-path") {
-            Some(read_docker_config_json_file(Path::new(
-                docker_config_json_path,
-            ))?)
-        } else if let Some(user_dir) = UserDirs::new() {
-            let config_json_path = user_dir.home_dir().join(".docker").join("config.json");
-            if Path::exists(&config_json_path) {
-                Some(read_docker_config_json_file(&config_json_path)?)
-            } else {
-                None
+    let verification_options = verification_options(matches, config)?;
+    let mut verified_manifest_digest: Option<String> = None;
+    let fulcio_and_rekor_data = build_fulcio_and_rekor_data(matches).await?;
+    if verification_options.is_some() {
+        // verify policy prior to pulling if keys listed, and keep the
+        // verified manifest digest:
+        verified_manifest_digest = Some(
+            verify::verify(
+                uri,
+                docker_config.as_ref(),
+                sources.as_ref(),
+                verification_options.as_ref().unwrap(),
+                &fulcio_and_rekor_data,
+            )
+            .await
+            .map_err(|e| anyhow!("Policy {} cannot be validated\n{:?}", uri, e))?,
+        );
+    }
+
+    let output_file = matches
+        .value_of("output-file")
+        .map(|output| PathBuf::from_str(output).unwrap());
+    let response_view = if matches.is_present("quiet") {
+        run::ResponseView::Decision
+    } else if matches.is_present("show-validation-only") {
+        run::ResponseView::ValidationOnly
+    } else if matches.is_present("show-mutation-only") {
+        run::ResponseView::MutationOnly
+    } else {
+        run::ResponseView::Full
+    };
+    let template = matches
+        .value_of("template")
+        .map(template::Template::parse)
+        .transpose()?;
+    let evaluator_logs = matches
+        .value_of("save-evaluator-tracing-logs")
+        .map(evaluator_logs::LogsDestination::new)
+        .transpose()?;
+    let mutation_expectation = if matches.is_present("expect-mutation") {
+        Some(run::MutationExpectation::Mutation)
+    } else if matches.is_present("expect-no-mutation") {
+        Some(run::MutationExpectation::NoMutation)
+    } else {
+        None
+    };
+    let watch = if matches.is_present("watch") {
+        let request = match requests.as_slice() {
+            [request] if request.source != "-" => request,
+            _ => {
+                return Err(anyhow!(
+                    "'watch' requires a single request, read from a file"
+                ))
             }
+        };
+        Some(run::WatchSettings {
+            request_path: PathBuf::from(&request.source),
+            request_format: request.format,
+            settings_paths: matches
+                .values_of("settings-path")
+                .map(|paths| paths.map(PathBuf::from).collect())
+                .unwrap_or_default(),
+        })
+    } else {
+        None
+    };
+    let namespace_labels = matches
+        .values_of("namespace-labels")
+        .map(run::parse_namespace_labels)
+        .transpose()?;
+    let user_info = if matches.is_present("user") || matches.is_present("group") {
+        Some(run::UserInfo {
+            username: matches.value_of("user").map(String::from),
+            groups: matches
+                .values_of("group")
+                .map(|groups| groups.map(String::from).collect())
+                .unwrap_or_default(),
+        })
+    } else {
+        None
+    };
+    let summary_format = match matches.value_of("output") {
+        Some("yaml") => run::SummaryFormat::Yaml,
+        _ => run::SummaryFormat::Json,
+    };
+    let crd = matches
+        .value_of("crd")
+        .map(|crd_path| {
+            fs::read_to_string(crd_path)
+                .map_err(|e| anyhow!("Error reading CRD from {}: {}", crd_path, e))
+        })
+        .transpose()?;
+
+    run::pull_and_run(&run::PullAndRunSettings {
+        uri: String::from(uri),
+        user_execution_mode: execution_mode,
+        sources,
+        docker_config,
+        requests,
+        settings,
+        verified_manifest_digest,
+        fulcio_and_rekor_data,
+        output_file,
+        response_view,
+        crd,
+        mutation_expectation,
+        summary_format,
+        show_responses: matches.is_present("show-responses"),
+        namespace_labels,
+        watch,
+        user_info,
+        template,
+        evaluator_logs,
+    })
+    .await
+}
+
+// Evaluates the fixtures of a directory against a policy, as requested by
+// the `test` command
+async fn run_fixtures(matches: &ArgMatches, config: &Config) -> Result<fixtures::TestReport> {
+    let uri = matches.value_of("uri").unwrap();
+    let dir = Path::new(matches.value_of("dir").unwrap());
+    let fixtures = fixtures::load_fixtures(dir)?;
+    let settings_path = dir.join(fixtures::SETTINGS_FILE);
+    let settings = if settings_path.exists() {
+        Some(fs::read_to_string(&settings_path).map_err(|e| {
+            anyhow!(
+                "Error reading settings from {}: {}",
+                settings_path.display(),
+                e
+            )
+        })?)
+    } else {
+        None
+    };
+    let (sources, docker_config) = remote_server_options(matches, config)
+        .map_err(|e| anyhow!("Error getting remote server options: {}", e))?;
+    let execution_mode: Option<PolicyExecutionMode> =
+        if let Some(mode_name) = matches.value_of("execution-mode") {
+            Some(new_policy_execution_mode_from_str(mode_name)?)
         } else {
             None
         };
+
+    let responses = run::pull_and_evaluate(&run::PullAndRunSettings {
+        uri: String::from(uri),
+        user_execution_mode: execution_mode,
+        sources,
+        docker_config,
+        requests: fixtures
+            .iter()
+            .map(|fixture| fixture.request.clone())
+            .collect(),
+        settings,
+        verified_manifest_digest: None,
+        // the policy is not verified, hence no trust root is fetched
+        fulcio_and_rekor_data: FulcioAndRekorData::FromCustomData {
+            fulcio_certs: Vec::new(),
+            rekor_public_key: None,
+        },
+        output_file: None,
+        // nothing but the report is printed
+        response_view: run::ResponseView::Decision,
+        crd: None,
+        mutation_expectation: None,
+        summary_format: run::SummaryFormat::Json,
+        show_responses: false,
+        namespace_labels: None,
+        watch: None,
+        user_info: None,
+        template: None,
+        evaluator_logs: None,
+    })
+    .await?;
+
+    Ok(fixtures::TestReport::new(
+        fixtures
+            .iter()
+            .zip(responses.into_iter())
+            .map(|(fixture, response)| fixtures::FixtureResult::new(fixture, response))
+            .collect(),
+    ))
+}
+
+// Reads one of the requests to be evaluated by the `run` command
+fn read_request(matches: &ArgMatches, request_path: &str) -> Result<run::RequestInput> {
+    let contents = match request_path {
+        "-" => {
+            let mut buffer = String::new();
+            io::stdin()
+                .read_to_string(&mut buffer)
+                .map_err(|e| anyhow!("Error reading request from stdin: {}", e))?;
+            buffer
+        }
+        request_path => fs::read_to_string(request_path)
+            .map_err(|e| anyhow!("Error opening request file {}; {}", request_path, e))?,
+    };
+    // when no format is given, YAML files are detected by their extension
+    let format = match matches.value_of("request-format") {
+        Some("yaml") => run::RequestFormat::Yaml,
+        _ if matches.occurrences_of("request-format") == 0
+            && (request_path.ends_with(".yaml") || request_path.ends_with(".yml")) =>
+        {
+            run::RequestFormat::Yaml
+        }
+        _ => run::RequestFormat::Json,
+    };
+
+    Ok(run::RequestInput {
+        source: String::from(request_path),
+        contents,
+        format,
+    })
+}
+
+// Finds the name of the subcommand inside of the CLI arguments, without
+// relying on clap. Used only when the arguments cannot be parsed.
+fn subcommand_from_args() -> Option<String> {
+    subcommand_from(std::env::args().skip(1))
+}
+
+// The subcommand is the first argument that is neither an option of kwctl
+// nor the value of one of them, like the path following `--config`
+fn subcommand_from(mut args: impl Iterator<Item = String>) -> Option<String> {
+    let cli = cli::build_cli();
+    let options_with_value: Vec<String> = cli
+        .get_arguments()
+        .filter(|arg| arg.is_takes_value_set())
+        .filter_map(|arg| arg.get_long())
+        .map(|long| format!("--{}", long))
+        .collect();
+
+    while let Some(arg) = args.next() {
+        if options_with_value.contains(&arg) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            return Some(arg);
+        }
+    }
+    None
+}
+
+fn remote_server_options(
+    matches: &ArgMatches,
+    config: &Config,
+) -> Result<(Option<Sources>, Option<DockerConfig>)> {
+    let sources = sources_option(matches, config)?;
+    let uri = matches.value_of("uri").map(normalize_scheme);
+    let docker_config = if let Some(credentials) = matches.value_of("registry-auth") {
+        let uri = uri
+            .as_deref()
+            .ok_or_else(|| anyhow!("'registry-auth' requires a policy URI"))?;
+        Some(oci::inline_docker_config(uri, credentials)?)
+    } else {
+        docker_config_option(matches, config, uri.as_deref())?
+    };
     Ok((sources, docker_config))
 }
 
-fn verification_options(matches: &ArgMatches) -> Result<Option<LatestVerificationConfig>> {
+// Reads the sources file given via the `sources-path` flag, the
+// configuration file or found at its default location
+fn sources_option(matches: &ArgMatches, config: &Config) -> Result<Option<Sources>> {
+    if let Some(sources_path) = matches.value_of("sources-path") {
+        Ok(Some(read_sources_file(Path::new(&sources_path))?))
+    } else if let Some(sources_path) = &config.sources_path {
+        Ok(Some(read_sources_file(sources_path)?))
+    } else {
+        let sources_path = DEFAULT_ROOT.config_dir().join("sources.yaml");
+        if Path::exists(&sources_path) {
+            Ok(Some(read_sources_file(&sources_path)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// Reads the docker config file given via the `docker-config-json-path`
+// flag, the configuration file or found at its default location. The
+// credential helpers are invoked for the registry of `uri`, when given
+fn docker_config_option(
+    matches: &ArgMatches,
+    config: &Config,
+    uri: Option<&str>,
+) -> Result<Option<DockerConfig>> {
+    if let Some(docker_config_json_path) = matches.value_of("docker-config-json-path") {
+        Ok(Some(oci::read_docker_config(
+            Path::new(docker_config_json_path),
+            uri,
+        )?))
+    } else if let Some(docker_config_json_path) = &config.docker_config_json_path {
+        Ok(Some(oci::read_docker_config(docker_config_json_path, uri)?))
+    } else if let Some(user_dir) = UserDirs::new() {
+        let config_json_path = user_dir.home_dir().join(".docker").join("config.json");
+        if Path::exists(&config_json_path) {
+            Ok(Some(oci::read_docker_config(&config_json_path, uri)?))
+        } else {
+            Ok(None)
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+fn verification_options(
+    matches: &ArgMatches,
+    config: &Config,
+) -> Result<Option<LatestVerificationConfig>> {
     if let Some(verification_config) = build_verification_options_from_flags(matches)? {
         // flags present, built configmap from them:
         if matches.is_present("verification-config-path") {
@@ -435,6 +1109,8 @@ fn verification_options(matches: &ArgMatches) -> Result<Option<LatestVerificatio
         return Ok(Some(read_verification_file(Path::new(
             &verification_config_path,
         ))?));
+    } else if let Some(verification_config_path) = &config.verification_config_path {
+        Ok(Some(read_verification_file(verification_config_path)?))
     } else {
         let verification_config_path = DEFAULT_ROOT.config_dir().join(KWCTL_VERIFICATION_CONFIG);
         if Path::exists(&verification_config_path) {
@@ -609,3 +1285,50 @@ async fn build_fulcio_and_rekor_data(matches: &ArgMatches) -> Result<FulcioAndRe
         Ok(FulcioAndRekorData::FromTufRepository { repo })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_remote_server_options() -> Result<()> {
+        let matches = cli::build_cli().try_get_matches_from(vec![
+            "kwctl",
+            "digest",
+            "--registry-auth",
+            "user:password",
+            "registry://ghcr.io/kubewarden/policies/psp:v0.1.0",
+        ])?;
+        let matches = matches.subcommand_matches("digest").unwrap();
+
+        let (_, docker_config) = remote_server_options(matches, &Config::default())?;
+        assert!(docker_config.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_subcommand_from_args() {
+        let args = |args: &[&str]| {
+            args.iter()
+                .map(|arg| String::from(*arg))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            subcommand_from(args(&["run", "--bogus"]).into_iter()).as_deref(),
+            Some("run")
+        );
+        assert_eq!(
+            subcommand_from(args(&["--config", "c.yaml", "run", "--bogus"]).into_iter()).as_deref(),
+            Some("run")
+        );
+        assert_eq!(
+            subcommand_from(args(&["-v", "--config=c.yaml", "run"]).into_iter()).as_deref(),
+            Some("run")
+        );
+        assert_eq!(
+            subcommand_from(args(&["--config", "c.yaml"]).into_iter()),
+            None
+        );
+    }
+}
@@ -0,0 +1,1067 @@
+use anyhow::{anyhow, Result};
+use policy_evaluator::policy_fetcher::{
+    oci_distribution::{
+        client::{Client, ClientConfig as OciClientConfig, Config, ImageLayer},
+        manifest::OciImageManifest,
+        secrets::RegistryAuth,
+        Reference,
+    },
+    registry::config::DockerConfig,
+    sigstore::registry::{Auth, CertificateEncoding, ClientConfig, ClientProtocol},
+    sources::Sources,
+};
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
+    future::Future,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+use tracing::{debug, warn};
+use url::Url;
+
+// Number of tags requested to the registry for each page of results
+const TAGS_PAGE_SIZE: usize = 100;
+
+pub(crate) const OCI_IMAGE_MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+pub(crate) const OCI_IMAGE_INDEX_MEDIA_TYPE: &str = "application/vnd.oci.image.index.v1+json";
+pub(crate) const DOCKER_IMAGE_MANIFEST_MEDIA_TYPE: &str =
+    "application/vnd.docker.distribution.manifest.v2+json";
+pub(crate) const DOCKER_MANIFEST_LIST_MEDIA_TYPE: &str =
+    "application/vnd.docker.distribution.manifest.list.v2+json";
+pub(crate) const OCI_ARTIFACT_MANIFEST_MEDIA_TYPE: &str =
+    "application/vnd.oci.artifact.manifest.v1+json";
+
+/// A reference to an OCI artifact, split into its components
+#[derive(Debug, PartialEq)]
+pub(crate) struct ImageReference {
+    pub registry: String,
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl ImageReference {
+    /// Parses references like `registry://ghcr.io/kubewarden/policies/psp:v0.1.0`.
+    /// The `registry://` scheme is optional. Like docker does, references
+    /// whose first component is not a host, e.g. `kubewarden/psp:v1` or
+    /// `busybox:v1`, refer to images hosted on Docker Hub.
+    ///
+    /// Unlike the parser of oci-distribution, no validation is done on the
+    /// tag: this allows tags to be glob patterns.
+    pub(crate) fn parse(uri: &str) -> Result<Self> {
+        let image = uri.strip_prefix("registry://").unwrap_or(uri);
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(String::from(digest))),
+            None => (image, None),
+        };
+        let (registry, path) = match name.split_once('/') {
+            Some((host, path))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                (host, path)
+            }
+            _ => ("docker.io", name),
+        };
+        let (repository, tag) = match path.rsplit_once(':') {
+            Some((repository, tag)) => (repository, Some(String::from(tag))),
+            None => (path, None),
+        };
+        if registry.is_empty() || repository.is_empty() {
+            return Err(anyhow!("invalid image reference '{}'", uri));
+        }
+
+        Ok(ImageReference {
+            registry: String::from(registry),
+            repository: String::from(repository),
+            tag,
+            digest,
+        })
+    }
+
+    /// The host to be contacted to interact with the registry
+    fn api_host(&self) -> &str {
+        match self.registry.as_str() {
+            "docker.io" => "registry-1.docker.io",
+            registry => registry,
+        }
+    }
+
+    /// The repository name used by the distribution API
+    fn api_repository(&self) -> String {
+        if self.registry == "docker.io" && !self.repository.contains('/') {
+            format!("library/{}", self.repository)
+        } else {
+            self.repository.clone()
+        }
+    }
+
+    /// The reference without tag and digest, e.g. `ghcr.io/kubewarden/policies/psp`
+    pub(crate) fn name(&self) -> String {
+        format!("{}/{}", self.registry, self.repository)
+    }
+}
+
+/// Builds the sigstore `Auth` object to be used to interact with the registry
+/// hosting `image_name`, using the credentials found inside of `docker_config`
+pub(crate) fn sigstore_auth(
+    image_name: &str,
+    docker_config: Option<&DockerConfig>,
+) -> Result<Auth> {
+    let docker_config = match docker_config {
+        Some(docker_config) => docker_config,
+        None => return Ok(Auth::Anonymous),
+    };
+
+    let sigstore_auth: Option<Result<Auth>> = docker_config
+        .auth(image_name)
+        .map_err(|e| {
+            anyhow!(
+                "Cannot build Auth object for image '{}': {:?}",
+                image_name,
+                e
+            )
+        })?
+        .map(|ra| {
+            let a: Result<Auth> = TryInto::<Auth>::try_into(ra);
+            a
+        });
+
+    match sigstore_auth {
+        None => Ok(Auth::Anonymous),
+        Some(sa) => sa,
+    }
+}
+
+/// Builds a `DockerConfig` holding the given `user:password` credentials
+/// for the registry of the `uri` policy
+pub(crate) fn inline_docker_config(uri: &str, credentials: &str) -> Result<DockerConfig> {
+    if uri.contains("://") && !uri.starts_with("registry://") {
+        return Err(anyhow!(
+            "Registry credentials can be used only with registry:// URIs"
+        ));
+    }
+    let image = ImageReference::parse(uri)?;
+    let (username, password) = credentials
+        .split_once(':')
+        .filter(|(username, _)| !username.is_empty())
+        .ok_or_else(|| {
+            anyhow!("Invalid registry credentials: the `user:password` format must be used")
+        })?;
+
+    debug!(
+        registry = image.registry.as_str(),
+        username,
+        password = "<redacted>",
+        "using inline registry credentials"
+    );
+
+    let docker_config = serde_json::json!({
+        "auths": {
+            image.registry.as_str(): {
+                "auth": base64::encode(format!("{}:{}", username, password)),
+            }
+        }
+    });
+    serde_json::from_value(docker_config)
+        .map_err(|e| anyhow!("Cannot build registry credentials: {}", e))
+}
+
+/// Output of the `get` command of a docker credential helper
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HelperCredentials {
+    username: String,
+    secret: String,
+}
+
+/// Username returned by credential helpers when the secret is an identity
+/// token, not a password
+const IDENTITY_TOKEN_USERNAME: &str = "<token>";
+
+/// Reads the docker config file at `path`. When a credential helper is
+/// configured for the registry of `uri`, via `credHelpers` or `credsStore`,
+/// the helper is invoked to obtain fresh credentials, which take
+/// precedence over the static ones of the file.
+pub(crate) fn read_docker_config(path: &Path, uri: Option<&str>) -> Result<DockerConfig> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Cannot read docker config {}: {}", path.display(), e))?;
+    let mut raw_config: serde_json::Value = serde_json::from_str(&contents)
+        .map_err(|e| anyhow!("Invalid docker config {}: {}", path.display(), e))?;
+
+    let registry = uri
+        .filter(|uri| !uri.contains("://") || uri.starts_with("registry://"))
+        .and_then(|uri| ImageReference::parse(uri).ok())
+        .map(|image| image.registry);
+    if let Some(registry) = registry {
+        if let Some(helper) = credential_helper(&raw_config, &registry) {
+            let server_url = helper_server_url(&registry);
+            match run_credential_helper(&helper, &server_url) {
+                Ok(credentials) => {
+                    debug!(
+                        registry = registry.as_str(),
+                        helper = helper.as_str(),
+                        "using credentials obtained from credential helper"
+                    );
+                    add_auth(&mut raw_config, &registry, &credentials)?;
+                }
+                Err(e) => warn!(
+                    registry = registry.as_str(),
+                    helper = helper.as_str(),
+                    error = e.to_string().as_str(),
+                    "cannot obtain credentials from credential helper"
+                ),
+            }
+        }
+    }
+
+    // `credHelpers` and `credsStore` are not known by `DockerConfig`
+    if let Some(raw_config) = raw_config.as_object_mut() {
+        raw_config.remove("credHelpers");
+        raw_config.remove("credsStore");
+    }
+    serde_json::from_value(raw_config)
+        .map_err(|e| anyhow!("Invalid docker config {}: {}", path.display(), e))
+}
+
+// Finds the name of the credential helper to be used for `registry`. The
+// helpers configured for a specific registry take precedence over the
+// default credentials store
+fn credential_helper(raw_config: &serde_json::Value, registry: &str) -> Option<String> {
+    raw_config
+        .get("credHelpers")
+        .and_then(|helpers| helpers.get(registry))
+        .or_else(|| raw_config.get("credsStore"))
+        .and_then(serde_json::Value::as_str)
+        .filter(|helper| !helper.is_empty())
+        .map(String::from)
+}
+
+// The server URL used by docker to identify the registry
+fn helper_server_url(registry: &str) -> String {
+    match registry {
+        "docker.io" => String::from("https://index.docker.io/v1/"),
+        registry => String::from(registry),
+    }
+}
+
+// Runs the `get` command of the `docker-credential-<helper>` program
+fn run_credential_helper(helper: &str, server_url: &str) -> Result<HelperCredentials> {
+    let program = format!("docker-credential-{}", helper);
+    let mut child = Command::new(&program)
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("Cannot run {}: {}", program, e))?;
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("Cannot write to the stdin of {}", program))?
+        .write_all(server_url.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stdout).trim()
+        ));
+    }
+
+    parse_helper_output(&output.stdout)
+}
+
+fn parse_helper_output(output: &[u8]) -> Result<HelperCredentials> {
+    let credentials: HelperCredentials = serde_json::from_slice(output)
+        .map_err(|e| anyhow!("Invalid output of the credential helper: {}", e))?;
+    if credentials.username == IDENTITY_TOKEN_USERNAME {
+        return Err(anyhow!("identity tokens are not supported"));
+    }
+    Ok(credentials)
+}
+
+fn add_auth(
+    raw_config: &mut serde_json::Value,
+    registry: &str,
+    credentials: &HelperCredentials,
+) -> Result<()> {
+    let raw_config = raw_config
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Invalid docker config: not an object"))?;
+    let auths = raw_config
+        .entry("auths")
+        .or_insert_with(|| serde_json::json!({}))
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Invalid docker config: auths is not an object"))?;
+    auths.insert(
+        String::from(registry),
+        serde_json::json!({
+            "auth": base64::encode(format!("{}:{}", credentials.username, credentials.secret)),
+        }),
+    );
+    Ok(())
+}
+
+/// Pushes an image made by the given layers and config to `image_name`,
+/// adding `annotations` to its manifest
+pub(crate) async fn push_image(
+    image_name: &str,
+    layers: &[ImageLayer],
+    config: Config,
+    annotations: Option<HashMap<String, String>>,
+    docker_config: Option<&DockerConfig>,
+    sources: Option<&Sources>,
+) -> Result<()> {
+    let reference: Reference = image_name
+        .parse()
+        .map_err(|e| anyhow!("invalid reference '{}': {:?}", image_name, e))?;
+    let auth = match sigstore_auth(image_name, docker_config)? {
+        Auth::Anonymous => RegistryAuth::Anonymous,
+        Auth::Basic(username, password) => RegistryAuth::Basic(username, password),
+    };
+    let client_config: ClientConfig = sources.cloned().unwrap_or_default().into();
+    let mut client = Client::new(OciClientConfig::from(client_config));
+
+    let image_manifest = OciImageManifest::build(layers, &config, annotations);
+    client
+        .push(&reference, layers, config, &auth, Some(image_manifest))
+        .await
+        .map_err(|e| anyhow!("{:?}", e))?;
+
+    Ok(())
+}
+
+#[derive(Clone)]
+enum Credentials {
+    Basic(String, String),
+    Bearer(String),
+}
+
+#[derive(Deserialize)]
+struct TagsList {
+    tags: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+/// An artifact referring to an image, as listed by the Referrers API
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Referrer {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct ReferrersIndex {
+    #[serde(default)]
+    manifests: Vec<Referrer>,
+}
+
+/// A page of the tags list returned by the registry
+#[derive(Debug)]
+struct TagsPage {
+    tags: Vec<String>,
+    next: Option<Url>,
+}
+
+/// Client of the OCI distribution API, covering the operations that are
+/// not offered by the `Registry` object of policy-fetcher
+pub(crate) struct OciClient {
+    client: reqwest::Client,
+    protocol: &'static str,
+    auth: Auth,
+    credentials: Mutex<Option<Credentials>>,
+}
+
+impl OciClient {
+    pub(crate) fn new(
+        image: &ImageReference,
+        sources: Option<&Sources>,
+        docker_config: Option<&DockerConfig>,
+    ) -> Result<Self> {
+        let client_config: ClientConfig = sources.cloned().unwrap_or_default().into();
+        let protocol = match &client_config.protocol {
+            ClientProtocol::Http => "http",
+            ClientProtocol::Https => "https",
+            ClientProtocol::HttpsExcept(insecure_hosts) => {
+                if insecure_hosts.iter().any(|host| host == &image.registry) {
+                    "http"
+                } else {
+                    "https"
+                }
+            }
+        };
+
+        let mut client_builder = reqwest::Client::builder()
+            .danger_accept_invalid_certs(client_config.accept_invalid_certificates);
+        for certificate in &client_config.extra_root_certificates {
+            let certificate = match certificate.encoding {
+                CertificateEncoding::Der => reqwest::Certificate::from_der(&certificate.data),
+                CertificateEncoding::Pem => reqwest::Certificate::from_pem(&certificate.data),
+            }
+            .map_err(|e| anyhow!("Invalid certificate authority: {}", e))?;
+            client_builder = client_builder.add_root_certificate(certificate);
+        }
+
+        Ok(OciClient {
+            client: client_builder.build()?,
+            protocol,
+            auth: sigstore_auth(&image.name(), docker_config)?,
+            credentials: Mutex::new(None),
+        })
+    }
+
+    fn api_url(&self, image: &ImageReference, path: &str) -> Result<Url> {
+        Url::parse(&format!(
+            "{}://{}/v2/{}/{}",
+            self.protocol,
+            image.api_host(),
+            image.api_repository(),
+            path
+        ))
+        .map_err(|e| anyhow!("Cannot build registry url: {}", e))
+    }
+
+    /// Lists all the tags of the repository of `image`. The pagination
+    /// performed by the registry is handled transparently
+    pub(crate) async fn list_tags(&self, image: &ImageReference) -> Result<Vec<String>> {
+        let mut first_page = self.api_url(image, "tags/list")?;
+        first_page
+            .query_pairs_mut()
+            .append_pair("n", &TAGS_PAGE_SIZE.to_string());
+
+        let client = self;
+        collect_tags(first_page, move |url| client.fetch_tags_page(url)).await
+    }
+
+    /// Fetches the manifest identified by `reference` (a tag or a digest)
+    /// inside of the repository of `image`. Returns the media type of the
+    /// manifest together with its raw contents, or `None` when the manifest
+    /// does not exist
+    pub(crate) async fn fetch_manifest(
+        &self,
+        image: &ImageReference,
+        reference: &str,
+        accepted_media_types: &[&str],
+    ) -> Result<Option<(String, Vec<u8>)>> {
+        let url = self.api_url(image, &format!("manifests/{}", reference))?;
+        let response = self.get(url, &accepted_media_types.join(", ")).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response
+            .error_for_status()
+            .map_err(|e| anyhow!("Cannot fetch manifest {}: {}", reference, e))?;
+        let media_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|media_type| media_type.to_str().ok())
+            .map(String::from)
+            .unwrap_or_default();
+        let manifest = response.bytes().await?.to_vec();
+
+        Ok(Some((media_type, manifest)))
+    }
+
+    /// Fetches the blob identified by `digest` inside of the repository of
+    /// `image`. The contents of the blob are checked against the digest
+    pub(crate) async fn fetch_blob(&self, image: &ImageReference, digest: &str) -> Result<Vec<u8>> {
+        let url = self.api_url(image, &format!("blobs/{}", digest))?;
+        let blob = self
+            .get(url, "*/*")
+            .await?
+            .error_for_status()
+            .map_err(|e| anyhow!("Cannot fetch blob {}: {}", digest, e))?
+            .bytes()
+            .await?
+            .to_vec();
+
+        let actual_digest = format!("sha256:{:x}", Sha256::digest(&blob));
+        if actual_digest != digest {
+            return Err(anyhow!(
+                "Digest mismatch for blob {}: got {}",
+                digest,
+                actual_digest
+            ));
+        }
+
+        Ok(blob)
+    }
+
+    /// Lists the artifacts referring to the manifest identified by `digest`
+    /// inside of the repository of `image`, using the Referrers API. When
+    /// `artifact_type` is given, only the artifacts of that type are returned
+    pub(crate) async fn list_referrers(
+        &self,
+        image: &ImageReference,
+        digest: &str,
+        artifact_type: Option<&str>,
+    ) -> Result<Vec<Referrer>> {
+        let mut first_page = self.api_url(image, &format!("referrers/{}", digest))?;
+        if let Some(artifact_type) = artifact_type {
+            first_page
+                .query_pairs_mut()
+                .append_pair("artifactType", artifact_type);
+        }
+
+        let mut referrers = Vec::new();
+        let mut next = Some(first_page);
+        while let Some(url) = next {
+            let response = self.get(url.clone(), OCI_IMAGE_INDEX_MEDIA_TYPE).await?;
+            if response.status() == StatusCode::NOT_FOUND {
+                return Err(anyhow!(
+                    "The registry {} does not support the Referrers API",
+                    image.registry
+                ));
+            }
+            let response = response
+                .error_for_status()
+                .map_err(|e| anyhow!("Cannot list referrers of {}: {}", digest, e))?;
+            next = next_page_url(
+                &url,
+                response
+                    .headers()
+                    .get(header::LINK)
+                    .and_then(|link| link.to_str().ok()),
+            )?;
+            let index: ReferrersIndex = response
+                .json()
+                .await
+                .map_err(|e| anyhow!("Cannot decode list of referrers: {}", e))?;
+            referrers.extend(index.manifests);
+        }
+
+        // registries are not required to apply the filter
+        Ok(filter_referrers(referrers, artifact_type))
+    }
+
+    async fn fetch_tags_page(&self, url: Url) -> Result<TagsPage> {
+        let response = self
+            .get(url.clone(), "application/json")
+            .await?
+            .error_for_status()
+            .map_err(|e| anyhow!("Cannot list tags: {}", e))?;
+        let next = next_page_url(
+            &url,
+            response
+                .headers()
+                .get(header::LINK)
+                .and_then(|link| link.to_str().ok()),
+        )?;
+        let tags_list: TagsList = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Cannot decode list of tags: {}", e))?;
+
+        Ok(TagsPage {
+            tags: tags_list.tags.unwrap_or_default(),
+            next,
+        })
+    }
+
+    /// Uploads `data` as a blob inside of the repository of `image`, unless
+    /// the registry already has it. Returns the digest of the blob
+    pub(crate) async fn push_blob(&self, image: &ImageReference, data: &[u8]) -> Result<String> {
+        let digest = format!("sha256:{:x}", Sha256::digest(data));
+        let blob_url = self.api_url(image, &format!("blobs/{}", digest))?;
+        if self
+            .send(|client| client.head(blob_url.clone()))
+            .await?
+            .status()
+            .is_success()
+        {
+            return Ok(digest);
+        }
+
+        let uploads_url = self.api_url(image, "blobs/uploads/")?;
+        let response = self
+            .send(|client| {
+                client
+                    .post(uploads_url.clone())
+                    .header(header::CONTENT_LENGTH, 0)
+            })
+            .await?
+            .error_for_status()
+            .map_err(|e| anyhow!("Cannot start the upload of blob {}: {}", digest, e))?;
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Registry did not return the upload location of blob {}",
+                    digest
+                )
+            })?;
+        // the location can be relative to the uploads url
+        let mut upload_url = uploads_url
+            .join(location)
+            .map_err(|e| anyhow!("Invalid upload location '{}': {}", location, e))?;
+        upload_url.query_pairs_mut().append_pair("digest", &digest);
+
+        self.send(|client| {
+            client
+                .put(upload_url.clone())
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .body(data.to_vec())
+        })
+        .await?
+        .error_for_status()
+        .map_err(|e| anyhow!("Cannot upload blob {}: {}", digest, e))?;
+
+        Ok(digest)
+    }
+
+    /// Uploads `manifest` as is, tagging it with `reference` (a tag or a
+    /// digest) inside of the repository of `image`
+    pub(crate) async fn push_manifest(
+        &self,
+        image: &ImageReference,
+        reference: &str,
+        media_type: &str,
+        manifest: &[u8],
+    ) -> Result<()> {
+        let url = self.api_url(image, &format!("manifests/{}", reference))?;
+        self.send(|client| {
+            client
+                .put(url.clone())
+                .header(header::CONTENT_TYPE, media_type)
+                .body(manifest.to_vec())
+        })
+        .await?
+        .error_for_status()
+        .map_err(|e| anyhow!("Cannot push manifest {}: {}", reference, e))?;
+
+        Ok(())
+    }
+
+    /// Performs a GET request
+    async fn get(&self, url: Url, accept: &str) -> Result<reqwest::Response> {
+        self.send(|client| client.get(url.clone()).header(header::ACCEPT, accept))
+            .await
+    }
+
+    /// Sends the request created by `build`, handling the authentication
+    /// challenge returned by the registry
+    async fn send<F>(&self, build: F) -> Result<reqwest::Response>
+    where
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let response = self.authorize(build(&self.client)).send().await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        let challenge = response
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .and_then(|challenge| challenge.to_str().ok())
+            .map(String::from)
+            .ok_or_else(|| anyhow!("Registry requires authentication for {}", response.url()))?;
+        self.authenticate(&challenge).await?;
+
+        Ok(self.authorize(build(&self.client)).send().await?)
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.credentials.lock().unwrap().clone() {
+            Some(Credentials::Basic(username, password)) => {
+                request.basic_auth(username, Some(password))
+            }
+            Some(Credentials::Bearer(token)) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn authenticate(&self, challenge: &str) -> Result<()> {
+        let (scheme, params) = parse_www_authenticate(challenge)
+            .ok_or_else(|| anyhow!("Invalid authentication challenge: {}", challenge))?;
+
+        let credentials = if scheme.eq_ignore_ascii_case("basic") {
+            match &self.auth {
+                Auth::Basic(username, password) => {
+                    Credentials::Basic(username.clone(), password.clone())
+                }
+                Auth::Anonymous => {
+                    return Err(anyhow!(
+                        "Registry requires credentials, but none have been provided"
+                    ))
+                }
+            }
+        } else if scheme.eq_ignore_ascii_case("bearer") {
+            let realm = params
+                .get("realm")
+                .ok_or_else(|| anyhow!("Invalid authentication challenge: {}", challenge))?;
+            let query: Vec<(&str, &String)> = ["service", "scope"]
+                .iter()
+                .filter_map(|key| params.get(*key).map(|value| (*key, value)))
+                .collect();
+            let mut request = self.client.get(realm.as_str()).query(&query);
+            if let Auth::Basic(username, password) = &self.auth {
+                request = request.basic_auth(username, Some(password));
+            }
+            let token: TokenResponse = request
+                .send()
+                .await?
+                .error_for_status()
+                .map_err(|e| anyhow!("Cannot obtain registry token: {}", e))?
+                .json()
+                .await
+                .map_err(|e| anyhow!("Cannot decode registry token: {}", e))?;
+            Credentials::Bearer(
+                token
+                    .token
+                    .or(token.access_token)
+                    .ok_or_else(|| anyhow!("Registry did not return an authentication token"))?,
+            )
+        } else {
+            return Err(anyhow!("Unsupported authentication scheme: {}", scheme));
+        };
+
+        *self.credentials.lock().unwrap() = Some(credentials);
+        Ok(())
+    }
+}
+
+// Fetches all the pages of a tags list, starting from `first_page`
+async fn collect_tags<F, Fut>(first_page: Url, mut fetch_page: F) -> Result<Vec<String>>
+where
+    F: FnMut(Url) -> Fut,
+    Fut: Future<Output = Result<TagsPage>>,
+{
+    let mut tags: Vec<String> = Vec::new();
+    let mut next = Some(first_page);
+    while let Some(url) = next {
+        let page = fetch_page(url.clone()).await?;
+        if page.next.as_ref() == Some(&url) {
+            return Err(anyhow!("Registry pagination is looping over {}", url));
+        }
+        tags.extend(page.tags);
+        next = page.next;
+    }
+
+    Ok(tags)
+}
+
+// Keeps only the referrers of the given artifact type, if any
+fn filter_referrers(referrers: Vec<Referrer>, artifact_type: Option<&str>) -> Vec<Referrer> {
+    match artifact_type {
+        Some(artifact_type) => referrers
+            .into_iter()
+            .filter(|referrer| referrer.artifact_type.as_deref() == Some(artifact_type))
+            .collect(),
+        None => referrers,
+    }
+}
+
+// Finds the url of the next page inside of a `Link` header, as described
+// by the OCI distribution spec. Relative urls are resolved against `current`.
+//
+// Example of header: `</v2/kubewarden/psp/tags/list?last=v1.0&n=100>; rel="next"`
+fn next_page_url(current: &Url, link_header: Option<&str>) -> Result<Option<Url>> {
+    let link_header = match link_header {
+        Some(link_header) => link_header,
+        None => return Ok(None),
+    };
+
+    for link in link_header.split(',') {
+        let (target, params) = match link.split_once(';') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let is_next = params.split(';').any(|param| {
+            param
+                .trim()
+                .replace('"', "")
+                .eq_ignore_ascii_case("rel=next")
+        });
+        if !is_next {
+            continue;
+        }
+        let target = target
+            .trim()
+            .strip_prefix('<')
+            .and_then(|target| target.strip_suffix('>'))
+            .ok_or_else(|| anyhow!("Invalid Link header: {}", link_header))?;
+        return current
+            .join(target)
+            .map(Some)
+            .map_err(|e| anyhow!("Invalid Link header {}: {}", link_header, e));
+    }
+
+    Ok(None)
+}
+
+// Parses a `WWW-Authenticate` header, like
+// `Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:user/image:pull"`
+fn parse_www_authenticate(challenge: &str) -> Option<(String, HashMap<String, String>)> {
+    let (scheme, params) = match challenge.trim().split_once(' ') {
+        Some((scheme, params)) => (scheme, params),
+        None => (challenge.trim(), ""),
+    };
+    if scheme.is_empty() {
+        return None;
+    }
+
+    let mut result: HashMap<String, String> = HashMap::new();
+    let mut rest = params.trim();
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=')?;
+        let key = key.trim().trim_start_matches(',').trim().to_lowercase();
+        let value = value.trim_start();
+        let (value, remainder) = if let Some(quoted) = value.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            (&quoted[..end], &quoted[end + 1..])
+        } else {
+            match value.find(',') {
+                Some(end) => (&value[..end], &value[end..]),
+                None => (value, ""),
+            }
+        };
+        result.insert(key, String::from(value));
+        rest = remainder.trim().trim_start_matches(',').trim();
+    }
+
+    Some((String::from(scheme), result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_image_reference() -> Result<()> {
+        assert_eq!(
+            ImageReference::parse("registry://ghcr.io/kubewarden/policies/psp:v0.1.*")?,
+            ImageReference {
+                registry: String::from("ghcr.io"),
+                repository: String::from("kubewarden/policies/psp"),
+                tag: Some(String::from("v0.1.*")),
+                digest: None,
+            }
+        );
+        assert_eq!(
+            ImageReference::parse("localhost:5000/psp@sha256:1234")?,
+            ImageReference {
+                registry: String::from("localhost:5000"),
+                repository: String::from("psp"),
+                tag: None,
+                digest: Some(String::from("sha256:1234")),
+            }
+        );
+        assert_eq!(
+            ImageReference::parse("registry://kubewarden/psp:v1")?,
+            ImageReference {
+                registry: String::from("docker.io"),
+                repository: String::from("kubewarden/psp"),
+                tag: Some(String::from("v1")),
+                digest: None,
+            }
+        );
+        let busybox = ImageReference::parse("registry://busybox:v1")?;
+        assert_eq!(busybox.registry, "docker.io");
+        assert_eq!(busybox.api_host(), "registry-1.docker.io");
+        assert_eq!(busybox.api_repository(), "library/busybox");
+        assert!(ImageReference::parse("registry://ghcr.io/").is_err());
+        assert!(ImageReference::parse("registry://").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_docker_config() -> Result<()> {
+        let docker_config =
+            inline_docker_config("registry://ghcr.io/kubewarden/psp:v0.1.0", "jane:s3cr3t")?;
+        assert!(docker_config
+            .auth("ghcr.io/kubewarden/psp:v0.1.0")?
+            .is_some());
+
+        assert!(inline_docker_config("registry://ghcr.io/kubewarden/psp", "jane").is_err());
+        assert!(inline_docker_config("registry://ghcr.io/kubewarden/psp", ":s3cr3t").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_referrers() -> Result<()> {
+        let index: ReferrersIndex = serde_json::from_str(
+            r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.index.v1+json",
+                "manifests": [
+                    {
+                        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                        "digest": "sha256:1111",
+                        "size": 100,
+                        "artifactType": "application/vnd.dev.cosign.artifact.sig.v1+json"
+                    },
+                    {
+                        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                        "digest": "sha256:2222",
+                        "size": 200,
+                        "artifactType": "application/spdx+json",
+                        "annotations": {"org.opencontainers.image.created": "2022-05-01T00:00:00Z"}
+                    }
+                ]
+            }"#,
+        )?;
+
+        let referrers = filter_referrers(index.manifests, Some("application/spdx+json"));
+        assert_eq!(referrers.len(), 1);
+        assert_eq!(referrers[0].digest, "sha256:2222");
+        assert_eq!(
+            referrers[0]
+                .annotations
+                .get("org.opencontainers.image.created")
+                .map(String::as_str),
+            Some("2022-05-01T00:00:00Z")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_credential_helper() {
+        let raw_config = serde_json::json!({
+            "auths": {"ghcr.io": {}},
+            "credsStore": "desktop",
+            "credHelpers": {
+                "123456789012.dkr.ecr.eu-west-1.amazonaws.com": "ecr-login",
+                "gcr.io": "gcloud"
+            }
+        });
+        assert_eq!(
+            credential_helper(&raw_config, "123456789012.dkr.ecr.eu-west-1.amazonaws.com"),
+            Some(String::from("ecr-login"))
+        );
+        assert_eq!(
+            credential_helper(&raw_config, "ghcr.io"),
+            Some(String::from("desktop"))
+        );
+        assert_eq!(
+            credential_helper(&serde_json::json!({"auths": {}}), "ghcr.io"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_credentials_from_helper() -> Result<()> {
+        let credentials = parse_helper_output(
+            br#"{"ServerURL": "gcr.io", "Username": "_dcgcloud_token", "Secret": "s3cr3t"}"#,
+        )?;
+        let mut raw_config = serde_json::json!({});
+        add_auth(&mut raw_config, "gcr.io", &credentials)?;
+        let docker_config: DockerConfig = serde_json::from_value(raw_config)?;
+        assert!(docker_config
+            .auth("gcr.io/kubewarden/psp:v0.1.0")?
+            .is_some());
+
+        assert!(parse_helper_output(
+            br#"{"ServerURL": "gcr.io", "Username": "<token>", "Secret": "refresh"}"#
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_page_url() -> Result<()> {
+        let current = Url::parse("https://ghcr.io/v2/kubewarden/psp/tags/list?n=2")?;
+
+        assert_eq!(next_page_url(&current, None)?, None);
+        assert_eq!(
+            next_page_url(
+                &current,
+                Some(r#"</v2/kubewarden/psp/tags/list?last=v2&n=2>; rel="next""#)
+            )?,
+            Some(Url::parse(
+                "https://ghcr.io/v2/kubewarden/psp/tags/list?last=v2&n=2"
+            )?)
+        );
+        assert_eq!(
+            next_page_url(
+                &current,
+                Some(r#"<https://mirror.lan/v2/psp/tags/list?last=v2>; rel=next"#)
+            )?,
+            Some(Url::parse("https://mirror.lan/v2/psp/tags/list?last=v2")?)
+        );
+        assert_eq!(
+            next_page_url(&current, Some(r#"</v2/other>; rel="prev""#))?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_www_authenticate() {
+        let (scheme, params) = parse_www_authenticate(
+            r#"Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:kubewarden/psp:pull""#,
+        )
+        .unwrap();
+        assert_eq!(scheme, "Bearer");
+        assert_eq!(params.get("realm").unwrap(), "https://ghcr.io/token");
+        assert_eq!(params.get("service").unwrap(), "ghcr.io");
+        assert_eq!(
+            params.get("scope").unwrap(),
+            "repository:kubewarden/psp:pull"
+        );
+
+        let (scheme, params) = parse_www_authenticate(r#"Basic realm="registry""#).unwrap();
+        assert_eq!(scheme, "Basic");
+        assert_eq!(params.get("realm").unwrap(), "registry");
+    }
+
+    #[tokio::test]
+    async fn test_collect_tags_follows_pagination() -> Result<()> {
+        let base = Url::parse("https://ghcr.io/v2/kubewarden/psp/tags/list")?;
+        let mut pages: HashMap<String, (Vec<&str>, Option<&str>)> = HashMap::new();
+        pages.insert(
+            String::from("https://ghcr.io/v2/kubewarden/psp/tags/list?n=2"),
+            (
+                vec!["v0.1.0", "v0.1.1"],
+                Some(r#"</v2/kubewarden/psp/tags/list?last=v0.1.1&n=2>; rel="next""#),
+            ),
+        );
+        pages.insert(
+            String::from("https://ghcr.io/v2/kubewarden/psp/tags/list?last=v0.1.1&n=2"),
+            (
+                vec!["v0.2.0", "v0.2.1"],
+                Some(r#"</v2/kubewarden/psp/tags/list?last=v0.2.1&n=2>; rel="next""#),
+            ),
+        );
+        pages.insert(
+            String::from("https://ghcr.io/v2/kubewarden/psp/tags/list?last=v0.2.1&n=2"),
+            (vec!["latest"], None),
+        );
+
+        let tags = collect_tags(base.join("list?n=2")?, |url| {
+            let page = pages.get(url.as_str()).cloned();
+            async move {
+                let (tags, link) = page.ok_or_else(|| anyhow!("unexpected url {}", url))?;
+                Ok(TagsPage {
+                    tags: tags.iter().map(|tag| String::from(*tag)).collect(),
+                    next: next_page_url(&url, link)?,
+                })
+            }
+        })
+        .await?;
+
+        assert_eq!(tags, vec!["v0.1.0", "v0.1.1", "v0.2.0", "v0.2.1", "latest"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,478 @@
+use anyhow::{anyhow, Result};
+use policy_evaluator::policy_fetcher::{
+    oci_distribution::manifest::{OciDescriptor, OciImageManifest, WASM_LAYER_MEDIA_TYPE},
+    policy::Policy,
+    store::{PolicyPath, Store},
+    PullDestination,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+use tempfile::TempPath;
+
+use crate::{oci::ImageReference, pull};
+
+/// Scheme of the policies stored inside of an OCI image layout directory
+pub(crate) const OCI_LAYOUT_SCHEME: &str = "oci-layout:";
+
+/// Scheme of the policies stored inside of a tar archive of an OCI image
+/// layout
+pub(crate) const OCI_ARCHIVE_SCHEME: &str = "oci-archive:";
+
+/// Annotation of the manifests of the index of the layout holding their
+/// reference, usually a tag or a whole image reference
+const REF_NAME_ANNOTATION: &str = "org.opencontainers.image.ref.name";
+
+const OCI_LAYOUT_FILE: &str = "oci-layout";
+const INDEX_FILE: &str = "index.json";
+
+/// Returns true when `uri` references a policy stored inside of an OCI
+/// image layout, either a directory or a tar archive
+pub(crate) fn is_layout_uri(uri: &str) -> bool {
+    uri.starts_with(OCI_LAYOUT_SCHEME) || uri.starts_with(OCI_ARCHIVE_SCHEME)
+}
+
+/// A policy read from an OCI image layout
+#[derive(Debug)]
+pub(crate) struct LayoutPolicy {
+    /// The reference of the manifest of the policy inside of the layout
+    pub reference: Option<String>,
+    pub wasm: Vec<u8>,
+}
+
+impl LayoutPolicy {
+    /// Reads the policy referenced by `uri`, like `oci-layout:./policies`
+    /// or `oci-archive:./policies.tar:v0.1.0`. The reference following the
+    /// path is matched against the `org.opencontainers.image.ref.name`
+    /// annotation of the manifests of the layout, it can be omitted when
+    /// the layout holds a single manifest. Like skopeo, the path cannot
+    /// contain colons.
+    pub(crate) fn read(uri: &str) -> Result<Self> {
+        let (layout, reference) = if let Some(location) = uri.strip_prefix(OCI_LAYOUT_SCHEME) {
+            let (path, reference) = split_reference(location);
+            (Layout::Directory(PathBuf::from(path)), reference)
+        } else if let Some(location) = uri.strip_prefix(OCI_ARCHIVE_SCHEME) {
+            let (path, reference) = split_reference(location);
+            (Layout::Archive(PathBuf::from(path)), reference)
+        } else {
+            return Err(anyhow!("{} is not an OCI layout reference", uri));
+        };
+
+        layout.ensure_is_layout()?;
+        let index: Index = serde_json::from_slice(&layout.read_file(INDEX_FILE)?)
+            .map_err(|e| anyhow!("Invalid {} of OCI layout: {}", INDEX_FILE, e))?;
+        let descriptor = index.find_manifest(reference.as_deref())?;
+        let manifest: OciImageManifest =
+            serde_json::from_slice(&layout.read_blob(&descriptor.digest)?)
+                .map_err(|e| anyhow!("Invalid manifest {}: {}", descriptor.digest, e))?;
+        let wasm_layer = manifest
+            .layers
+            .iter()
+            .find(|layer| layer.media_type == WASM_LAYER_MEDIA_TYPE)
+            .ok_or_else(|| {
+                anyhow!(
+                    "The manifest {} does not have a Wasm layer",
+                    descriptor.digest
+                )
+            })?;
+        let wasm = layout.read_blob(&wasm_layer.digest)?;
+
+        Ok(LayoutPolicy {
+            reference: reference.or_else(|| ref_name(descriptor)),
+            wasm,
+        })
+    }
+
+    /// The `registry://` uri the policy is stored under inside of the main
+    /// store. Available only when its reference is a whole image reference,
+    /// like `ghcr.io/kubewarden/policies/psp-capabilities:v0.1.6`
+    pub(crate) fn registry_uri(&self) -> Option<String> {
+        let reference = self.reference.as_deref()?;
+        let image = ImageReference::parse(reference).ok()?;
+        // short references, like `psp:v1`, default to Docker Hub: they are
+        // not whole image references
+        let names_registry = reference.starts_with(&format!("{}/", image.registry));
+        if names_registry && (image.tag.is_some() || image.digest.is_some()) {
+            Some(format!("registry://{}", reference))
+        } else {
+            None
+        }
+    }
+
+    // The uri and the path of the policy once pulled into `store`
+    fn store_location(&self, uri: &str, store: &Store) -> Result<(String, PathBuf)> {
+        let stored_uri = self.registry_uri().ok_or_else(|| {
+            anyhow!(
+                "The policy {} cannot be stored inside of the store: its reference {} is not a whole image reference, like ghcr.io/kubewarden/policies/psp-capabilities:v0.1.6. Use --output-path to write it somewhere else",
+                uri,
+                self.reference.as_deref().unwrap_or("<none>")
+            )
+        })?;
+        let path = store.policy_full_path(&stored_uri, PolicyPath::PrefixAndFilename)?;
+        Ok((stored_uri, path))
+    }
+
+    // The name of the file the policy is written to when pulled into a
+    // directory
+    fn file_name(&self) -> String {
+        let name = self
+            .reference
+            .as_deref()
+            .and_then(|reference| reference.rsplit('/').next())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("policy");
+        format!("{}.wasm", name.replace(':', "-"))
+    }
+}
+
+/// Copies the Wasm module of the policy stored inside of the OCI layout
+/// referenced by `uri` to `destination`. Policies can be pulled into the
+/// main store only when the layout references them with a whole image
+/// reference, which becomes their uri.
+pub(crate) fn pull(uri: &str, destination: PullDestination) -> Result<Policy> {
+    let policy = LayoutPolicy::read(uri)?;
+    let (stored_uri, destination_path) = match destination {
+        PullDestination::LocalFile(path) if path.is_dir() => {
+            (String::from(uri), path.join(policy.file_name()))
+        }
+        PullDestination::LocalFile(path) => (String::from(uri), path),
+        PullDestination::MainStore => policy.store_location(uri, &Store::default())?,
+        PullDestination::Store(root) => policy.store_location(uri, &Store::new(&root))?,
+    };
+
+    if let Some(parent) = destination_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| anyhow!("Cannot create directory {}: {}", parent.display(), e))?;
+    }
+    let tmp_path = pull::tmp_path(&destination_path)?;
+    fs::write(&tmp_path, &policy.wasm)
+        .and_then(|_| fs::rename(&tmp_path, &destination_path))
+        .map_err(|e| {
+            #[allow(unused_must_use)]
+            {
+                // try to clean up the temporary file. Ignore errors.
+                fs::remove_file(&tmp_path);
+            }
+            anyhow!(
+                "Cannot write policy to {}: {}",
+                destination_path.display(),
+                e
+            )
+        })?;
+
+    Ok(Policy {
+        uri: stored_uri,
+        local_path: destination_path,
+    })
+}
+
+/// Extracts the Wasm module of the policy stored inside of the OCI layout
+/// referenced by `uri` to a new temporary file, so that it can be inspected.
+/// The file is removed once the returned path is dropped.
+pub(crate) fn extract_wasm(uri: &str) -> Result<TempPath> {
+    let policy = LayoutPolicy::read(uri)?;
+    // the file is created with a random name, and never reused: another
+    // user cannot plant its own module in its place
+    let mut file = tempfile::Builder::new()
+        .prefix("kwctl-oci-layout-")
+        .suffix(".wasm")
+        .tempfile()
+        .map_err(|e| anyhow!("Cannot create temporary file: {}", e))?;
+    file.write_all(&policy.wasm)
+        .map_err(|e| anyhow!("Cannot write policy to {}: {}", file.path().display(), e))?;
+    Ok(file.into_temp_path())
+}
+
+// Splits the location of an OCI layout into its path and the optional
+// reference of the manifest
+fn split_reference(location: &str) -> (&str, Option<String>) {
+    match location.split_once(':') {
+        Some((path, reference)) if !reference.is_empty() => (path, Some(String::from(reference))),
+        Some((path, _)) => (path, None),
+        None => (location, None),
+    }
+}
+
+fn ref_name(descriptor: &OciDescriptor) -> Option<String> {
+    descriptor
+        .annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(REF_NAME_ANNOTATION))
+        .cloned()
+}
+
+#[derive(Deserialize)]
+struct Index {
+    manifests: Vec<OciDescriptor>,
+}
+
+impl Index {
+    fn find_manifest(&self, reference: Option<&str>) -> Result<&OciDescriptor> {
+        match (reference, self.manifests.as_slice()) {
+            (Some(reference), manifests) => manifests
+                .iter()
+                .find(|manifest| ref_name(manifest).as_deref() == Some(reference))
+                .ok_or_else(|| anyhow!("Cannot find {} inside of the OCI layout", reference)),
+            (None, [manifest]) => Ok(manifest),
+            (None, []) => Err(anyhow!("The OCI layout doesn't hold any manifest")),
+            (None, manifests) => Err(anyhow!(
+                "The OCI layout holds {} manifests, choose one by appending its reference to the path, like oci-layout:./dir:<reference>. Available references: {}",
+                manifests.len(),
+                manifests
+                    .iter()
+                    .filter_map(ref_name)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+// The files of an OCI layout, read either from a directory or from a
+// tar archive
+enum Layout {
+    Directory(PathBuf),
+    Archive(PathBuf),
+}
+
+impl Layout {
+    fn read_file(&self, name: &str) -> Result<Vec<u8>> {
+        match self {
+            Layout::Directory(dir) => {
+                let path = dir.join(name);
+                fs::read(&path).map_err(|e| anyhow!("Cannot read {}: {}", path.display(), e))
+            }
+            Layout::Archive(path) => read_archive_file(path, name)
+                .map_err(|e| anyhow!("Invalid OCI archive {}: {}", path.display(), e))?
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Cannot find {} inside of the archive {}",
+                        name,
+                        path.display()
+                    )
+                }),
+        }
+    }
+
+    fn ensure_is_layout(&self) -> Result<()> {
+        let oci_layout: serde_json::Value =
+            serde_json::from_slice(&self.read_file(OCI_LAYOUT_FILE)?)
+                .map_err(|e| anyhow!("Invalid {} file: {}", OCI_LAYOUT_FILE, e))?;
+        match oci_layout
+            .get("imageLayoutVersion")
+            .and_then(|version| version.as_str())
+        {
+            Some("1.0.0") => Ok(()),
+            Some(version) => Err(anyhow!("Unsupported OCI layout version {}", version)),
+            None => Err(anyhow!(
+                "Invalid {} file: imageLayoutVersion not defined",
+                OCI_LAYOUT_FILE
+            )),
+        }
+    }
+
+    // Reads the blob with the given digest, ensuring its contents match it
+    fn read_blob(&self, digest: &str) -> Result<Vec<u8>> {
+        let hex = digest
+            .strip_prefix("sha256:")
+            .filter(|hex| hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| anyhow!("Unsupported digest {}", digest))?;
+        let blob = self.read_file(&format!("blobs/sha256/{}", hex))?;
+        let actual = format!("{:x}", Sha256::digest(&blob));
+        if actual != hex {
+            return Err(anyhow!(
+                "The blob {} is corrupted, its digest is sha256:{}",
+                digest,
+                actual
+            ));
+        }
+        Ok(blob)
+    }
+}
+
+// Reads the regular file `name` of the uncompressed tar archive at `path`,
+// like the ones produced by `skopeo copy` and `docker save`. The archive is
+// streamed, only the contents of the file are loaded in memory.
+fn read_archive_file(path: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+    let archive = File::open(path)?;
+    let mut archive = tar::Archive::new(archive);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        // entries are usually prefixed by `./`
+        let entry_path: PathBuf = entry
+            .path()?
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect();
+        if entry_path == Path::new(name) {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            return Ok(Some(contents));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WASM: &[u8] = b"\0asm\x01\0\0\0";
+
+    fn digest(blob: &[u8]) -> String {
+        format!("sha256:{:x}", Sha256::digest(blob))
+    }
+
+    // The files of an OCI layout holding a policy for each reference
+    fn layout_files(references: &[&str]) -> Vec<(String, Vec<u8>)> {
+        let config = b"{}".to_vec();
+        let manifest = serde_json::to_vec(&serde_json::json!({
+            "schemaVersion": 2,
+            "config": {
+                "mediaType": "application/vnd.wasm.config.v1+json",
+                "digest": digest(&config),
+                "size": config.len(),
+            },
+            "layers": [{
+                "mediaType": WASM_LAYER_MEDIA_TYPE,
+                "digest": digest(WASM),
+                "size": WASM.len(),
+            }],
+        }))
+        .unwrap();
+        let index = serde_json::to_vec(&serde_json::json!({
+            "schemaVersion": 2,
+            "manifests": references.iter().map(|reference| serde_json::json!({
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "digest": digest(&manifest),
+                "size": manifest.len(),
+                "annotations": {REF_NAME_ANNOTATION: reference},
+            })).collect::<Vec<serde_json::Value>>(),
+        }))
+        .unwrap();
+
+        let blob_path = |blob: &[u8]| format!("blobs/sha256/{:x}", Sha256::digest(blob));
+        vec![
+            (
+                String::from(OCI_LAYOUT_FILE),
+                br#"{"imageLayoutVersion": "1.0.0"}"#.to_vec(),
+            ),
+            (String::from(INDEX_FILE), index),
+            (blob_path(&manifest), manifest.clone()),
+            (blob_path(&config), config.clone()),
+            (blob_path(WASM), WASM.to_vec()),
+        ]
+    }
+
+    fn write_layout(dir: &Path, files: &[(String, Vec<u8>)]) -> Result<()> {
+        for (name, contents) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, contents)?;
+        }
+        Ok(())
+    }
+
+    fn tar(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, contents) in files {
+            let mut header = tar::Header::new_ustar();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, format!("./{}", name), contents.as_slice())?;
+        }
+        Ok(builder.into_inner()?)
+    }
+
+    #[test]
+    fn test_read_layout_directory() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        write_layout(dir.path(), &layout_files(&["v0.1.0"]))?;
+        let uri = format!("{}{}", OCI_LAYOUT_SCHEME, dir.path().display());
+
+        let policy = LayoutPolicy::read(&uri)?;
+        assert_eq!(policy.wasm, WASM);
+        assert_eq!(policy.reference.as_deref(), Some("v0.1.0"));
+        assert_eq!(policy.registry_uri(), None);
+
+        assert!(LayoutPolicy::read(&format!("{}:v0.2.0", uri)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_wasm_removed_on_drop() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        write_layout(dir.path(), &layout_files(&["v0.1.0"]))?;
+        let uri = format!("{}{}", OCI_LAYOUT_SCHEME, dir.path().display());
+
+        let wasm_path = extract_wasm(&uri)?;
+        assert_eq!(fs::read(&wasm_path)?, WASM);
+        let path = wasm_path.to_path_buf();
+        drop(wasm_path);
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_layout_archive() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let archive = dir.path().join("policies.tar");
+        fs::write(
+            &archive,
+            tar(&layout_files(&[
+                "ghcr.io/kubewarden/policies/psp-capabilities:v0.1.6",
+                "v0.1.6",
+            ]))?,
+        )?;
+        let uri = format!("{}{}", OCI_ARCHIVE_SCHEME, archive.display());
+
+        // the reference is required when the layout has many manifests
+        assert!(LayoutPolicy::read(&uri).is_err());
+
+        let policy = LayoutPolicy::read(&format!(
+            "{}:ghcr.io/kubewarden/policies/psp-capabilities:v0.1.6",
+            uri
+        ))?;
+        assert_eq!(policy.wasm, WASM);
+        assert_eq!(
+            policy.registry_uri().as_deref(),
+            Some("registry://ghcr.io/kubewarden/policies/psp-capabilities:v0.1.6")
+        );
+
+        let destination = dir.path().join("out");
+        fs::create_dir(&destination)?;
+        let pulled = pull(
+            &format!("{}:v0.1.6", uri),
+            PullDestination::LocalFile(destination.clone()),
+        )?;
+        assert_eq!(pulled.local_path, destination.join("v0.1.6.wasm"));
+        assert_eq!(fs::read(&pulled.local_path)?, WASM);
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted_blob() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut files = layout_files(&["v0.1.0"]);
+        files.last_mut().unwrap().1 = b"tampered".to_vec();
+        write_layout(dir.path(), &files)?;
+
+        let error = LayoutPolicy::read(&format!("{}{}", OCI_LAYOUT_SCHEME, dir.path().display()))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("is corrupted"),
+            "unexpected error: {}",
+            error
+        );
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::inspect::OutputType;
 use anyhow::Result;
 use policy_evaluator::{
     policy_fetcher::{policy::Policy, store::Store},
@@ -5,57 +6,165 @@ use policy_evaluator::{
 };
 use pretty_bytes::converter::convert;
 use prettytable::{format, Table};
+use serde::Serialize;
+use std::path::PathBuf;
 
-pub(crate) fn list() -> Result<()> {
-    if policy_list()?.is_empty() {
-        return Ok(());
+/// A policy of the local store, as reported by the machine readable
+/// outputs of `policies`
+#[derive(Serialize)]
+struct PolicySummary {
+    uri: String,
+    /// The SHA-256 digest of the Wasm module
+    digest: String,
+    /// The size of the Wasm module, in bytes
+    size: u64,
+    /// The location of the Wasm module inside of the local store
+    path: PathBuf,
+    /// `None` when the policy doesn't have Kubewarden metadata
+    mutating: Option<bool>,
+    context_aware: Option<bool>,
+}
+
+impl PolicySummary {
+    fn from_policy(policy: &Policy) -> Result<Self> {
+        let policy_metadata = PolicyMetadata::from_path(&policy.local_path)?;
+        let policy_filesystem_metadata = std::fs::metadata(&policy.local_path)?;
+
+        Ok(PolicySummary {
+            uri: policy.uri.clone(),
+            digest: format!("sha256:{}", policy.digest()?),
+            size: policy_filesystem_metadata.len(),
+            path: policy.local_path.clone(),
+            mutating: policy_metadata.as_ref().map(|metadata| metadata.mutating),
+            context_aware: policy_metadata
+                .as_ref()
+                .map(|metadata| metadata.context_aware),
+        })
     }
-    let mut table = Table::new();
-    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-    table.set_titles(row![
-        "Policy",
-        "Mutating",
-        "Context aware",
-        "SHA-256",
-        "Size"
-    ]);
-    for policy in policy_list()? {
-        let (mutating, context_aware) =
-            if let Some(policy_metadata) = PolicyMetadata::from_path(&policy.local_path)? {
-                let mutating = if policy_metadata.mutating {
-                    "yes"
-                } else {
-                    "no"
-                };
+}
 
-                let context_aware = if policy_metadata.context_aware {
-                    "yes"
+enum PoliciesPrinter {
+    Yaml,
+    Json,
+    Pretty,
+}
+
+impl From<&OutputType> for PoliciesPrinter {
+    fn from(output_type: &OutputType) -> Self {
+        match output_type {
+            OutputType::Yaml => Self::Yaml,
+            OutputType::Json => Self::Json,
+            OutputType::Pretty => Self::Pretty,
+        }
+    }
+}
+
+impl PoliciesPrinter {
+    fn print(&self, policies: &[Policy]) -> Result<()> {
+        match self {
+            PoliciesPrinter::Yaml => {
+                let summaries = policy_summaries(policies)?;
+                println!("{}", serde_yaml::to_string(&summaries)?);
+                Ok(())
+            }
+            PoliciesPrinter::Json => {
+                let summaries = policy_summaries(policies)?;
+                println!("{}", serde_json::to_string_pretty(&summaries)?);
+                Ok(())
+            }
+            PoliciesPrinter::Pretty => self.print_table(policies),
+        }
+    }
+
+    fn print_table(&self, policies: &[Policy]) -> Result<()> {
+        if policies.is_empty() {
+            return Ok(());
+        }
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+        table.set_titles(row![
+            "Policy",
+            "Mutating",
+            "Context aware",
+            "SHA-256",
+            "Size"
+        ]);
+        for policy in policies {
+            let (mutating, context_aware) =
+                if let Some(policy_metadata) = PolicyMetadata::from_path(&policy.local_path)? {
+                    let mutating = if policy_metadata.mutating {
+                        "yes"
+                    } else {
+                        "no"
+                    };
+
+                    let context_aware = if policy_metadata.context_aware {
+                        "yes"
+                    } else {
+                        "no"
+                    };
+
+                    (mutating, context_aware)
                 } else {
-                    "no"
+                    ("unknown", "no")
                 };
 
-                (mutating, context_aware)
-            } else {
-                ("unknown", "no")
-            };
+            let mut sha256sum = policy.digest()?;
+            sha256sum.truncate(12);
 
-        let mut sha256sum = policy.digest()?;
-        sha256sum.truncate(12);
-
-        let policy_filesystem_metadata = std::fs::metadata(&policy.local_path)?;
+            let policy_filesystem_metadata = std::fs::metadata(&policy.local_path)?;
 
-        table.add_row(row![
-            format!("{}", policy),
-            mutating,
-            context_aware,
-            sha256sum,
-            convert(policy_filesystem_metadata.len() as f64),
-        ]);
+            table.add_row(row![
+                format!("{}", policy),
+                mutating,
+                context_aware,
+                sha256sum,
+                convert(policy_filesystem_metadata.len() as f64),
+            ]);
+        }
+        table.printstd();
+        Ok(())
     }
-    table.printstd();
-    Ok(())
+}
+
+pub(crate) fn list(output: OutputType) -> Result<()> {
+    PoliciesPrinter::from(&output).print(&policy_list()?)
+}
+
+fn policy_summaries(policies: &[Policy]) -> Result<Vec<PolicySummary>> {
+    policies.iter().map(PolicySummary::from_policy).collect()
 }
 
 fn policy_list() -> Result<Vec<Policy>> {
     Store::default().list()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_summary() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let local_path = dir.path().join("policy.wasm");
+        std::fs::write(&local_path, b"\0asm\x01\0\0\0")?;
+        let policy = Policy {
+            uri: String::from("registry://ghcr.io/kubewarden/policies/psp:v0.1.0"),
+            local_path: local_path.clone(),
+        };
+
+        let summary = serde_json::to_value(PolicySummary::from_policy(&policy)?)?;
+        assert_eq!(
+            summary,
+            serde_json::json!({
+                "uri": "registry://ghcr.io/kubewarden/policies/psp:v0.1.0",
+                "digest": format!("sha256:{}", policy.digest()?),
+                "size": 8,
+                "path": local_path,
+                "mutating": null,
+                "context_aware": null,
+            })
+        );
+        Ok(())
+    }
+}
@@ -1,7 +1,28 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use policy_evaluator::policy_fetcher::registry::config::DockerConfig;
 use policy_evaluator::policy_fetcher::{
-    fetch_policy, policy::Policy, sources::Sources, PullDestination,
+    fetch_policy,
+    oci_distribution::manifest::{OciImageManifest, WASM_LAYER_MEDIA_TYPE},
+    policy::Policy,
+    sources::Sources,
+    store::{PolicyPath, Store},
+    verify::FulcioAndRekorData,
+    PullDestination,
+};
+use regex::Regex;
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use url::Url;
+
+use crate::{
+    oci::{
+        ImageReference, OciClient, DOCKER_IMAGE_MANIFEST_MEDIA_TYPE, OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+    },
+    oci_layout,
 };
 
 pub(crate) async fn pull(
@@ -10,5 +31,426 @@ pub(crate) async fn pull(
     sources: Option<&Sources>,
     destination: PullDestination,
 ) -> Result<Policy> {
+    if oci_layout::is_layout_uri(uri) {
+        return oci_layout::pull(uri, destination);
+    }
     fetch_policy(uri, destination, docker_config, sources).await
 }
+
+/// Rewrites the beginning of the `registry://` uris of the policies, so
+/// that they are stored as if they were pulled from another location (e.g.
+/// an internal mirror), while being fetched from the original one
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PrefixRewrite {
+    from: String,
+    to: String,
+}
+
+impl FromStr for PrefixRewrite {
+    type Err = anyhow::Error;
+
+    fn from_str(rewrite: &str) -> Result<Self> {
+        let (from, to) = rewrite
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid prefix rewrite '{}': must be from=to", rewrite))?;
+        let from = from.strip_prefix("registry://").unwrap_or(from);
+        let to = to.strip_prefix("registry://").unwrap_or(to);
+        if from.is_empty() || to.is_empty() {
+            return Err(anyhow!(
+                "Invalid prefix rewrite '{}': from and to cannot be empty",
+                rewrite
+            ));
+        }
+
+        Ok(PrefixRewrite {
+            from: String::from(from.trim_end_matches('/')),
+            to: String::from(to.trim_end_matches('/')),
+        })
+    }
+}
+
+impl PrefixRewrite {
+    /// Returns the rewritten uri, or `None` when `uri` doesn't start with
+    /// the prefix. The prefix must match whole path components
+    pub(crate) fn rewrite(&self, uri: &str) -> Option<String> {
+        let remainder = uri
+            .strip_prefix("registry://")?
+            .strip_prefix(self.from.as_str())?;
+        if remainder.is_empty() || remainder.starts_with(|c| c == '/' || c == ':' || c == '@') {
+            Some(format!("registry://{}{}", self.to, remainder))
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the uri under which the policy is stored, applying the first
+/// matching rewrite
+pub(crate) fn stored_uri(uri: &str, rewrites: &[PrefixRewrite]) -> String {
+    rewrites
+        .iter()
+        .find_map(|rewrite| rewrite.rewrite(uri))
+        .unwrap_or_else(|| String::from(uri))
+}
+
+/// Returns the destination of a policy pulled into the main store, which
+/// is the location of `stored_uri` when the uri of the policy is rewritten
+pub(crate) fn store_destination(uri: &str, stored_uri: &str) -> Result<PullDestination> {
+    if uri == stored_uri {
+        return Ok(PullDestination::MainStore);
+    }
+
+    let path = Store::default().policy_full_path(stored_uri, PolicyPath::PrefixAndFilename)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| anyhow!("Cannot create directory {}: {}", parent.display(), e))?;
+    }
+    Ok(PullDestination::LocalFile(path))
+}
+
+/// Pulls the policy into a temporary file next to its destination, and moves
+/// it into place only once its checksum matches the verified manifest. When
+/// the verification fails nothing is written to the destination.
+pub(crate) async fn pull_verified(
+    uri: &str,
+    docker_config: Option<&DockerConfig>,
+    sources: Option<&Sources>,
+    destination: PullDestination,
+    verified_manifest_digest: &str,
+    fulcio_and_rekor_data: &FulcioAndRekorData,
+) -> Result<Policy> {
+    let destination_path = destination_path(uri, &destination)?;
+    if let Some(parent) = destination_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| anyhow!("Cannot create directory {}: {}", parent.display(), e))?;
+    }
+    let tmp_path = tmp_path(&destination_path)?;
+
+    let policy = fetch_policy(
+        uri,
+        PullDestination::LocalFile(tmp_path.clone()),
+        docker_config,
+        sources,
+    )
+    .await?;
+    if let Err(e) = crate::verify::verify_local_checksum(
+        &policy,
+        docker_config,
+        sources,
+        verified_manifest_digest,
+        fulcio_and_rekor_data,
+    )
+    .await
+    {
+        #[allow(unused_must_use)]
+        {
+            // try to clean up the temporary file. Ignore errors.
+            fs::remove_file(&tmp_path);
+        }
+        return Err(e);
+    }
+
+    fs::rename(&tmp_path, &destination_path).map_err(|e| {
+        #[allow(unused_must_use)]
+        {
+            // try to clean up the temporary file. Ignore errors.
+            fs::remove_file(&tmp_path);
+        }
+        anyhow!(
+            "Cannot write policy to {}: {}",
+            destination_path.display(),
+            e
+        )
+    })?;
+
+    Ok(Policy {
+        uri: String::from(uri),
+        local_path: destination_path,
+    })
+}
+
+// Returns the path where the policy is going to be written when pulled
+// to `destination`
+fn destination_path(uri: &str, destination: &PullDestination) -> Result<PathBuf> {
+    match destination {
+        PullDestination::MainStore => {
+            Store::default().policy_full_path(uri, PolicyPath::PrefixAndFilename)
+        }
+        PullDestination::Store(root) => {
+            Store::new(root).policy_full_path(uri, PolicyPath::PrefixAndFilename)
+        }
+        PullDestination::LocalFile(path) if path.is_dir() => {
+            let url = Url::parse(uri).map_err(|e| anyhow!("Invalid uri {}: {}", uri, e))?;
+            let file_name = url
+                .path()
+                .split('/')
+                .last()
+                .filter(|file_name| !file_name.is_empty())
+                .ok_or_else(|| anyhow!("Cannot find the file name of {}", uri))?;
+            Ok(path.join(file_name))
+        }
+        PullDestination::LocalFile(path) => Ok(path.clone()),
+    }
+}
+
+pub(crate) fn tmp_path(path: &Path) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid destination: {}", path.display()))?;
+    let mut tmp_file_name = OsString::from(".");
+    tmp_file_name.push(file_name);
+    tmp_file_name.push(format!(".{}.tmp", std::process::id()));
+    Ok(path.with_file_name(tmp_file_name))
+}
+
+/// Returns the local copy of the policy when it holds exactly the
+/// contents published in the registry, hence there's no need to download
+/// it again.
+///
+/// Only `registry://` policies are checked: the digest of the Wasm layer
+/// referenced by the remote manifest is compared against the digest of the
+/// local file.
+pub(crate) async fn up_to_date_policy(
+    uri: &str,
+    docker_config: Option<&DockerConfig>,
+    sources: Option<&Sources>,
+    destination: &PullDestination,
+) -> Result<Option<Policy>> {
+    if !uri.starts_with("registry://") {
+        return Ok(None);
+    }
+
+    let local_path = if let PullDestination::LocalFile(path) = destination {
+        path.clone()
+    } else {
+        Store::default().policy_full_path(uri, PolicyPath::PrefixAndFilename)?
+    };
+    if !local_path.exists() {
+        return Ok(None);
+    }
+    let policy = Policy {
+        uri: String::from(uri),
+        local_path,
+    };
+
+    let image = ImageReference::parse(uri)?;
+    let reference = image
+        .digest
+        .clone()
+        .or_else(|| image.tag.clone())
+        .unwrap_or_else(|| String::from("latest"));
+    let client = OciClient::new(&image, sources, docker_config)?;
+    let (_, manifest) = client
+        .fetch_manifest(
+            &image,
+            &reference,
+            &[
+                OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+                DOCKER_IMAGE_MANIFEST_MEDIA_TYPE,
+            ],
+        )
+        .await?
+        .ok_or_else(|| anyhow!("Cannot find the manifest of {}", uri))?;
+
+    let remote_digest = policy_layer_digest(&manifest)?;
+    let local_digest = format!("sha256:{}", policy.digest()?);
+    if remote_digest == local_digest {
+        Ok(Some(policy))
+    } else {
+        Ok(None)
+    }
+}
+
+// Finds the digest of the layer holding the Wasm module of the policy
+pub(crate) fn policy_layer_digest(manifest: &[u8]) -> Result<String> {
+    let manifest: OciImageManifest =
+        serde_json::from_slice(manifest).map_err(|e| anyhow!("Invalid manifest: {}", e))?;
+    manifest
+        .layers
+        .iter()
+        .find(|layer| layer.media_type == WASM_LAYER_MEDIA_TYPE)
+        .map(|layer| layer.digest.clone())
+        .ok_or_else(|| anyhow!("The manifest does not have a Wasm layer"))
+}
+
+/// Expands `uri` into the list of policies to be pulled.
+///
+/// When the tag of a `registry://` uri is a glob pattern (e.g.
+/// `registry://ghcr.io/kubewarden/policies/psp:v0.1.*`), the uris of all
+/// the tags of the repository matching the pattern are returned. Any other
+/// uri is returned as it is.
+pub(crate) async fn expand_tag_glob(
+    uri: &str,
+    docker_config: Option<&DockerConfig>,
+    sources: Option<&Sources>,
+) -> Result<Vec<String>> {
+    let tag_pattern = match tag_glob(uri) {
+        Some(tag_pattern) => tag_pattern,
+        None => return Ok(vec![String::from(uri)]),
+    };
+
+    let image = ImageReference::parse(uri)?;
+    let tag_regex = glob_to_regex(&tag_pattern)?;
+    let client = OciClient::new(&image, sources, docker_config)?;
+    let mut tags: Vec<String> = client
+        .list_tags(&image)
+        .await
+        .map_err(|e| anyhow!("Cannot list tags of {}: {}", image.name(), e))?
+        .into_iter()
+        .filter(|tag| tag_regex.is_match(tag))
+        .collect();
+    if tags.is_empty() {
+        return Err(anyhow!(
+            "No tag of {} matches '{}'",
+            image.name(),
+            tag_pattern
+        ));
+    }
+    tags.sort();
+
+    Ok(tags
+        .iter()
+        .map(|tag| format!("registry://{}:{}", image.name(), tag))
+        .collect())
+}
+
+// Returns the tag of `uri` when it is a glob pattern
+fn tag_glob(uri: &str) -> Option<String> {
+    if !uri.starts_with("registry://") {
+        return None;
+    }
+    let image = ImageReference::parse(uri).ok()?;
+    if image.digest.is_some() {
+        return None;
+    }
+    image
+        .tag
+        .filter(|tag| tag.contains(|c| c == '*' || c == '?'))
+}
+
+fn glob_to_regex(pattern: &str) -> Result<Regex> {
+    let regex = regex::escape(pattern)
+        .replace(r"\*", ".*")
+        .replace(r"\?", ".");
+    Regex::new(&format!("^{}$", regex))
+        .map_err(|e| anyhow!("Invalid tag pattern '{}': {}", pattern, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_glob() {
+        assert_eq!(
+            tag_glob("registry://ghcr.io/kubewarden/policies/psp:v0.1.*"),
+            Some(String::from("v0.1.*"))
+        );
+        assert_eq!(
+            tag_glob("registry://localhost:5000/psp:v?"),
+            Some(String::from("v?"))
+        );
+        assert_eq!(
+            tag_glob("registry://ghcr.io/kubewarden/policies/psp:v0.1.0"),
+            None
+        );
+        assert_eq!(tag_glob("https://example.com/psp-*.wasm"), None);
+    }
+
+    #[test]
+    fn test_prefix_rewrite() -> Result<()> {
+        let rewrite: PrefixRewrite = "ghcr.io/kubewarden=registry.internal/kubewarden".parse()?;
+        assert_eq!(
+            rewrite.rewrite("registry://ghcr.io/kubewarden/policies/psp-capabilities:v0.1.6"),
+            Some(String::from(
+                "registry://registry.internal/kubewarden/policies/psp-capabilities:v0.1.6"
+            ))
+        );
+        assert_eq!(
+            rewrite.rewrite("registry://ghcr.io/kubewardenx/policies/psp:v0.1.6"),
+            None
+        );
+        assert_eq!(
+            rewrite.rewrite("https://ghcr.io/kubewarden/policies/psp.wasm"),
+            None
+        );
+
+        let rewrite: PrefixRewrite = "registry://ghcr.io/=registry.internal/".parse()?;
+        assert_eq!(
+            stored_uri("registry://ghcr.io/kubewarden/psp:v1", &[rewrite]),
+            "registry://registry.internal/kubewarden/psp:v1"
+        );
+        assert_eq!(
+            stored_uri("registry://quay.io/kubewarden/psp:v1", &[]),
+            "registry://quay.io/kubewarden/psp:v1"
+        );
+
+        assert!("ghcr.io".parse::<PrefixRewrite>().is_err());
+        assert!("=registry.internal".parse::<PrefixRewrite>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_destination_path() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let uri = "registry://ghcr.io/kubewarden/policies/psp-capabilities:v0.1.6";
+
+        assert_eq!(
+            destination_path(uri, &PullDestination::LocalFile(dir.path().to_path_buf()))?,
+            dir.path().join("psp-capabilities:v0.1.6")
+        );
+        let file = dir.path().join("policy.wasm");
+        assert_eq!(
+            destination_path(uri, &PullDestination::LocalFile(file.clone()))?,
+            file
+        );
+        assert_eq!(
+            tmp_path(&file)?,
+            dir.path()
+                .join(format!(".policy.wasm.{}.tmp", std::process::id()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_layer_digest() -> Result<()> {
+        let manifest = br#"{
+            "schemaVersion": 2,
+            "config": {
+                "mediaType": "application/vnd.wasm.config.v1+json",
+                "digest": "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a",
+                "size": 2
+            },
+            "layers": [
+                {
+                    "mediaType": "application/vnd.wasm.content.layer.v1+wasm",
+                    "digest": "sha256:3c4c1d81fb9a5e4e9d2b1a4b0b8a1e8b3bbf3f7cc5d6a2d1b74c6b0e11e56c6e",
+                    "size": 1024
+                }
+            ]
+        }"#;
+
+        assert_eq!(
+            policy_layer_digest(manifest)?,
+            "sha256:3c4c1d81fb9a5e4e9d2b1a4b0b8a1e8b3bbf3f7cc5d6a2d1b74c6b0e11e56c6e"
+        );
+        assert!(policy_layer_digest(br#"{"schemaVersion": 2, "config": {"mediaType": "application/vnd.wasm.config.v1+json", "digest": "sha256:44", "size": 2}, "layers": []}"#).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_glob_to_regex() -> Result<()> {
+        let regex = glob_to_regex("v0.1.*")?;
+        assert!(regex.is_match("v0.1.0"));
+        assert!(regex.is_match("v0.1.10"));
+        assert!(!regex.is_match("v0.10.0"));
+        assert!(!regex.is_match("xv0.1.0"));
+
+        let regex = glob_to_regex("v?")?;
+        assert!(regex.is_match("v1"));
+        assert!(!regex.is_match("v10"));
+
+        Ok(())
+    }
+}
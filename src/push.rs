@@ -1,11 +1,88 @@
 use anyhow::{anyhow, Result};
 use policy_evaluator::policy_fetcher::{
-    registry::config::DockerConfig, registry::Registry, sources::Sources,
+    oci_distribution::{manifest, Reference},
+    registry::config::DockerConfig,
+    sources::Sources,
 };
 use policy_evaluator::policy_metadata::Metadata;
-use std::{fs, path::PathBuf};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, fs, path::PathBuf};
 
 use crate::backend::BackendDetector;
+use crate::oci::{ImageReference, OciClient, OCI_IMAGE_MANIFEST_MEDIA_TYPE};
+
+/// What would be uploaded to the registry by `push`
+#[derive(Debug, Serialize)]
+pub(crate) struct PushPlan {
+    /// The reference the policy would be pushed to
+    pub reference: String,
+    /// The digest of the manifest that would be pushed
+    pub manifest_digest: String,
+    pub config: BlobPlan,
+    pub layers: Vec<BlobPlan>,
+    pub annotations: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct BlobPlan {
+    pub media_type: String,
+    pub digest: String,
+    pub size: usize,
+}
+
+impl BlobPlan {
+    fn new(blob: &Blob) -> Self {
+        BlobPlan {
+            media_type: blob.media_type.clone(),
+            digest: sha256_digest(&blob.data),
+            size: blob.data.len(),
+        }
+    }
+}
+
+// A blob of the image holding the policy
+struct Blob {
+    media_type: String,
+    data: Vec<u8>,
+}
+
+// The image holding the policy. The manifest is serialized only once, the
+// very same bytes are used to compute its digest and to push it
+struct Image {
+    config: Blob,
+    layers: Vec<Blob>,
+    manifest: Vec<u8>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageManifest<'a> {
+    schema_version: u8,
+    media_type: &'a str,
+    config: ManifestDescriptor<'a>,
+    layers: Vec<ManifestDescriptor<'a>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    annotations: &'a BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestDescriptor<'a> {
+    media_type: &'a str,
+    digest: String,
+    size: usize,
+}
+
+impl<'a> ManifestDescriptor<'a> {
+    fn new(blob: &'a Blob) -> Self {
+        ManifestDescriptor {
+            media_type: &blob.media_type,
+            digest: sha256_digest(&blob.data),
+            size: blob.data.len(),
+        }
+    }
+}
 
 pub(crate) async fn push(
     wasm_path: PathBuf,
@@ -13,7 +90,77 @@ pub(crate) async fn push(
     docker_config: Option<&DockerConfig>,
     sources: Option<&Sources>,
     force: bool,
+    annotations: BTreeMap<String, String>,
 ) -> Result<String> {
+    let policy = read_policy(wasm_path, force)?;
+    let reference = parse_reference(uri)?;
+    let image = build_image(policy, &annotations)?;
+
+    // The `Registry` object of policy-fetcher doesn't allow to customize
+    // the manifest of the pushed policy, hence the image is pushed by kwctl
+    let image_reference = ImageReference::parse(uri)?;
+    let client = OciClient::new(&image_reference, sources, docker_config)?;
+    for blob in image.layers.iter().chain(std::iter::once(&image.config)) {
+        client
+            .push_blob(&image_reference, &blob.data)
+            .await
+            .map_err(|e| anyhow!("Cannot push policy to {}: {:?}", uri, e))?;
+    }
+    let manifest_reference = image_reference
+        .digest
+        .as_deref()
+        .or_else(|| image_reference.tag.as_deref())
+        .unwrap_or("latest");
+    client
+        .push_manifest(
+            &image_reference,
+            manifest_reference,
+            OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+            &image.manifest,
+        )
+        .await
+        .map_err(|e| anyhow!("Cannot push policy to {}: {:?}", uri, e))?;
+
+    Ok(format!(
+        "registry://{}/{}@{}",
+        reference.registry(),
+        reference.repository(),
+        sha256_digest(&image.manifest)
+    ))
+}
+
+/// Performs all the checks done by `push`, returning what would be
+/// uploaded to the registry. The registry is not contacted.
+pub(crate) fn plan(
+    wasm_path: PathBuf,
+    uri: &str,
+    force: bool,
+    annotations: BTreeMap<String, String>,
+) -> Result<PushPlan> {
+    let policy = read_policy(wasm_path, force)?;
+    let reference = parse_reference(uri)?;
+    let image = build_image(policy, &annotations)?;
+
+    Ok(PushPlan {
+        reference: format!("registry://{}", reference.whole()),
+        manifest_digest: sha256_digest(&image.manifest),
+        config: BlobPlan::new(&image.config),
+        layers: image.layers.iter().map(BlobPlan::new).collect(),
+        annotations,
+    })
+}
+
+fn parse_reference(uri: &str) -> Result<Reference> {
+    let image_name = uri
+        .strip_prefix("registry://")
+        .ok_or_else(|| anyhow!("invalid uri: {}", uri))?;
+    image_name
+        .parse()
+        .map_err(|e| anyhow!("invalid reference '{}': {:?}", image_name, e))
+}
+
+// Ensures the policy can be pushed, and reads its contents
+fn read_policy(wasm_path: PathBuf, force: bool) -> Result<Vec<u8>> {
     match Metadata::from_path(&wasm_path)? {
         Some(_) => {}
         None => {
@@ -30,10 +177,60 @@ pub(crate) async fn push(
         }
     };
 
-    let policy = fs::read(&wasm_path).map_err(|e| anyhow!("Cannot open policy file: {:?}", e))?;
-    Registry::new(docker_config)
-        .push(&policy, uri, sources)
-        .await
+    fs::read(&wasm_path).map_err(|e| anyhow!("Cannot open policy file: {:?}", e))
+}
+
+// Builds the image holding the policy, serializing its manifest
+fn build_image(policy: Vec<u8>, annotations: &BTreeMap<String, String>) -> Result<Image> {
+    let config = Blob {
+        media_type: manifest::WASM_CONFIG_MEDIA_TYPE.to_string(),
+        data: b"{}".to_vec(),
+    };
+    let layers = vec![Blob {
+        media_type: manifest::WASM_LAYER_MEDIA_TYPE.to_string(),
+        data: policy,
+    }];
+    let manifest = serde_json::to_vec(&ImageManifest {
+        schema_version: 2,
+        media_type: OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+        config: ManifestDescriptor::new(&config),
+        layers: layers.iter().map(ManifestDescriptor::new).collect(),
+        annotations,
+    })?;
+
+    Ok(Image {
+        config,
+        layers,
+        manifest,
+    })
+}
+
+fn sha256_digest(data: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(data))
+}
+
+/// Parses the `key=value` annotations given on the command line
+pub(crate) fn parse_annotations<'a>(
+    values: impl Iterator<Item = &'a str>,
+) -> Result<BTreeMap<String, String>> {
+    let mut annotations = BTreeMap::new();
+    for value in values {
+        let (key, annotation_value) = value.split_once('=').ok_or_else(|| {
+            anyhow!(
+                "Invalid annotation '{}': annotations must be in the `key=value` format",
+                value
+            )
+        })?;
+        if key.trim().is_empty() {
+            return Err(anyhow!(
+                "Invalid annotation '{}': the annotation key cannot be empty",
+                value
+            ));
+        }
+        annotations.insert(String::from(key.trim()), String::from(annotation_value));
+    }
+
+    Ok(annotations)
 }
 
 fn can_be_force_pushed_without_metadata(
@@ -46,3 +243,95 @@ fn can_be_force_pushed_without_metadata(
 
     Ok(!is_rego)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_annotations() -> Result<()> {
+        let annotations = parse_annotations(
+            vec![
+                "org.opencontainers.image.source=https://github.com/kubewarden/psp-capabilities",
+                "org.opencontainers.image.description=a=b",
+            ]
+            .into_iter(),
+        )?;
+
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(
+            annotations.get("org.opencontainers.image.source").unwrap(),
+            "https://github.com/kubewarden/psp-capabilities"
+        );
+        assert_eq!(
+            annotations
+                .get("org.opencontainers.image.description")
+                .unwrap(),
+            "a=b"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let wasm_path = dir.path().join("policy.wasm");
+        let policy = b"\0asm\x01\0\0\0";
+        fs::write(&wasm_path, policy)?;
+
+        let plan = plan(
+            wasm_path.clone(),
+            "registry://ghcr.io/kubewarden/policies/psp:v0.1.0",
+            true,
+            BTreeMap::new(),
+        )?;
+        assert_eq!(
+            plan.reference,
+            "registry://ghcr.io/kubewarden/policies/psp:v0.1.0"
+        );
+        assert_eq!(plan.layers.len(), 1);
+        assert_eq!(plan.layers[0].media_type, manifest::WASM_LAYER_MEDIA_TYPE);
+        assert_eq!(plan.layers[0].digest, sha256_digest(policy));
+        assert_eq!(plan.layers[0].size, policy.len());
+        assert_eq!(plan.config.media_type, manifest::WASM_CONFIG_MEDIA_TYPE);
+
+        // policies without metadata cannot be pushed, unless forced
+        assert!(plan(
+            wasm_path,
+            "registry://ghcr.io/kubewarden/policies/psp:v0.1.0",
+            false,
+            BTreeMap::new()
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_digest_is_stable() -> Result<()> {
+        let annotations: BTreeMap<String, String> = (0..20)
+            .map(|i| (format!("io.kubewarden.test.{}", i), i.to_string()))
+            .collect();
+        let image = build_image(b"\0asm\x01\0\0\0".to_vec(), &annotations)?;
+
+        for _ in 0..10 {
+            let other = build_image(b"\0asm\x01\0\0\0".to_vec(), &annotations)?;
+            assert_eq!(image.manifest, other.manifest);
+        }
+
+        let manifest: serde_json::Value = serde_json::from_slice(&image.manifest)?;
+        assert_eq!(manifest["mediaType"], OCI_IMAGE_MANIFEST_MEDIA_TYPE);
+        assert_eq!(
+            manifest["layers"][0]["digest"],
+            sha256_digest(b"\0asm\x01\0\0\0")
+        );
+        assert_eq!(manifest["annotations"]["io.kubewarden.test.7"], "7");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_malformed_annotations() {
+        assert!(parse_annotations(vec!["org.opencontainers.image.source"].into_iter()).is_err());
+        assert!(parse_annotations(vec!["=value"].into_iter()).is_err());
+    }
+}
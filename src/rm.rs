@@ -1,12 +1,18 @@
 use anyhow::{anyhow, Result};
 use policy_evaluator::policy_fetcher::store::{PolicyPath, Store};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub(crate) fn rm(uri: &str) -> Result<()> {
     let store = Store::default();
     let policy_path = store.policy_full_path(uri, PolicyPath::PrefixAndFilename)?;
-    std::fs::remove_file(&policy_path)
-        .map_err(|err| anyhow!("could not delete policy {}: {}", uri, err))?;
+    remove_policy_file(&store, &policy_path)
+        .map_err(|err| anyhow!("could not delete policy {}: {}", uri, err))
+}
+
+/// Removes the file of a policy from the store, together with the
+/// directories left empty
+pub(crate) fn remove_policy_file(store: &Store, policy_path: &Path) -> Result<()> {
+    std::fs::remove_file(policy_path)?;
 
     // Given a policy in the store, try to cleanup all intermediate
     // directories up to the store root, from the innermost to the
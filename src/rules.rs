@@ -0,0 +1,171 @@
+use regex::Regex;
+use serde_json::Value;
+
+const OPERATIONS: &[&str] = &["CREATE", "UPDATE", "DELETE", "CONNECT", "*"];
+
+/// Looks for obviously invalid entries inside of the rules of the policy,
+/// which would never match any request once deployed. The rules are read
+/// from the raw metadata, because unknown operations cannot be parsed.
+///
+/// Returns a warning for each problem found.
+pub(crate) fn lint_rules(metadata: &Value) -> Vec<String> {
+    let rules = match metadata.get("rules") {
+        Some(Value::Array(rules)) => rules,
+        Some(_) => return vec![String::from("rules: must be a list")],
+        None => return vec![String::from("rules: not defined")],
+    };
+    if rules.is_empty() {
+        return vec![String::from(
+            "rules: the list is empty, the policy will never be evaluated",
+        )];
+    }
+
+    let mut warnings = Vec::new();
+    for (index, rule) in rules.iter().enumerate() {
+        let lint = |field: &str, check: fn(&str) -> Option<String>| -> Vec<String> {
+            match rule.get(field) {
+                Some(Value::Array(values)) if values.is_empty() => {
+                    vec![format!("rules[{}].{}: the list is empty", index, field)]
+                }
+                Some(Value::Array(values)) => values
+                    .iter()
+                    .filter_map(|value| match value.as_str() {
+                        Some(value) => check(value),
+                        None => Some(format!("{} is not a string", value)),
+                    })
+                    .map(|warning| format!("rules[{}].{}: {}", index, field, warning))
+                    .collect(),
+                Some(_) => vec![format!("rules[{}].{}: must be a list", index, field)],
+                None => vec![format!("rules[{}].{}: not defined", index, field)],
+            }
+        };
+        warnings.extend(lint("apiGroups", check_api_group));
+        warnings.extend(lint("apiVersions", check_api_version));
+        warnings.extend(lint("resources", check_resource));
+        warnings.extend(lint("operations", check_operation));
+    }
+
+    warnings
+}
+
+fn check_api_group(group: &str) -> Option<String> {
+    if group.is_empty() || group == "*" {
+        return None;
+    }
+    if group.contains('/') {
+        return Some(format!(
+            "'{}' is not a group, versions go inside of apiVersions",
+            group
+        ));
+    }
+    let group_regex =
+        Regex::new(r"^[a-z0-9]([-a-z0-9]*[a-z0-9])?(\.[a-z0-9]([-a-z0-9]*[a-z0-9])?)*$").unwrap();
+    if group.len() > 253 || !group_regex.is_match(group) {
+        return Some(format!("'{}' is not a valid group name", group));
+    }
+    None
+}
+
+fn check_api_version(version: &str) -> Option<String> {
+    let version_regex = Regex::new(r"^v[1-9][0-9]*((alpha|beta)[1-9][0-9]*)?$").unwrap();
+    if version == "*" || version_regex.is_match(version) {
+        return None;
+    }
+    if version.contains('/') {
+        return Some(format!(
+            "'{}' is not a version, groups go inside of apiGroups",
+            version
+        ));
+    }
+    Some(format!("'{}' is not a valid version", version))
+}
+
+fn check_resource(resource: &str) -> Option<String> {
+    let resource_regex =
+        Regex::new(r"^(\*|[a-z0-9]([-a-z0-9.]*[a-z0-9])?)(/(\*|[a-z0-9]([-a-z0-9]*[a-z0-9])?))?$")
+            .unwrap();
+    if resource_regex.is_match(resource) {
+        return None;
+    }
+    if resource.chars().any(|c| c.is_ascii_uppercase()) {
+        return Some(format!(
+            "'{}' is not valid, resources are lowercase plural names (e.g. 'pods')",
+            resource
+        ));
+    }
+    Some(format!("'{}' is not a valid resource", resource))
+}
+
+fn check_operation(operation: &str) -> Option<String> {
+    if OPERATIONS.contains(&operation) {
+        None
+    } else {
+        Some(format!(
+            "'{}' is not a valid operation, allowed values are {}",
+            operation,
+            OPERATIONS.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_valid_rules() {
+        let metadata = json!({
+            "rules": [
+                {
+                    "apiGroups": [""],
+                    "apiVersions": ["v1"],
+                    "resources": ["pods", "pods/exec"],
+                    "operations": ["CREATE", "CONNECT"]
+                },
+                {
+                    "apiGroups": ["apps", "networking.k8s.io"],
+                    "apiVersions": ["v1", "v1beta1"],
+                    "resources": ["*"],
+                    "operations": ["*"]
+                }
+            ]
+        });
+        assert!(lint_rules(&metadata).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let metadata = json!({
+            "rules": [
+                {
+                    "apiGroups": ["apps/v1"],
+                    "apiVersions": ["apps/v1"],
+                    "resources": ["Deployments"],
+                    "operations": ["CREATE", "PATCH"]
+                },
+                {
+                    "apiGroups": [""],
+                    "apiVersions": ["v1"],
+                    "resources": []
+                }
+            ]
+        });
+        assert_eq!(
+            lint_rules(&metadata),
+            vec![
+                "rules[0].apiGroups: 'apps/v1' is not a group, versions go inside of apiVersions",
+                "rules[0].apiVersions: 'apps/v1' is not a version, groups go inside of apiGroups",
+                "rules[0].resources: 'Deployments' is not valid, resources are lowercase plural names (e.g. 'pods')",
+                "rules[0].operations: 'PATCH' is not a valid operation, allowed values are CREATE, UPDATE, DELETE, CONNECT, *",
+                "rules[1].resources: the list is empty",
+                "rules[1].operations: not defined",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_rules() {
+        assert_eq!(lint_rules(&json!({"rules": []})).len(), 1);
+    }
+}
@@ -1,10 +1,11 @@
 use anyhow::{anyhow, Result};
 use kube::Client;
 use policy_evaluator::callback_handler::CallbackHandlerBuilder;
+use policy_evaluator::callback_requests::CallbackRequest;
 use policy_evaluator::{
     cluster_context::ClusterContext,
     constants::*,
-    policy_evaluator::{PolicyExecutionMode, ValidateRequest},
+    policy_evaluator::{PolicyEvaluator, PolicyExecutionMode, ValidateRequest},
     policy_evaluator_builder::PolicyEvaluatorBuilder,
     policy_fetcher::{
         registry::config::DockerConfig, sources::Sources, verify::FulcioAndRekorData,
@@ -12,31 +13,287 @@ use policy_evaluator::{
     },
     policy_metadata::Metadata,
 };
-use std::path::Path;
-use tokio::sync::oneshot;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
 use tracing::error;
 
-use crate::{backend::BackendDetector, pull, verify};
+use crate::{
+    backend::BackendDetector,
+    crd::CustomResourceDefinition,
+    evaluator_logs::{self, LogsDestination},
+    pull,
+    template::Template,
+    verify,
+};
 
-// TODO(ereslibre): arguments to be refactored, and allow to be removed.
-#[allow(clippy::too_many_arguments)]
-pub(crate) async fn pull_and_run(
-    uri: &str,
-    user_execution_mode: Option<PolicyExecutionMode>,
-    docker_config: Option<&DockerConfig>,
-    sources: Option<&Sources>,
-    request: &str,
-    settings: Option<String>,
-    verified_manifest_digest: &Option<String>,
-    fulcio_and_rekor_data: &FulcioAndRekorData,
-) -> Result<()> {
-    let uri = crate::utils::map_path_to_uri(uri)?;
+/// How often the watch mode looks for changes of the watched files
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub(crate) struct PullAndRunSettings {
+    pub uri: String,
+    pub user_execution_mode: Option<PolicyExecutionMode>,
+    pub sources: Option<Sources>,
+    pub docker_config: Option<DockerConfig>,
+    /// The requests to be evaluated. When more than one request is given,
+    /// a summary of the evaluations is printed at the end
+    pub requests: Vec<RequestInput>,
+    pub settings: Option<String>,
+    pub verified_manifest_digest: Option<String>,
+    pub fulcio_and_rekor_data: FulcioAndRekorData,
+    /// When set, the evaluation response is written to this file instead
+    /// of being printed to stdout
+    pub output_file: Option<PathBuf>,
+    pub response_view: ResponseView,
+    /// YAML definition of the CRD of the evaluated object. When set, the
+    /// object is validated against its schema before the evaluation
+    pub crd: Option<String>,
+    /// When set, the evaluation fails if the policy doesn't behave as
+    /// expected with regards to the mutation of the request
+    pub mutation_expectation: Option<MutationExpectation>,
+    /// Format of the summary printed at the end of a batch evaluation
+    pub summary_format: SummaryFormat,
+    /// Print the response of each request of a batch evaluation
+    pub show_responses: bool,
+    /// Labels of the namespace of the request. When provided, the
+    /// namespace object is added to the request being evaluated
+    pub namespace_labels: Option<BTreeMap<String, String>>,
+    /// When set, the request is evaluated again every time it, or the
+    /// settings, change
+    pub watch: Option<WatchSettings>,
+    /// When set, replaces the `userInfo` of the evaluated requests
+    pub user_info: Option<UserInfo>,
+    /// When set, the evaluation response is rendered with this template
+    pub template: Option<Template>,
+    /// When set, the log messages of the policy evaluator, including the
+    /// ones sent by the policy, are captured and saved there
+    pub evaluator_logs: Option<LogsDestination>,
+}
+
+/// The user making the evaluated requests, as found inside of the
+/// `userInfo` field of an admission request
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub(crate) struct UserInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+}
+
+/// The files looked at by the watch mode
+pub(crate) struct WatchSettings {
+    pub request_path: PathBuf,
+    pub request_format: RequestFormat,
+    /// Settings files, merged in order
+    pub settings_paths: Vec<PathBuf>,
+}
+
+/// A request to be evaluated
+#[derive(Clone)]
+pub(crate) struct RequestInput {
+    /// Where the request has been read from, used when reporting errors
+    pub source: String,
+    pub contents: String,
+    pub format: RequestFormat,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SummaryFormat {
+    Json,
+    Yaml,
+}
+
+/// The summary of a batch evaluation
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct BatchSummary {
+    total: usize,
+    allowed: usize,
+    denied: usize,
+    /// Requests that could not be evaluated, or whose evaluation failed
+    /// because of an internal error of the policy
+    errored: usize,
+    failed_mutation_expectations: usize,
+    total_duration_ms: u64,
+    average_duration_ms: u64,
+    max_duration_ms: u64,
+}
+
+impl BatchSummary {
+    fn record(&mut self, outcome: Option<&EvaluationOutcome>, duration: Duration) {
+        self.total += 1;
+        match outcome {
+            Some(EvaluationOutcome::Allowed) => self.allowed += 1,
+            Some(EvaluationOutcome::Rejected) => self.denied += 1,
+            Some(EvaluationOutcome::PolicyError) | None => self.errored += 1,
+            Some(EvaluationOutcome::MutationExpectationFailed) => {
+                self.failed_mutation_expectations += 1
+            }
+        }
+
+        let duration_ms = duration.as_millis() as u64;
+        self.total_duration_ms += duration_ms;
+        self.max_duration_ms = self.max_duration_ms.max(duration_ms);
+        self.average_duration_ms = self.total_duration_ms / self.total as u64;
+    }
+}
+
+/// Whether the policy is expected to mutate the request
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum MutationExpectation {
+    Mutation,
+    NoMutation,
+}
+
+/// The format of the request to be evaluated
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RequestFormat {
+    Json,
+    Yaml,
+}
+
+/// Which parts of the evaluation response are reported to the user
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ResponseView {
+    /// The whole response, as returned by the policy
+    Full,
+    /// Only the validation outcome: whether the request is allowed, plus
+    /// the rejection details
+    ValidationOnly,
+    /// Only the mutation patch, decoded
+    MutationOnly,
+    /// A single line with the decision: `ALLOWED`, or `DENIED` followed by
+    /// the rejection message
+    Decision,
+}
+
+/// Exit code of `kwctl run` when kwctl itself fails: the policy cannot be
+/// pulled, the request cannot be parsed, the CLI flags are wrong,...
+pub(crate) const KWCTL_ERROR_EXIT_CODE: i32 = 3;
+
+/// The outcome of the evaluation of a request
+#[derive(Debug, PartialEq)]
+pub(crate) enum EvaluationOutcome {
+    Allowed,
+    Rejected,
+    /// The policy failed with an internal error, e.g. the Wasm module trapped
+    PolicyError,
+    /// The policy did not mutate the request as expected
+    MutationExpectationFailed,
+}
+
+impl EvaluationOutcome {
+    fn from_response(response: &serde_json::Value) -> Self {
+        if response
+            .get("allowed")
+            .and_then(|allowed| allowed.as_bool())
+            == Some(true)
+        {
+            return EvaluationOutcome::Allowed;
+        }
+
+        // The policy evaluator reports internal errors of the policy with
+        // a `500` status code
+        let status_code = response
+            .get("status")
+            .and_then(|status| status.get("code"))
+            .and_then(|code| code.as_u64());
+        if status_code == Some(500) {
+            EvaluationOutcome::PolicyError
+        } else {
+            EvaluationOutcome::Rejected
+        }
+    }
+
+    /// The exit code of `kwctl run`:
+    ///
+    /// * `0`: the request has been allowed
+    /// * `1`: the request has been rejected
+    /// * `2`: the policy failed with an internal error
+    /// * `3`: kwctl failed, see `KWCTL_ERROR_EXIT_CODE`
+    /// * `4`: the mutation expectation has not been met
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            EvaluationOutcome::Allowed => 0,
+            EvaluationOutcome::Rejected => 1,
+            EvaluationOutcome::PolicyError => 2,
+            EvaluationOutcome::MutationExpectationFailed => 4,
+        }
+    }
+}
+
+// The policy pulled and prepared for the evaluation, together with the
+// tokio task running the CallbackHandler used by its evaluators
+struct LoadedPolicy {
+    id: String,
+    local_path: PathBuf,
+    execution_mode: PolicyExecutionMode,
+    crd: Option<CustomResourceDefinition>,
+    callback_sender_channel: mpsc::Sender<CallbackRequest>,
+    callback_handler_shutdown_channel_tx: oneshot::Sender<()>,
+    callback_handle: JoinHandle<()>,
+}
+
+impl LoadedPolicy {
+    fn build_evaluator(
+        &self,
+        settings: Option<&str>,
+        response_view: ResponseView,
+    ) -> Result<PolicyEvaluator> {
+        build_policy_evaluator(
+            &self.id,
+            &self.local_path,
+            self.execution_mode.clone(),
+            settings,
+            self.callback_sender_channel.clone(),
+            response_view,
+        )
+    }
+
+    // The evaluation is done, we can shutdown the tokio task that is running
+    // the CallbackHandler
+    async fn shutdown(self) {
+        shutdown_callback_handler(
+            self.callback_handler_shutdown_channel_tx,
+            self.callback_handle,
+        )
+        .await
+    }
+
+    // Builds the evaluator with the settings given by the user, shutting
+    // down the CallbackHandler task when the settings are not valid
+    async fn initial_evaluator(self, cfg: &PullAndRunSettings) -> Result<(Self, PolicyEvaluator)> {
+        match self.build_evaluator(cfg.settings.as_deref(), cfg.response_view) {
+            Ok(policy_evaluator) => Ok((self, policy_evaluator)),
+            Err(e) => {
+                self.shutdown().await;
+                Err(e)
+            }
+        }
+    }
+}
+
+async fn load_policy(cfg: &PullAndRunSettings) -> Result<LoadedPolicy> {
+    let uri = crate::utils::map_path_to_uri(&cfg.uri)?;
+    let docker_config = cfg.docker_config.as_ref();
+    let sources = cfg.sources.as_ref();
+    let fulcio_and_rekor_data = &cfg.fulcio_and_rekor_data;
 
     let policy = pull::pull(&uri, docker_config, sources, PullDestination::MainStore)
         .await
         .map_err(|e| anyhow!("error pulling policy {}: {}", uri, e))?;
 
-    if let Some(digest) = verified_manifest_digest {
+    if let Some(digest) = &cfg.verified_manifest_digest {
         verify::verify_local_checksum(
             &policy,
             docker_config,
@@ -47,10 +304,13 @@ pub(crate) async fn pull_and_run(
         .await?
     }
 
+    ensure_execution_mode_is_supported(&policy.local_path)?;
     let metadata = Metadata::from_path(&policy.local_path)?;
     if let Some(ref metadata) = metadata {
         if metadata.context_aware {
-            println!("Fetching Kubernetes context since this policy is context-aware");
+            if cfg.response_view != ResponseView::Decision {
+                println!("Fetching Kubernetes context since this policy is context-aware");
+            }
 
             let kubernetes_client = Client::try_default()
                 .await
@@ -64,23 +324,19 @@ pub(crate) async fn pull_and_run(
     }
     let policy_id = read_policy_title_from_metadata(&metadata).unwrap_or_else(|| uri.clone());
 
-    let request = serde_json::from_str::<serde_json::Value>(request)?;
+    let crd = cfg
+        .crd
+        .as_ref()
+        .map(|crd| CustomResourceDefinition::from_yaml(crd))
+        .transpose()?;
 
     let execution_mode = determine_execution_mode(
         metadata.clone(),
-        user_execution_mode,
+        cfg.user_execution_mode.clone(),
         BackendDetector::default(),
         &policy.local_path,
     )?;
 
-    let policy_settings = settings.map_or(Ok(None), |settings| {
-        if settings.is_empty() {
-            Ok(None)
-        } else {
-            serde_yaml::from_str(&settings)
-        }
-    })?;
-
     // This is a channel used to stop the tokio task that is run
     // inside of the CallbackHandler
     let (callback_handler_shutdown_channel_tx, callback_handler_shutdown_channel_rx) =
@@ -94,59 +350,804 @@ pub(crate) async fn pull_and_run(
 
     let callback_sender_channel = callback_handler.sender_channel();
 
-    let mut policy_evaluator = PolicyEvaluatorBuilder::new(policy_id)
-        .policy_file(&policy.local_path)?
-        .execution_mode(execution_mode)
-        .settings(policy_settings)
-        .callback_channel(callback_sender_channel)
-        .build()?;
+    // Spawn the tokio task used by the CallbackHandler
+    let callback_handle = tokio::spawn(async move {
+        callback_handler.loop_eval().await;
+    });
 
-    let req_obj = match request {
-        serde_json::Value::Object(ref object) => {
-            if object.get("kind").and_then(serde_json::Value::as_str) == Some("AdmissionReview") {
-                object
-                    .get("request")
-                    .ok_or_else(|| anyhow!("invalid admission review object"))
-            } else {
-                Ok(&request)
+    Ok(LoadedPolicy {
+        id: policy_id,
+        local_path: policy.local_path,
+        execution_mode,
+        crd,
+        callback_sender_channel,
+        callback_handler_shutdown_channel_tx,
+        callback_handle,
+    })
+}
+
+/// Evaluates all the requests against the policy, returning the whole
+/// response of each one of them, in order. The requests that cannot be
+/// evaluated are reported with their error.
+pub(crate) async fn pull_and_evaluate(
+    cfg: &PullAndRunSettings,
+) -> Result<Vec<Result<serde_json::Value>>> {
+    let (policy, mut policy_evaluator) = load_policy(cfg).await?.initial_evaluator(cfg).await?;
+
+    let responses = cfg
+        .requests
+        .iter()
+        .map(|request| evaluate(&mut policy_evaluator, request, policy.crd.as_ref(), cfg))
+        .collect();
+
+    policy.shutdown().await;
+    Ok(responses)
+}
+
+pub(crate) async fn pull_and_run(cfg: &PullAndRunSettings) -> Result<EvaluationOutcome> {
+    let (policy, mut policy_evaluator) = load_policy(cfg).await?.initial_evaluator(cfg).await?;
+
+    if let Some(watch) = &cfg.watch {
+        let outcome = watch_and_run(
+            cfg,
+            watch,
+            policy_evaluator,
+            policy.crd.as_ref(),
+            |settings| policy.build_evaluator(settings, cfg.response_view),
+        )
+        .await;
+        policy.shutdown().await;
+        return outcome;
+    }
+
+    // evaluate the requests
+    let batch = cfg.requests.len() > 1;
+    let mut summary = BatchSummary::default();
+    let mut outcomes: Vec<EvaluationOutcome> = Vec::new();
+    let mut rendered_responses: Vec<String> = Vec::new();
+    let mut evaluation_error: Option<anyhow::Error> = None;
+    for request in cfg.requests.iter() {
+        let start = Instant::now();
+        let evaluation = evaluate_request(&mut policy_evaluator, request, policy.crd.as_ref(), cfg);
+        let outcome = match evaluation {
+            Ok((outcome, rendered_response)) => {
+                rendered_responses.push(rendered_response);
+                Some(outcome)
+            }
+            Err(e) if batch => {
+                eprintln!("Cannot evaluate request {}: {:?}", request.source, e);
+                None
+            }
+            Err(e) => {
+                evaluation_error = Some(e);
+                None
+            }
+        };
+        summary.record(outcome.as_ref(), start.elapsed());
+        outcomes.extend(outcome);
+    }
+
+    if !batch || cfg.show_responses {
+        match &cfg.output_file {
+            Some(output_file) => write_output_file(output_file, &rendered_responses.join("\n"))?,
+            None => {
+                for rendered_response in rendered_responses.iter() {
+                    println!("{}", rendered_response);
+                }
             }
         }
-        _ => Err(anyhow!("request to evaluate is invalid")),
-    }?;
+    }
+    if batch {
+        match cfg.summary_format {
+            SummaryFormat::Json => println!("{}", serde_json::to_string(&summary)?),
+            SummaryFormat::Yaml => println!("{}", serde_yaml::to_string(&summary)?),
+        }
+    }
+
+    policy.shutdown().await;
+
+    if let Some(e) = evaluation_error {
+        return Err(e);
+    }
+    if summary.total > outcomes.len() {
+        return Err(anyhow!(
+            "{} requests cannot be evaluated",
+            summary.total - outcomes.len()
+        ));
+    }
+
+    // the outcome with the highest exit code is the one reported
+    outcomes
+        .into_iter()
+        .max_by_key(|outcome| outcome.exit_code())
+        .ok_or_else(|| anyhow!("No request to be evaluated"))
+}
+
+async fn shutdown_callback_handler(
+    shutdown_channel_tx: oneshot::Sender<()>,
+    callback_handle: JoinHandle<()>,
+) {
+    if shutdown_channel_tx.send(()).is_err() {
+        error!("Cannot shut down the CallbackHandler task");
+    } else if let Err(e) = callback_handle.await {
+        error!(
+            error = e.to_string().as_str(),
+            "Error waiting for the CallbackHandler task"
+        );
+    }
+}
+
+// Builds the evaluator of the policy, ensuring the settings given by the
+// user are valid
+fn build_policy_evaluator(
+    policy_id: &str,
+    policy_path: &Path,
+    execution_mode: PolicyExecutionMode,
+    settings: Option<&str>,
+    callback_channel: mpsc::Sender<CallbackRequest>,
+    response_view: ResponseView,
+) -> Result<PolicyEvaluator> {
+    let policy_settings = match settings {
+        Some(settings) => parse_settings(settings)?,
+        None => None,
+    };
+
+    let mut policy_evaluator = PolicyEvaluatorBuilder::new(String::from(policy_id))
+        .policy_file(policy_path)?
+        .execution_mode(execution_mode)
+        .settings(policy_settings)
+        .callback_channel(callback_channel)
+        .build()?;
 
     // validate the settings given by the user
     let settings_validation_response = policy_evaluator.validate_settings();
     if !settings_validation_response.valid {
-        println!("{}", serde_json::to_string(&settings_validation_response)?);
+        if response_view != ResponseView::Decision {
+            println!("{}", serde_json::to_string(&settings_validation_response)?);
+        }
         return Err(anyhow!(
             "Provided settings are not valid: {:?}",
             settings_validation_response.message
         ));
     }
 
-    // Spawn the tokio task used by the CallbackHandler
-    let callback_handle = tokio::spawn(async move {
-        callback_handler.loop_eval().await;
+    Ok(policy_evaluator)
+}
+
+// Parses the settings given by the user, which must be an object. Empty
+// and null documents mean the policy has no settings
+fn parse_settings(settings: &str) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+    if settings.trim().is_empty() {
+        return Ok(None);
+    }
+    let settings: serde_json::Value =
+        serde_yaml::from_str(settings).map_err(|e| anyhow!("Invalid settings: {}", e))?;
+    let type_name = match settings {
+        serde_json::Value::Object(settings) => return Ok(Some(settings)),
+        serde_json::Value::Null => return Ok(None),
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "a list",
+    };
+    Err(anyhow!(
+        "Invalid settings: they must be an object, like `{{\"key\": \"value\"}}`, but {} has been provided",
+        type_name
+    ))
+}
+
+// Evaluates the watched request every time it, or the settings, change.
+// The policy is loaded only once, the evaluator is rebuilt only when the
+// settings change. Runs until the user presses Ctrl-C, returning the
+// outcome of the last evaluation.
+async fn watch_and_run(
+    cfg: &PullAndRunSettings,
+    watch: &WatchSettings,
+    mut policy_evaluator: PolicyEvaluator,
+    crd: Option<&CustomResourceDefinition>,
+    build_evaluator: impl Fn(Option<&str>) -> Result<PolicyEvaluator>,
+) -> Result<EvaluationOutcome> {
+    let mut last_outcome: Option<EvaluationOutcome>;
+    let mut request_modified = modification_time(&watch.request_path);
+    let mut settings_modified: Vec<Option<SystemTime>> = watch
+        .settings_paths
+        .iter()
+        .map(|path| modification_time(path))
+        .collect();
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    loop {
+        // clear the screen
+        print!("\x1B[2J\x1B[1;1H");
+        let evaluation = read_watched_request(watch)
+            .and_then(|request| evaluate_request(&mut policy_evaluator, &request, crd, cfg));
+        match evaluation {
+            Ok((outcome, rendered_response)) => {
+                match &cfg.output_file {
+                    Some(output_file) => write_output_file(output_file, &rendered_response)?,
+                    None => println!("{}", rendered_response),
+                }
+                last_outcome = Some(outcome);
+            }
+            Err(e) => {
+                eprintln!(
+                    "Cannot evaluate request {}: {:?}",
+                    watch.request_path.display(),
+                    e
+                );
+                last_outcome = None;
+            }
+        }
+        println!();
+        println!("Watching for changes, press Ctrl-C to stop");
+
+        // wait for the request or the settings to change
+        loop {
+            tokio::select! {
+                _ = &mut ctrl_c => {
+                    return last_outcome.ok_or_else(|| anyhow!("The last evaluation failed"));
+                }
+                _ = tokio::time::sleep(WATCH_POLL_INTERVAL) => {}
+            }
+
+            let current_settings_modified: Vec<Option<SystemTime>> = watch
+                .settings_paths
+                .iter()
+                .map(|path| modification_time(path))
+                .collect();
+            if current_settings_modified != settings_modified {
+                settings_modified = current_settings_modified;
+                request_modified = modification_time(&watch.request_path);
+                match read_watched_settings(watch)
+                    .and_then(|settings| build_evaluator(settings.as_deref()))
+                {
+                    Ok(evaluator) => policy_evaluator = evaluator,
+                    Err(e) => {
+                        eprintln!("Cannot load the new settings: {:?}", e);
+                        continue;
+                    }
+                }
+                break;
+            }
+
+            let current_request_modified = modification_time(&watch.request_path);
+            if current_request_modified != request_modified {
+                request_modified = current_request_modified;
+                break;
+            }
+        }
+    }
+}
+
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn read_watched_request(watch: &WatchSettings) -> Result<RequestInput> {
+    let contents = fs::read_to_string(&watch.request_path).map_err(|e| {
+        anyhow!(
+            "Error opening request file {}; {}",
+            watch.request_path.display(),
+            e
+        )
+    })?;
+    Ok(RequestInput {
+        source: watch.request_path.display().to_string(),
+        contents,
+        format: watch.request_format,
+    })
+}
+
+fn read_watched_settings(watch: &WatchSettings) -> Result<Option<String>> {
+    let documents = watch
+        .settings_paths
+        .iter()
+        .map(|path| {
+            fs::read_to_string(path)
+                .map_err(|e| anyhow!("Error reading settings from {}: {}", path.display(), e))
+        })
+        .collect::<Result<Vec<String>>>()?;
+    match documents.len() {
+        0 => Ok(None),
+        1 => Ok(documents.into_iter().next()),
+        _ => Ok(Some(merge_settings(&documents)?)),
+    }
+}
+
+// Evaluates a single request, returning the whole response
+fn evaluate(
+    policy_evaluator: &mut PolicyEvaluator,
+    request: &RequestInput,
+    crd: Option<&CustomResourceDefinition>,
+    cfg: &PullAndRunSettings,
+) -> Result<serde_json::Value> {
+    let request_value = parse_request(&request.contents, request.format)?;
+    let mut req_obj = admission_request(&request_value)?.clone();
+    if let Some(crd) = crd {
+        crd.complete_request(&mut req_obj)?;
+    }
+    if let Some(namespace_labels) = &cfg.namespace_labels {
+        add_namespace_object(&mut req_obj, namespace_labels)?;
+    }
+    set_user_info(&mut req_obj, cfg.user_info.as_ref())?;
+
+    let validate_request = ValidateRequest::new(req_obj);
+    let response = match &cfg.evaluator_logs {
+        Some(destination) => {
+            let (response, logs) =
+                evaluator_logs::capture(|| policy_evaluator.validate(validate_request));
+            destination.save(&logs)?;
+            response
+        }
+        None => policy_evaluator.validate(validate_request),
+    };
+
+    Ok(serde_json::to_value(&response)?)
+}
+
+// Evaluates a single request, returning the outcome of the evaluation and
+// the rendered response
+fn evaluate_request(
+    policy_evaluator: &mut PolicyEvaluator,
+    request: &RequestInput,
+    crd: Option<&CustomResourceDefinition>,
+    cfg: &PullAndRunSettings,
+) -> Result<(EvaluationOutcome, String)> {
+    let response = evaluate(policy_evaluator, request, crd, cfg)?;
+    let rendered_response = match &cfg.template {
+        Some(template) => template.render(&response)?,
+        None => render_response(&response, cfg.response_view)?,
+    };
+
+    if let Some(expectation) = cfg.mutation_expectation {
+        if let Err(e) = check_mutation_expectation(&response, expectation) {
+            eprintln!("{}", e);
+            return Ok((
+                EvaluationOutcome::MutationExpectationFailed,
+                rendered_response,
+            ));
+        }
+    }
+
+    Ok((
+        EvaluationOutcome::from_response(&response),
+        rendered_response,
+    ))
+}
+
+fn parse_request(request: &str, format: RequestFormat) -> Result<serde_json::Value> {
+    match format {
+        RequestFormat::Json => serde_json::from_str::<serde_json::Value>(request)
+            .map_err(|e| anyhow!("Error parsing JSON request: {}", e)),
+        RequestFormat::Yaml => serde_yaml::from_str::<serde_json::Value>(request)
+            .map_err(|e| anyhow!("Error parsing YAML request: {}", e)),
+    }
+}
+
+// The `apiVersion`s of the `AdmissionReview` objects sent by the
+// Kubernetes API server. Both wrap the request in the same way
+const ADMISSION_REVIEW_API_VERSIONS: &[&str] = &["admission.k8s.io/v1", "admission.k8s.io/v1beta1"];
+
+// The request to be evaluated can be either a whole `AdmissionReview`
+// object or just its `request` field
+fn admission_request(request: &serde_json::Value) -> Result<&serde_json::Value> {
+    match request {
+        serde_json::Value::Object(ref object) => {
+            if object.get("kind").and_then(serde_json::Value::as_str) != Some("AdmissionReview") {
+                return Ok(request);
+            }
+
+            match object.get("apiVersion").and_then(serde_json::Value::as_str) {
+                Some(api_version) if ADMISSION_REVIEW_API_VERSIONS.contains(&api_version) => {}
+                Some(api_version) => {
+                    return Err(anyhow!(
+                        "unsupported admission review apiVersion '{}', expected one of: {}",
+                        api_version,
+                        ADMISSION_REVIEW_API_VERSIONS.join(", ")
+                    ))
+                }
+                None => {
+                    return Err(anyhow!(
+                        "invalid admission review object: apiVersion not defined"
+                    ))
+                }
+            }
+
+            object
+                .get("request")
+                .filter(|request| request.is_object())
+                .ok_or_else(|| anyhow!("invalid admission review object: request not defined"))
+        }
+        _ => Err(anyhow!("request to evaluate is invalid")),
+    }
+}
+
+/// `uid` of the requests built from a raw object
+const OBJECT_REQUEST_UID: &str = "kwctl-object-request";
+
+/// Builds an `AdmissionReview` asking to perform `operation` on the raw
+/// Kubernetes `object` read from `source`, like a Pod or a Deployment.
+///
+/// The group, version and kind of the request are taken from the object.
+/// The resource is inferred from the kind, following the pluralization
+/// rules of Kubernetes for the built-in types.
+pub(crate) fn object_request(source: &str, object: &str, operation: &str) -> Result<RequestInput> {
+    let object: serde_json::Value = serde_yaml::from_str(object)
+        .map_err(|e| anyhow!("Error parsing object {}: {}", source, e))?;
+    let field = |name: &str| -> Result<String> {
+        object
+            .get(name)
+            .and_then(serde_json::Value::as_str)
+            .map(String::from)
+            .ok_or_else(|| anyhow!("Invalid object {}: {} not defined", source, name))
+    };
+    let api_version = field("apiVersion")?;
+    let kind = field("kind")?;
+    let (group, version) = api_version
+        .split_once('/')
+        .unwrap_or(("", api_version.as_str()));
+    let resource = resource_name(&kind);
+    let metadata = object.get("metadata");
+    let name = metadata.and_then(|metadata| metadata.get("name"));
+    let namespace = metadata.and_then(|metadata| metadata.get("namespace"));
+
+    let group_version_kind = json!({"group": group, "version": version, "kind": kind});
+    let group_version_resource = json!({"group": group, "version": version, "resource": resource});
+    let mut request = json!({
+        "uid": OBJECT_REQUEST_UID,
+        "kind": group_version_kind,
+        "resource": group_version_resource,
+        "requestKind": group_version_kind,
+        "requestResource": group_version_resource,
+        "operation": operation,
+        "userInfo": {},
+        "dryRun": false,
     });
+    let request_obj = request.as_object_mut().unwrap();
+    if let Some(name) = name {
+        request_obj.insert(String::from("name"), name.clone());
+    }
+    if let Some(namespace) = namespace {
+        request_obj.insert(String::from("namespace"), namespace.clone());
+    }
+    // the object being deleted is found only inside of `oldObject`, while
+    // an update is evaluated as if the object didn't change
+    match operation {
+        "DELETE" => {
+            request_obj.insert(String::from("object"), serde_json::Value::Null);
+            request_obj.insert(String::from("oldObject"), object);
+        }
+        "UPDATE" => {
+            request_obj.insert(String::from("oldObject"), object.clone());
+            request_obj.insert(String::from("object"), object);
+        }
+        _ => {
+            request_obj.insert(String::from("object"), object);
+        }
+    }
 
-    // evaluate request
-    let response = policy_evaluator.validate(ValidateRequest::new(req_obj.clone()));
-    println!("{}", serde_json::to_string(&response)?);
+    let admission_review = json!({
+        "apiVersion": ADMISSION_REVIEW_API_VERSIONS[0],
+        "kind": "AdmissionReview",
+        "request": request,
+    });
+    Ok(RequestInput {
+        source: String::from(source),
+        contents: serde_json::to_string(&admission_review)?,
+        format: RequestFormat::Json,
+    })
+}
 
-    // The evaluation is done, we can shutdown the tokio task that is running
-    // the CallbackHandler
-    if callback_handler_shutdown_channel_tx.send(()).is_err() {
-        error!("Cannot shut down the CallbackHandler task");
-    } else if let Err(e) = callback_handle.await {
-        error!(
-            error = e.to_string().as_str(),
-            "Error waiting for the CallbackHandler task"
-        );
+// Lowercase plural of a kind, e.g. `NetworkPolicy` becomes `networkpolicies`
+fn resource_name(kind: &str) -> String {
+    let kind = kind.to_lowercase();
+    if kind.ends_with("ss") || kind.ends_with('x') || kind.ends_with("ch") || kind.ends_with("sh") {
+        format!("{}es", kind)
+    } else if kind.ends_with('s') {
+        // like `endpoints`, already plural
+        kind
+    } else if let Some(stem) = kind
+        .strip_suffix('y')
+        .filter(|stem| !stem.ends_with(&['a', 'e', 'i', 'o', 'u'][..]))
+    {
+        format!("{}ies", stem)
+    } else {
+        format!("{}s", kind)
+    }
+}
+
+/// Parses the `key=value` labels of the namespace of the request,
+/// ensuring they are valid Kubernetes labels
+pub(crate) fn parse_namespace_labels<'a>(
+    values: impl Iterator<Item = &'a str>,
+) -> Result<BTreeMap<String, String>> {
+    let mut labels = BTreeMap::new();
+    for value in values {
+        let (key, label_value) = value.split_once('=').ok_or_else(|| {
+            anyhow!(
+                "Invalid namespace label '{}': labels must be in the `key=value` format",
+                value
+            )
+        })?;
+        validate_label(key, label_value)
+            .map_err(|e| anyhow!("Invalid namespace label '{}': {}", value, e))?;
+        if labels
+            .insert(String::from(key), String::from(label_value))
+            .is_some()
+        {
+            return Err(anyhow!("Namespace label '{}' provided more than once", key));
+        }
     }
 
+    Ok(labels)
+}
+
+// Validates a label according to the Kubernetes syntax rules: the key is
+// made by an optional DNS subdomain prefix and a name, the value can be
+// empty
+fn validate_label(key: &str, value: &str) -> Result<()> {
+    let name_regex = Regex::new(r"^[A-Za-z0-9]([-A-Za-z0-9_.]*[A-Za-z0-9])?$").unwrap();
+    let prefix_regex =
+        Regex::new(r"^[a-z0-9]([-a-z0-9]*[a-z0-9])?(\.[a-z0-9]([-a-z0-9]*[a-z0-9])?)*$").unwrap();
+
+    let (prefix, name) = match key.split_once('/') {
+        Some((prefix, name)) => (Some(prefix), name),
+        None => (None, key),
+    };
+    if let Some(prefix) = prefix {
+        if prefix.len() > 253 || !prefix_regex.is_match(prefix) {
+            return Err(anyhow!("the key prefix must be a DNS subdomain"));
+        }
+    }
+    if name.len() > 63 || !name_regex.is_match(name) {
+        return Err(anyhow!(
+            "the key name must be at most 63 alphanumeric characters, '-', '_' or '.'"
+        ));
+    }
+    if !value.is_empty() && (value.len() > 63 || !name_regex.is_match(value)) {
+        return Err(anyhow!(
+            "the value must be at most 63 alphanumeric characters, '-', '_' or '.'"
+        ));
+    }
+
+    Ok(())
+}
+
+// Adds the `Namespace` the request belongs to, with the given labels, to
+// the `namespaceObject` field of the request. This is where policies
+// looking at the labels of the namespace expect to find it.
+fn add_namespace_object(
+    request: &mut serde_json::Value,
+    labels: &BTreeMap<String, String>,
+) -> Result<()> {
+    let namespace = request
+        .get("namespace")
+        .and_then(serde_json::Value::as_str)
+        .filter(|namespace| !namespace.is_empty())
+        .map(String::from)
+        .ok_or_else(|| {
+            anyhow!("Namespace labels cannot be used with requests that are not namespaced")
+        })?;
+
+    let request = request
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("request to evaluate is invalid"))?;
+    request.insert(
+        String::from("namespaceObject"),
+        json!({
+            "apiVersion": "v1",
+            "kind": "Namespace",
+            "metadata": {
+                "name": namespace,
+                "labels": labels,
+            }
+        }),
+    );
+
     Ok(())
 }
 
+// Replaces the `userInfo` of the request with the given one. Requests
+// without `userInfo` get an empty one, like the requests about anonymous
+// users.
+fn set_user_info(request: &mut serde_json::Value, user_info: Option<&UserInfo>) -> Result<()> {
+    let request = request
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("request to evaluate is invalid"))?;
+    match user_info {
+        Some(user_info) => {
+            request.insert(String::from("userInfo"), serde_json::to_value(user_info)?);
+        }
+        None => {
+            request.entry("userInfo").or_insert_with(|| json!({}));
+        }
+    }
+
+    Ok(())
+}
+
+// Renders the evaluation response, keeping only the parts of it the user
+// is interested into. The validation and the mutation outcomes are
+// reported inside of dedicated sections.
+fn render_response(response: &serde_json::Value, view: ResponseView) -> Result<String> {
+    let response_obj = response
+        .as_object()
+        .ok_or_else(|| anyhow!("invalid evaluation response"))?;
+
+    let rendered = match view {
+        ResponseView::Decision => {
+            let allowed = response_obj
+                .get("allowed")
+                .and_then(|allowed| allowed.as_bool())
+                .unwrap_or(false);
+            let message = response_obj
+                .get("status")
+                .and_then(|status| status.get("message"))
+                .and_then(|message| message.as_str());
+            return Ok(match (allowed, message) {
+                (true, _) => String::from("ALLOWED"),
+                (false, Some(message)) => format!("DENIED: {}", message),
+                (false, None) => String::from("DENIED"),
+            });
+        }
+        ResponseView::Full => response.clone(),
+        ResponseView::ValidationOnly => {
+            let mut validation = response_obj.clone();
+            validation.remove("patch");
+            validation.remove("patchType");
+            json!({ "validation": validation })
+        }
+        ResponseView::MutationOnly => {
+            let mutation = match response_obj.get("patch").and_then(|p| p.as_str()) {
+                Some(patch) => {
+                    let patch = base64::decode(patch)
+                        .map_err(|e| anyhow!("Cannot decode mutation patch: {}", e))?;
+                    let patch: serde_json::Value = serde_json::from_slice(&patch)
+                        .map_err(|e| anyhow!("Invalid mutation patch: {}", e))?;
+                    json!({
+                        "patchType": response_obj.get("patchType"),
+                        "patch": patch,
+                    })
+                }
+                None => serde_json::Value::Null,
+            };
+            json!({ "mutation": mutation })
+        }
+    };
+
+    Ok(serde_json::to_string(&rendered)?)
+}
+
+// Writes `contents` to `path` in an atomic way: the data is first written
+// to a temporary file that lives inside of the same directory, which is
+// then renamed to its final destination. Readers of `path` never see a
+// partially written file.
+fn write_output_file(path: &Path, contents: &str) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid output file: {}", path.display()))?;
+    let mut tmp_file_name = OsString::from(".");
+    tmp_file_name.push(file_name);
+    tmp_file_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_file_name);
+
+    fs::write(&tmp_path, contents).map_err(|e| {
+        anyhow!(
+            "Error writing temporary output file {}: {}",
+            tmp_path.display(),
+            e
+        )
+    })?;
+    fs::rename(&tmp_path, path).map_err(|e| {
+        #[allow(unused_must_use)]
+        {
+            // try to clean up the temporary file. Ignore errors.
+            fs::remove_file(&tmp_path);
+        }
+        anyhow!("Error writing output file {}: {}", path.display(), e)
+    })
+}
+
+// Policies annotated with an execution mode unknown to this version of
+// kwctl would make the parsing of the metadata fail with an obscure
+// error. The execution mode is looked up inside of the raw metadata to
+// provide a clear message.
+fn ensure_execution_mode_is_supported(wasm_path: &Path) -> Result<()> {
+    let wasm = fs::read(wasm_path)
+        .map_err(|e| anyhow!("Cannot read policy {}: {}", wasm_path.display(), e))?;
+    let execution_mode = match raw_execution_mode(&wasm)? {
+        Some(execution_mode) => execution_mode,
+        None => return Ok(()),
+    };
+
+    crate::utils::new_policy_execution_mode_from_str(&execution_mode)
+        .map(|_| ())
+        .map_err(|_| {
+            anyhow!(
+                "The policy requires the \"{}\" execution mode, which is not supported by this version of kwctl. Supported execution modes are {}, {} and {}: an updated version of kwctl is required to run this policy",
+                execution_mode,
+                serde_json::to_string(&PolicyExecutionMode::KubewardenWapc).unwrap(),
+                serde_json::to_string(&PolicyExecutionMode::Opa).unwrap(),
+                serde_json::to_string(&PolicyExecutionMode::OpaGatekeeper).unwrap(),
+            )
+        })
+}
+
+// Returns the execution mode declared inside of the metadata of the
+// policy, without parsing the whole metadata
+fn raw_execution_mode(wasm: &[u8]) -> Result<Option<String>> {
+    let metadata = match crate::utils::raw_metadata(wasm)? {
+        Some(metadata) => metadata,
+        None => return Ok(None),
+    };
+    let metadata: serde_json::Value =
+        serde_json::from_slice(metadata).map_err(|e| anyhow!("Invalid policy metadata: {}", e))?;
+    Ok(metadata
+        .get("executionMode")
+        .and_then(|execution_mode| execution_mode.as_str())
+        .map(String::from))
+}
+
+fn check_mutation_expectation(
+    response: &serde_json::Value,
+    expectation: MutationExpectation,
+) -> Result<()> {
+    let patch = response
+        .get("patch")
+        .filter(|patch| !patch.is_null())
+        .and_then(|patch| patch.as_str());
+    match (expectation, patch) {
+        (MutationExpectation::Mutation, None) => Err(anyhow!(
+            "The policy was expected to mutate the request, but no patch has been produced"
+        )),
+        (MutationExpectation::NoMutation, Some(patch)) => {
+            let patch = base64::decode(patch)
+                .ok()
+                .and_then(|patch| String::from_utf8(patch).ok())
+                .unwrap_or_else(|| String::from(patch));
+            Err(anyhow!(
+                "The policy was expected not to mutate the request, but this patch has been produced: {}",
+                patch
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Deep merges the given settings documents, in order. Objects are merged,
+/// any other value (scalars and arrays) defined by a later document replaces
+/// the one defined by the earlier ones.
+pub(crate) fn merge_settings(documents: &[String]) -> Result<String> {
+    let mut merged = serde_json::Value::Null;
+    for (index, document) in documents.iter().enumerate() {
+        let overlay: serde_json::Value = if document.trim().is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_yaml::from_str(document)
+                .map_err(|e| anyhow!("Invalid settings document #{}: {}", index + 1, e))?
+        };
+        merge_value(&mut merged, overlay);
+    }
+
+    Ok(serde_json::to_string(&merged)?)
+}
+
+fn merge_value(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge_value(base.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        // an empty overlay doesn't change anything
+        (_, serde_json::Value::Null) => {}
+        (base, overlay) => *base = overlay,
+    }
+}
+
 fn read_policy_title_from_metadata(metadata: &Option<Metadata>) -> Option<String> {
     match metadata {
         Some(ref metadata) => match metadata.annotations {
@@ -249,7 +1250,113 @@ fn determine_execution_mode(
 mod tests {
     use super::*;
     use policy_evaluator::ProtocolVersion;
-    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_settings() -> Result<()> {
+        assert_eq!(
+            parse_settings("allowed:\n  - a\n")?,
+            json!({"allowed": ["a"]}).as_object().cloned()
+        );
+        assert_eq!(
+            parse_settings(r#"{"allowed": ["a"]}"#)?.map(|s| s.len()),
+            Some(1)
+        );
+        assert_eq!(parse_settings("")?, None);
+        assert_eq!(parse_settings("null")?, None);
+
+        for (settings, type_name) in &[
+            ("- a\n- b\n", "a list"),
+            ("permissive", "a string"),
+            ("42", "a number"),
+            ("true", "a boolean"),
+        ] {
+            let error = parse_settings(settings).unwrap_err().to_string();
+            assert!(
+                error.contains(type_name),
+                "unexpected error for {}: {}",
+                settings,
+                error
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_settings() -> Result<()> {
+        let base = String::from(
+            r#"
+constrained_labels:
+  owner: ".*"
+  env: "dev"
+denied_labels: ["foo", "bar"]
+limit: 1
+"#,
+        );
+        let overlay = String::from(
+            r#"
+constrained_labels:
+  env: "prod"
+denied_labels: ["baz"]
+"#,
+        );
+
+        let merged: serde_json::Value =
+            serde_json::from_str(&merge_settings(&[base, overlay, String::new()])?)?;
+        assert_eq!(
+            merged,
+            json!({
+                "constrained_labels": {"owner": ".*", "env": "prod"},
+                "denied_labels": ["baz"],
+                "limit": 1
+            })
+        );
+
+        Ok(())
+    }
+
+    // Builds a Wasm module made only by the Kubewarden metadata custom section
+    fn wasm_with_metadata(metadata: &str) -> Vec<u8> {
+        wat::parse_str(format!(
+            r#"(module (@custom "{}" "{}"))"#,
+            KUBEWARDEN_CUSTOM_SECTION_METADATA,
+            metadata.replace('"', "\\\"")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_raw_execution_mode() -> Result<()> {
+        let wasm = wasm_with_metadata(r#"{"executionMode":"wasi","mutating":false}"#);
+        assert_eq!(raw_execution_mode(&wasm)?, Some(String::from("wasi")));
+
+        let wasm = wasm_with_metadata(r#"{"mutating":false}"#);
+        assert_eq!(raw_execution_mode(&wasm)?, None);
+
+        assert_eq!(raw_execution_mode(b"\0asm\x01\0\0\0")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_execution_mode() -> Result<()> {
+        let dir = tempdir()?;
+        let wasm_path = dir.path().join("policy.wasm");
+
+        fs::write(
+            &wasm_path,
+            wasm_with_metadata(r#"{"executionMode":"wasi","mutating":false}"#),
+        )?;
+        let error = ensure_execution_mode_is_supported(&wasm_path)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("\"wasi\" execution mode"));
+
+        fs::write(
+            &wasm_path,
+            wasm_with_metadata(r#"{"executionMode":"gatekeeper","mutating":false}"#),
+        )?;
+        ensure_execution_mode_is_supported(&wasm_path)
+    }
 
     fn mock_protocol_version_detector_v1(_wasm_path: PathBuf) -> Result<ProtocolVersion> {
         Ok(ProtocolVersion::V1)
@@ -458,4 +1565,411 @@ mod tests {
         assert!(actual.is_ok());
         assert_eq!(actual.unwrap(), PolicyExecutionMode::KubewardenWapc);
     }
+
+    #[test]
+    fn test_write_output_file() -> Result<()> {
+        let dir = tempdir()?;
+        let output_file = dir.path().join("response.json");
+
+        write_output_file(&output_file, r#"{"allowed":true}"#)?;
+        assert_eq!(fs::read_to_string(&output_file)?, r#"{"allowed":true}"#);
+
+        // overwriting an existing file must be possible too
+        write_output_file(&output_file, r#"{"allowed":false}"#)?;
+        assert_eq!(fs::read_to_string(&output_file)?, r#"{"allowed":false}"#);
+
+        // no temporary files must be left behind
+        assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_response_validation_only() -> Result<()> {
+        let response = json!({
+            "uid": "1234",
+            "allowed": false,
+            "patchType": "JSONPatch",
+            "patch": base64::encode(r#"[{"op":"remove","path":"/metadata/labels"}]"#),
+            "status": { "message": "not allowed" },
+        });
+
+        let rendered: serde_json::Value =
+            serde_json::from_str(&render_response(&response, ResponseView::ValidationOnly)?)?;
+        assert_eq!(
+            rendered,
+            json!({
+                "validation": {
+                    "uid": "1234",
+                    "allowed": false,
+                    "status": { "message": "not allowed" },
+                }
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_response_mutation_only() -> Result<()> {
+        let response = json!({
+            "uid": "1234",
+            "allowed": true,
+            "patchType": "JSONPatch",
+            "patch": base64::encode(r#"[{"op":"remove","path":"/metadata/labels"}]"#),
+        });
+
+        let rendered: serde_json::Value =
+            serde_json::from_str(&render_response(&response, ResponseView::MutationOnly)?)?;
+        assert_eq!(
+            rendered,
+            json!({
+                "mutation": {
+                    "patchType": "JSONPatch",
+                    "patch": [{"op": "remove", "path": "/metadata/labels"}],
+                }
+            })
+        );
+
+        let response = json!({ "uid": "1234", "allowed": true });
+        let rendered: serde_json::Value =
+            serde_json::from_str(&render_response(&response, ResponseView::MutationOnly)?)?;
+        assert_eq!(rendered, json!({ "mutation": null }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_namespace_labels() -> Result<()> {
+        let labels = parse_namespace_labels(
+            vec![
+                "environment=production",
+                "kubewarden.io/owner=team-a",
+                "empty=",
+            ]
+            .into_iter(),
+        )?;
+        assert_eq!(labels.get("environment").unwrap(), "production");
+        assert_eq!(labels.get("kubewarden.io/owner").unwrap(), "team-a");
+        assert_eq!(labels.get("empty").unwrap(), "");
+
+        for invalid in [
+            "environment",
+            "=production",
+            "environment=prod env",
+            "Example.com/owner=team-a",
+            "-environment=production",
+        ] {
+            assert!(
+                parse_namespace_labels(vec![invalid].into_iter()).is_err(),
+                "{} should be invalid",
+                invalid
+            );
+        }
+        assert!(parse_namespace_labels(vec!["env=a", "env=b"].into_iter()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_namespace_object() -> Result<()> {
+        let labels = parse_namespace_labels(vec!["environment=production"].into_iter())?;
+        let mut request = json!({"uid": "1", "namespace": "default", "operation": "CREATE"});
+        add_namespace_object(&mut request, &labels)?;
+        assert_eq!(
+            request["namespaceObject"],
+            json!({
+                "apiVersion": "v1",
+                "kind": "Namespace",
+                "metadata": {"name": "default", "labels": {"environment": "production"}}
+            })
+        );
+
+        let mut cluster_wide_request = json!({"uid": "1", "operation": "CREATE"});
+        assert!(add_namespace_object(&mut cluster_wide_request, &labels).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_user_info() -> Result<()> {
+        let user_info = UserInfo {
+            username: Some(String::from("jane")),
+            groups: vec![String::from("system:masters"), String::from("dev")],
+        };
+        let mut request = json!({"uid": "1", "userInfo": {"username": "kubernetes-admin"}});
+        set_user_info(&mut request, Some(&user_info))?;
+        assert_eq!(
+            request["userInfo"],
+            json!({"username": "jane", "groups": ["system:masters", "dev"]})
+        );
+
+        let mut request = json!({"uid": "1"});
+        set_user_info(&mut request, None)?;
+        assert_eq!(request["userInfo"], json!({}));
+
+        let mut request = json!({"uid": "1", "userInfo": {"username": "kubernetes-admin"}});
+        set_user_info(&mut request, None)?;
+        assert_eq!(request["userInfo"], json!({"username": "kubernetes-admin"}));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_watched_settings() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let base = dir.path().join("base.yaml");
+        let overlay = dir.path().join("overlay.yaml");
+        fs::write(&base, "allowed: [a]\nmode: strict\n")?;
+        fs::write(&overlay, "mode: permissive\n")?;
+        let watch = WatchSettings {
+            request_path: dir.path().join("request.json"),
+            request_format: RequestFormat::Json,
+            settings_paths: vec![base, overlay],
+        };
+
+        let settings: serde_json::Value =
+            serde_yaml::from_str(&read_watched_settings(&watch)?.unwrap())?;
+        assert_eq!(settings, json!({"allowed": ["a"], "mode": "permissive"}));
+        assert!(read_watched_request(&watch).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_summary() {
+        let mut summary = BatchSummary::default();
+        summary.record(Some(&EvaluationOutcome::Allowed), Duration::from_millis(10));
+        summary.record(
+            Some(&EvaluationOutcome::Rejected),
+            Duration::from_millis(30),
+        );
+        summary.record(
+            Some(&EvaluationOutcome::PolicyError),
+            Duration::from_millis(5),
+        );
+        summary.record(None, Duration::from_millis(3));
+
+        assert_eq!(
+            summary,
+            BatchSummary {
+                total: 4,
+                allowed: 1,
+                denied: 1,
+                errored: 2,
+                failed_mutation_expectations: 0,
+                total_duration_ms: 48,
+                average_duration_ms: 12,
+                max_duration_ms: 30,
+            }
+        );
+    }
+
+    #[test]
+    fn test_check_mutation_expectation() {
+        let mutated = json!({
+            "uid": "1234",
+            "allowed": true,
+            "patchType": "JSONPatch",
+            "patch": base64::encode(r#"[{"op":"remove","path":"/metadata/labels"}]"#),
+        });
+        let not_mutated = json!({ "uid": "1234", "allowed": true });
+
+        assert!(check_mutation_expectation(&mutated, MutationExpectation::Mutation).is_ok());
+        assert!(check_mutation_expectation(&not_mutated, MutationExpectation::Mutation).is_err());
+        assert!(check_mutation_expectation(&not_mutated, MutationExpectation::NoMutation).is_ok());
+
+        let error = check_mutation_expectation(&mutated, MutationExpectation::NoMutation)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(r#"[{"op":"remove","path":"/metadata/labels"}]"#));
+    }
+
+    #[test]
+    fn test_render_response_decision() -> Result<()> {
+        let response = json!({ "uid": "1234", "allowed": true });
+        assert_eq!(
+            render_response(&response, ResponseView::Decision)?,
+            "ALLOWED"
+        );
+
+        let response = json!({
+            "uid": "1234",
+            "allowed": false,
+            "status": { "message": "privileged containers are not allowed" },
+        });
+        assert_eq!(
+            render_response(&response, ResponseView::Decision)?,
+            "DENIED: privileged containers are not allowed"
+        );
+
+        let response = json!({ "uid": "1234", "allowed": false });
+        assert_eq!(
+            render_response(&response, ResponseView::Decision)?,
+            "DENIED"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_yaml_admission_review() -> Result<()> {
+        let request = r#"
+apiVersion: admission.k8s.io/v1
+kind: AdmissionReview
+request:
+  uid: 1299d386-525b-4032-98ae-1949f69f9cfc
+  operation: CREATE
+  object:
+    apiVersion: v1
+    kind: Pod
+    metadata:
+      name: nginx
+"#;
+
+        let request = parse_request(request, RequestFormat::Yaml)?;
+        let req_obj = admission_request(&request)?;
+        assert_eq!(
+            req_obj,
+            &json!({
+                "uid": "1299d386-525b-4032-98ae-1949f69f9cfc",
+                "operation": "CREATE",
+                "object": {
+                    "apiVersion": "v1",
+                    "kind": "Pod",
+                    "metadata": { "name": "nginx" },
+                },
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_request() -> Result<()> {
+        let deployment = r#"
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: nginx
+  namespace: default
+spec:
+  replicas: 1
+"#;
+        let request = object_request("deployment.yaml", deployment, "CREATE")?;
+        let review = parse_request(&request.contents, request.format)?;
+        let admission_request = admission_request(&review)?;
+        assert_eq!(
+            admission_request["kind"],
+            json!({"group": "apps", "version": "v1", "kind": "Deployment"})
+        );
+        assert_eq!(
+            admission_request["resource"],
+            json!({"group": "apps", "version": "v1", "resource": "deployments"})
+        );
+        assert_eq!(admission_request["operation"], json!("CREATE"));
+        assert_eq!(admission_request["name"], json!("nginx"));
+        assert_eq!(admission_request["namespace"], json!("default"));
+        assert_eq!(admission_request["object"]["spec"]["replicas"], json!(1));
+        assert!(admission_request.get("oldObject").is_none());
+
+        let pod = r#"{"apiVersion": "v1", "kind": "Pod", "metadata": {"name": "nginx"}}"#;
+        let request = object_request("pod.json", pod, "DELETE")?;
+        let review = parse_request(&request.contents, request.format)?;
+        let admission_request = admission_request(&review)?;
+        assert_eq!(
+            admission_request["resource"],
+            json!({"group": "", "version": "v1", "resource": "pods"})
+        );
+        assert_eq!(admission_request["object"], serde_json::Value::Null);
+        assert_eq!(admission_request["oldObject"]["kind"], json!("Pod"));
+
+        assert!(object_request("pod.yaml", "metadata:\n  name: nginx", "CREATE").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_name() {
+        for (kind, resource) in &[
+            ("Pod", "pods"),
+            ("Ingress", "ingresses"),
+            ("NetworkPolicy", "networkpolicies"),
+            ("Gateway", "gateways"),
+            ("Endpoints", "endpoints"),
+        ] {
+            assert_eq!(resource_name(kind), *resource);
+        }
+    }
+
+    #[test]
+    fn test_admission_review_versions() -> Result<()> {
+        for api_version in &["admission.k8s.io/v1", "admission.k8s.io/v1beta1"] {
+            let review = json!({
+                "apiVersion": api_version,
+                "kind": "AdmissionReview",
+                "request": {"uid": "1234", "operation": "CREATE"},
+                "response": {"uid": "1234", "allowed": true},
+            });
+            assert_eq!(
+                admission_request(&review)?,
+                &json!({"uid": "1234", "operation": "CREATE"})
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_admission_review() {
+        let unknown_version = json!({
+            "apiVersion": "admission.k8s.io/v2",
+            "kind": "AdmissionReview",
+            "request": {"uid": "1234"},
+        });
+        assert!(admission_request(&unknown_version).is_err());
+
+        let without_request = json!({
+            "apiVersion": "admission.k8s.io/v1beta1",
+            "kind": "AdmissionReview",
+        });
+        assert!(admission_request(&without_request).is_err());
+    }
+
+    #[test]
+    fn test_parse_json_request_without_admission_review() -> Result<()> {
+        let request = r#"{"uid": "1234", "operation": "DELETE"}"#;
+
+        let request = parse_request(request, RequestFormat::Json)?;
+        assert_eq!(
+            admission_request(&request)?,
+            &json!({"uid": "1234", "operation": "DELETE"})
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_yaml_request_as_json_fails() {
+        assert!(parse_request("uid: 1234", RequestFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_evaluation_outcome_from_response() {
+        assert_eq!(
+            EvaluationOutcome::from_response(&json!({"uid": "1234", "allowed": true})),
+            EvaluationOutcome::Allowed
+        );
+        assert_eq!(
+            EvaluationOutcome::from_response(&json!({
+                "uid": "1234",
+                "allowed": false,
+                "status": {"message": "privileged containers are not allowed"}
+            })),
+            EvaluationOutcome::Rejected
+        );
+        assert_eq!(
+            EvaluationOutcome::from_response(&json!({
+                "uid": "1234",
+                "allowed": false,
+                "status": {"message": "wasm trap: unreachable", "code": 500}
+            })),
+            EvaluationOutcome::PolicyError
+        );
+    }
 }
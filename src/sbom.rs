@@ -0,0 +1,84 @@
+use anyhow::{anyhow, Result};
+use policy_evaluator::policy_fetcher::{
+    oci_distribution::manifest::OciImageManifest,
+    registry::{config::DockerConfig, Registry},
+    sources::Sources,
+};
+use std::{fs, path::Path};
+use tracing::{debug, info};
+
+use crate::oci::{
+    ImageReference, OciClient, DOCKER_IMAGE_MANIFEST_MEDIA_TYPE, OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+};
+
+/// Downloads the SBOM attached to the policy identified by `uri` and writes
+/// it to `destination`.
+///
+/// The SBOM is looked up following the cosign convention: it is stored
+/// inside of the `sha256-<digest of the policy manifest>.sbom` tag of the
+/// policy repository.
+pub(crate) async fn pull_sbom(
+    uri: &str,
+    docker_config: Option<&DockerConfig>,
+    sources: Option<&Sources>,
+    destination: &Path,
+) -> Result<()> {
+    if !uri.starts_with("registry://") {
+        return Err(anyhow!(
+            "SBOMs can be fetched only for policies stored inside of an OCI registry"
+        ));
+    }
+    let image = ImageReference::parse(uri)?;
+    let digest = Registry::new(docker_config)
+        .manifest_digest(uri, sources)
+        .await?;
+    let tag = sbom_tag(&digest);
+    debug!(policy = uri, tag = tag.as_str(), "looking for policy SBOM");
+
+    let client = OciClient::new(&image, sources, docker_config)?;
+    let (_, manifest) = client
+        .fetch_manifest(
+            &image,
+            &tag,
+            &[
+                OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+                DOCKER_IMAGE_MANIFEST_MEDIA_TYPE,
+            ],
+        )
+        .await?
+        .ok_or_else(|| anyhow!("No SBOM is attached to policy {}", uri))?;
+    let manifest: OciImageManifest =
+        serde_json::from_slice(&manifest).map_err(|e| anyhow!("Invalid SBOM manifest: {}", e))?;
+    let layer = manifest
+        .layers
+        .first()
+        .ok_or_else(|| anyhow!("The SBOM manifest of policy {} has no layers", uri))?;
+
+    let sbom = client.fetch_blob(&image, &layer.digest).await?;
+    fs::write(destination, sbom)
+        .map_err(|e| anyhow!("Cannot write SBOM to {}: {}", destination.display(), e))?;
+
+    info!(
+        path = destination.display().to_string().as_str(),
+        media_type = layer.media_type.as_str(),
+        "SBOM saved"
+    );
+    Ok(())
+}
+
+fn sbom_tag(manifest_digest: &str) -> String {
+    format!("{}.sbom", manifest_digest.replace(':', "-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sbom_tag() {
+        assert_eq!(
+            sbom_tag("sha256:61ef63621fa5be8e422881d96d05edfef810992fbf9468e35d1fa5ae815bd97c"),
+            "sha256-61ef63621fa5be8e422881d96d05edfef810992fbf9468e35d1fa5ae815bd97c.sbom"
+        );
+    }
+}
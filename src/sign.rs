@@ -0,0 +1,625 @@
+use anyhow::{anyhow, Result};
+use policy_evaluator::policy_fetcher::{
+    oci_distribution::{
+        client::{Config, ImageLayer},
+        manifest::OciImageManifest,
+    },
+    registry::{config::DockerConfig, Registry},
+    sources::Sources,
+};
+use ring::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+use tracing::{debug, info};
+use x509_parser::pem::parse_x509_pem;
+use xsalsa20poly1305::{
+    aead::{Aead, NewAead},
+    Key, Nonce, XSalsa20Poly1305,
+};
+
+use crate::oci::{
+    ImageReference, OciClient, DOCKER_IMAGE_MANIFEST_MEDIA_TYPE, OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+};
+
+const SIMPLE_SIGNING_MEDIA_TYPE: &str = "application/vnd.dev.cosign.simplesigning.v1+json";
+const OCI_IMAGE_CONFIG_MEDIA_TYPE: &str = "application/vnd.oci.image.config.v1+json";
+
+const SIGNATURE_ANNOTATION: &str = "dev.cosignproject.cosign/signature";
+const CERTIFICATE_ANNOTATION: &str = "dev.sigstore.cosign/certificate";
+const CHAIN_ANNOTATION: &str = "dev.sigstore.cosign/chain";
+const BUNDLE_ANNOTATION: &str = "dev.sigstore.cosign/bundle";
+
+/// PEM label of the private keys generated by `cosign generate-key-pair`
+const COSIGN_ENCRYPTED_KEY_LABEL: &str = "ENCRYPTED COSIGN PRIVATE KEY";
+/// Environment variable holding the password of encrypted cosign keys
+const COSIGN_PASSWORD_ENV: &str = "COSIGN_PASSWORD";
+
+// DER encoding of the SubjectPublicKeyInfo header of an ECDSA P-256 public
+// key, to be followed by the uncompressed point
+const P256_SPKI_PREFIX: &[u8] = &[
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+/// How the signature is produced
+pub(crate) enum SigningMode {
+    /// Sign with a PKCS#8 PEM encoded ECDSA P-256 private key, optionally
+    /// encrypted by cosign
+    Key(PathBuf),
+    /// Sign with an ephemeral key, certified by Fulcio for the identity of
+    /// the given OIDC token. The signature is recorded inside of Rekor
+    Keyless {
+        identity_token: String,
+        fulcio_url: String,
+        rekor_url: String,
+    },
+}
+
+/// Signs the policy identified by `uri`, following the cosign conventions:
+/// the signature is stored inside of the `sha256-<digest of the policy
+/// manifest>.sig` tag of the policy repository, next to the signatures
+/// that are already there.
+///
+/// Returns the reference of the signature image.
+pub(crate) async fn sign(
+    uri: &str,
+    docker_config: Option<&DockerConfig>,
+    sources: Option<&Sources>,
+    signing_mode: &SigningMode,
+) -> Result<String> {
+    if !uri.starts_with("registry://") {
+        return Err(anyhow!(
+            "Only policies stored inside of an OCI registry can be signed"
+        ));
+    }
+    let image = ImageReference::parse(uri)?;
+    let digest = Registry::new(docker_config)
+        .manifest_digest(uri, sources)
+        .await?;
+    let payload = simple_signing_payload(&image.name(), &digest)?;
+
+    let annotations = match signing_mode {
+        SigningMode::Key(key_path) => {
+            let key = fs::read_to_string(key_path)
+                .map_err(|e| anyhow!("Cannot read key {}: {}", key_path.display(), e))?;
+            let key_pair = key_pair_from_pem(&key, key_password)?;
+            let mut annotations = BTreeMap::new();
+            annotations.insert(
+                String::from(SIGNATURE_ANNOTATION),
+                sign_payload(&key_pair, &payload)?,
+            );
+            annotations
+        }
+        SigningMode::Keyless {
+            identity_token,
+            fulcio_url,
+            rekor_url,
+        } => keyless_signature(&payload, identity_token, fulcio_url, rekor_url).await?,
+    };
+
+    let tag = signature_tag(&digest);
+    let signature_image = ImageReference {
+        tag: Some(tag.clone()),
+        digest: None,
+        ..ImageReference::parse(uri)?
+    };
+    let mut layers = existing_signatures(&signature_image, docker_config, sources).await?;
+    layers.push(ImageLayer::new(
+        payload,
+        String::from(SIMPLE_SIGNING_MEDIA_TYPE),
+        Some(annotations.into_iter().collect()),
+    ));
+
+    let config = Config {
+        data: signature_config(&layers)?,
+        media_type: String::from(OCI_IMAGE_CONFIG_MEDIA_TYPE),
+        annotations: None,
+    };
+    let signature_image_name = format!("{}:{}", image.name(), tag);
+    crate::oci::push_image(
+        &signature_image_name,
+        &layers,
+        config,
+        None,
+        docker_config,
+        sources,
+    )
+    .await
+    .map_err(|e| anyhow!("Cannot push signature {}: {}", signature_image_name, e))?;
+
+    info!(
+        policy = uri,
+        signature = signature_image_name.as_str(),
+        "policy signed"
+    );
+    Ok(signature_image_name)
+}
+
+fn signature_tag(manifest_digest: &str) -> String {
+    format!("{}.sig", manifest_digest.replace(':', "-"))
+}
+
+// The payload signed by cosign, following the "simple signing" format
+fn simple_signing_payload(repository: &str, manifest_digest: &str) -> Result<Vec<u8>> {
+    let payload = json!({
+        "critical": {
+            "identity": {
+                "docker-reference": repository,
+            },
+            "image": {
+                "docker-manifest-digest": manifest_digest,
+            },
+            "type": "cosign container image signature",
+        },
+        "optional": null,
+    });
+    Ok(serde_json::to_vec(&payload)?)
+}
+
+// The config of the signature image, as created by cosign
+fn signature_config(layers: &[ImageLayer]) -> Result<Vec<u8>> {
+    let diff_ids: Vec<String> = layers
+        .iter()
+        .map(|layer| format!("sha256:{:x}", Sha256::digest(&layer.data)))
+        .collect();
+    let config = json!({
+        "architecture": "",
+        "config": {},
+        "created": "0001-01-01T00:00:00Z",
+        "history": [{"created": "0001-01-01T00:00:00Z"}],
+        "os": "",
+        "rootfs": {
+            "type": "layers",
+            "diff_ids": diff_ids,
+        },
+    });
+    Ok(serde_json::to_vec(&config)?)
+}
+
+// Fetches the signatures already attached to the policy, they must be
+// preserved when the signature image is pushed again
+async fn existing_signatures(
+    signature_image: &ImageReference,
+    docker_config: Option<&DockerConfig>,
+    sources: Option<&Sources>,
+) -> Result<Vec<ImageLayer>> {
+    let client = OciClient::new(signature_image, sources, docker_config)?;
+    let tag = signature_image.tag.clone().unwrap_or_default();
+    let manifest = match client
+        .fetch_manifest(
+            signature_image,
+            &tag,
+            &[
+                OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+                DOCKER_IMAGE_MANIFEST_MEDIA_TYPE,
+            ],
+        )
+        .await?
+    {
+        Some((_, manifest)) => manifest,
+        None => return Ok(Vec::new()),
+    };
+    let manifest: OciImageManifest = serde_json::from_slice(&manifest)
+        .map_err(|e| anyhow!("Invalid signatures manifest: {}", e))?;
+
+    let mut layers = Vec::new();
+    for layer in manifest.layers {
+        debug!(
+            digest = layer.digest.as_str(),
+            "preserving existing signature"
+        );
+        let data = client.fetch_blob(signature_image, &layer.digest).await?;
+        layers.push(ImageLayer::new(data, layer.media_type, layer.annotations));
+    }
+    Ok(layers)
+}
+
+// Reads a PKCS#8 ECDSA P-256 private key, either unencrypted or encrypted
+// by `cosign generate-key-pair`. The `password` of encrypted keys is asked
+// only when needed
+fn key_pair_from_pem(key: &str, password: impl FnOnce() -> Result<String>) -> Result<EcdsaKeyPair> {
+    let (_, pem) = parse_x509_pem(key.as_bytes()).map_err(|e| anyhow!("Invalid PEM key: {}", e))?;
+    let pkcs8 = match pem.label.as_str() {
+        "PRIVATE KEY" => pem.contents,
+        COSIGN_ENCRYPTED_KEY_LABEL => decrypt_cosign_key(&pem.contents, &password()?)?,
+        label => {
+            return Err(anyhow!(
+                "Unsupported key of type '{}': a PKCS#8 ECDSA P-256 private key is required",
+                label
+            ))
+        }
+    };
+    EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8)
+        .map_err(|e| anyhow!("Invalid ECDSA P-256 private key: {}", e))
+}
+
+// The password of an encrypted cosign key, taken from the environment like
+// cosign does, or asked to the user
+fn key_password() -> Result<String> {
+    match std::env::var(COSIGN_PASSWORD_ENV) {
+        Ok(password) => Ok(password),
+        Err(_) => rpassword::prompt_password("Enter password for private key: ")
+            .map_err(|e| anyhow!("Cannot read the password of the private key: {}", e)),
+    }
+}
+
+/// A private key encrypted by cosign, stored as JSON inside of the PEM
+/// block. Binary values are base64 encoded
+#[derive(Deserialize)]
+struct CosignEncryptedKey {
+    kdf: CosignKdf,
+    cipher: CosignCipher,
+    ciphertext: String,
+}
+
+#[derive(Deserialize)]
+struct CosignKdf {
+    name: String,
+    params: ScryptParams,
+    salt: String,
+}
+
+#[derive(Deserialize)]
+struct ScryptParams {
+    #[serde(rename = "N")]
+    n: u64,
+    r: u32,
+    p: u32,
+}
+
+#[derive(Deserialize)]
+struct CosignCipher {
+    name: String,
+    nonce: String,
+}
+
+// Decrypts a key encrypted by cosign: the encryption key is derived from
+// the password with scrypt, then used to open the NaCl secretbox holding
+// the PKCS#8 private key
+fn decrypt_cosign_key(contents: &[u8], password: &str) -> Result<Vec<u8>> {
+    let key: CosignEncryptedKey = serde_json::from_slice(contents)
+        .map_err(|e| anyhow!("Invalid encrypted cosign key: {}", e))?;
+    if key.kdf.name != "scrypt" {
+        return Err(anyhow!(
+            "Unsupported key derivation function {}",
+            key.kdf.name
+        ));
+    }
+    if key.cipher.name != "nacl/secretbox" {
+        return Err(anyhow!("Unsupported cipher {}", key.cipher.name));
+    }
+
+    let ScryptParams { n, r, p } = key.kdf.params;
+    if !n.is_power_of_two() || n < 2 {
+        return Err(anyhow!("Invalid scrypt cost parameter {}", n));
+    }
+    let params = scrypt::Params::new(n.trailing_zeros() as u8, r, p)
+        .map_err(|e| anyhow!("Invalid scrypt parameters: {}", e))?;
+    let salt = base64::decode(&key.kdf.salt).map_err(|e| anyhow!("Invalid salt: {}", e))?;
+    let mut encryption_key = [0u8; 32];
+    scrypt::scrypt(password.as_bytes(), &salt, &params, &mut encryption_key)
+        .map_err(|e| anyhow!("Cannot derive the encryption key: {}", e))?;
+
+    let nonce = base64::decode(&key.cipher.nonce).map_err(|e| anyhow!("Invalid nonce: {}", e))?;
+    if nonce.len() != 24 {
+        return Err(anyhow!("Invalid nonce of {} bytes", nonce.len()));
+    }
+    let ciphertext =
+        base64::decode(&key.ciphertext).map_err(|e| anyhow!("Invalid ciphertext: {}", e))?;
+    XSalsa20Poly1305::new(Key::from_slice(&encryption_key))
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| anyhow!("Cannot decrypt the private key: wrong password"))
+}
+
+// Signs `payload`, returning the base64 encoded signature
+fn sign_payload(key_pair: &EcdsaKeyPair, payload: &[u8]) -> Result<String> {
+    let signature = key_pair
+        .sign(&SystemRandom::new(), payload)
+        .map_err(|_| anyhow!("Cannot sign payload"))?;
+    Ok(base64::encode(signature.as_ref()))
+}
+
+// Extracts the identity the OIDC token has been issued for
+fn token_subject(identity_token: &str) -> Result<String> {
+    let claims = identity_token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| anyhow!("Invalid identity token"))?;
+    let claims = base64::decode_config(claims, base64::URL_SAFE_NO_PAD)
+        .map_err(|e| anyhow!("Invalid identity token: {}", e))?;
+    let claims: serde_json::Value =
+        serde_json::from_slice(&claims).map_err(|e| anyhow!("Invalid identity token: {}", e))?;
+    claims
+        .get("email")
+        .or_else(|| claims.get("sub"))
+        .and_then(|subject| subject.as_str())
+        .map(String::from)
+        .ok_or_else(|| anyhow!("The identity token has neither an email nor a subject"))
+}
+
+// Signs the payload with an ephemeral key, returning the annotations of the
+// signature layer: the signature, the certificate issued by Fulcio and the
+// bundle proving the inclusion of the signature inside of Rekor
+async fn keyless_signature(
+    payload: &[u8],
+    identity_token: &str,
+    fulcio_url: &str,
+    rekor_url: &str,
+) -> Result<BTreeMap<String, String>> {
+    let rng = SystemRandom::new();
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng)
+        .map_err(|_| anyhow!("Cannot generate ephemeral key"))?;
+    let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref())
+        .map_err(|e| anyhow!("Cannot load ephemeral key: {}", e))?;
+    let mut public_key = P256_SPKI_PREFIX.to_vec();
+    public_key.extend_from_slice(key_pair.public_key().as_ref());
+
+    let client = reqwest::Client::new();
+
+    // request the certificate, proving the possession of the private key
+    // by signing the identity
+    let subject = token_subject(identity_token)?;
+    let certificate_request = json!({
+        "publicKey": {
+            "algorithm": "ecdsa",
+            "content": base64::encode(&public_key),
+        },
+        "signedEmailAddress": sign_payload(&key_pair, subject.as_bytes())?,
+    });
+    let url = format!("{}/api/v1/signingCert", fulcio_url.trim_end_matches('/'));
+    let certificates = client
+        .post(&url)
+        .bearer_auth(identity_token)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&certificate_request)?)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| anyhow!("Cannot obtain a certificate from {}: {}", url, e))?
+        .text()
+        .await?;
+    let (certificate, chain) = split_certificate_chain(&certificates)?;
+
+    let signature = sign_payload(&key_pair, payload)?;
+
+    // record the signature inside of the transparency log
+    let entry = json!({
+        "apiVersion": "0.0.1",
+        "kind": "hashedrekord",
+        "spec": {
+            "signature": {
+                "content": signature,
+                "publicKey": {
+                    "content": base64::encode(&certificate),
+                },
+            },
+            "data": {
+                "hash": {
+                    "algorithm": "sha256",
+                    "value": format!("{:x}", Sha256::digest(payload)),
+                },
+            },
+        },
+    });
+    let url = format!("{}/api/v1/log/entries", rekor_url.trim_end_matches('/'));
+    let response = client
+        .post(&url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&entry)?)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| anyhow!("Cannot upload the signature to {}: {}", url, e))?
+        .bytes()
+        .await?;
+    let log_entries: serde_json::Value = serde_json::from_slice(&response)
+        .map_err(|e| anyhow!("Invalid response from {}: {}", url, e))?;
+    let bundle = rekor_bundle(&log_entries)?;
+
+    let mut annotations = BTreeMap::new();
+    annotations.insert(String::from(SIGNATURE_ANNOTATION), signature);
+    annotations.insert(String::from(CERTIFICATE_ANNOTATION), certificate);
+    annotations.insert(String::from(CHAIN_ANNOTATION), chain);
+    annotations.insert(String::from(BUNDLE_ANNOTATION), bundle);
+    Ok(annotations)
+}
+
+// Splits the PEM chain returned by Fulcio into the certificate issued to
+// the signer and the rest of the chain
+fn split_certificate_chain(certificates: &str) -> Result<(String, String)> {
+    const END_CERTIFICATE: &str = "-----END CERTIFICATE-----";
+
+    let end = certificates
+        .find(END_CERTIFICATE)
+        .ok_or_else(|| anyhow!("Fulcio did not return any certificate"))?
+        + END_CERTIFICATE.len();
+    let certificate = format!("{}\n", certificates[..end].trim());
+    let chain = certificates[end..].trim();
+    let chain = if chain.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", chain)
+    };
+    Ok((certificate, chain))
+}
+
+// Builds the bundle annotation out of the entry created inside of Rekor
+fn rekor_bundle(log_entries: &serde_json::Value) -> Result<String> {
+    let entry = log_entries
+        .as_object()
+        .and_then(|entries| entries.values().next())
+        .ok_or_else(|| anyhow!("Rekor did not return the log entry"))?;
+    let bundle = json!({
+        "SignedEntryTimestamp": entry.pointer("/verification/signedEntryTimestamp"),
+        "Payload": {
+            "body": entry.get("body"),
+            "integratedTime": entry.get("integratedTime"),
+            "logIndex": entry.get("logIndex"),
+            "logID": entry.get("logID"),
+        },
+    });
+    Ok(serde_json::to_string(&bundle)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1};
+
+    fn pem_encode(label: &str, der: &[u8]) -> String {
+        let encoded = base64::encode(der);
+        let lines: Vec<&str> = encoded
+            .as_bytes()
+            .chunks(64)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap())
+            .collect();
+        format!(
+            "-----BEGIN {label}-----\n{}\n-----END {label}-----\n",
+            lines.join("\n"),
+            label = label
+        )
+    }
+
+    #[test]
+    fn test_signature_tag() {
+        assert_eq!(
+            signature_tag(
+                "sha256:61ef63621fa5be8e422881d96d05edfef810992fbf9468e35d1fa5ae815bd97c"
+            ),
+            "sha256-61ef63621fa5be8e422881d96d05edfef810992fbf9468e35d1fa5ae815bd97c.sig"
+        );
+    }
+
+    #[test]
+    fn test_simple_signing_payload() -> Result<()> {
+        let payload: serde_json::Value = serde_json::from_slice(&simple_signing_payload(
+            "ghcr.io/kubewarden/policies/psp-capabilities",
+            "sha256:1234",
+        )?)?;
+        assert_eq!(
+            payload
+                .pointer("/critical/identity/docker-reference")
+                .unwrap(),
+            "ghcr.io/kubewarden/policies/psp-capabilities"
+        );
+        assert_eq!(
+            payload
+                .pointer("/critical/image/docker-manifest-digest")
+                .unwrap(),
+            "sha256:1234"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_payload_with_pem_key() -> Result<()> {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &SystemRandom::new())
+                .unwrap();
+        let key_pair = key_pair_from_pem(&pem_encode("PRIVATE KEY", pkcs8.as_ref()), || {
+            panic!("the password of an unencrypted key should not be asked")
+        })?;
+
+        let signature = base64::decode(sign_payload(&key_pair, b"payload")?)?;
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, key_pair.public_key().as_ref())
+            .verify(b"payload", &signature)
+            .map_err(|_| anyhow!("invalid signature"))?;
+
+        assert!(
+            key_pair_from_pem(&pem_encode("EC PRIVATE KEY", pkcs8.as_ref()), || Ok(
+                String::new()
+            ))
+            .is_err()
+        );
+        Ok(())
+    }
+
+    // Encrypts a PKCS#8 key like `cosign generate-key-pair`, with a cheap
+    // scrypt cost to keep the test fast
+    fn cosign_encrypted_key(pkcs8: &[u8], password: &str) -> String {
+        let salt = [7u8; 32];
+        let nonce = [9u8; 24];
+        let mut encryption_key = [0u8; 32];
+        scrypt::scrypt(
+            password.as_bytes(),
+            &salt,
+            &scrypt::Params::new(4, 8, 1).unwrap(),
+            &mut encryption_key,
+        )
+        .unwrap();
+        let ciphertext = XSalsa20Poly1305::new(Key::from_slice(&encryption_key))
+            .encrypt(Nonce::from_slice(&nonce), pkcs8)
+            .unwrap();
+        let contents = json!({
+            "kdf": {
+                "name": "scrypt",
+                "params": {"N": 16, "r": 8, "p": 1},
+                "salt": base64::encode(salt),
+            },
+            "cipher": {"name": "nacl/secretbox", "nonce": base64::encode(nonce)},
+            "ciphertext": base64::encode(ciphertext),
+        });
+        pem_encode(
+            COSIGN_ENCRYPTED_KEY_LABEL,
+            &serde_json::to_vec(&contents).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_sign_payload_with_encrypted_cosign_key() -> Result<()> {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &SystemRandom::new())
+                .unwrap();
+        let key = cosign_encrypted_key(pkcs8.as_ref(), "secret");
+
+        let key_pair = key_pair_from_pem(&key, || Ok(String::from("secret")))?;
+        let signature = base64::decode(sign_payload(&key_pair, b"payload")?)?;
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, key_pair.public_key().as_ref())
+            .verify(b"payload", &signature)
+            .map_err(|_| anyhow!("invalid signature"))?;
+
+        let error = key_pair_from_pem(&key, || Ok(String::from("wrong")))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("wrong password"),
+            "unexpected error: {}",
+            error
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_subject() -> Result<()> {
+        let claims = base64::encode_config(
+            r#"{"iss":"https://oauth2.sigstore.dev/auth","email":"jane@example.com"}"#,
+            base64::URL_SAFE_NO_PAD,
+        );
+        assert_eq!(
+            token_subject(&format!("header.{}.signature", claims))?,
+            "jane@example.com"
+        );
+        assert!(token_subject("not a token").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_certificate_chain() -> Result<()> {
+        let certificates = "-----BEGIN CERTIFICATE-----\nleaf\n-----END CERTIFICATE-----\n-----BEGIN CERTIFICATE-----\nroot\n-----END CERTIFICATE-----\n";
+        let (certificate, chain) = split_certificate_chain(certificates)?;
+        assert_eq!(
+            certificate,
+            "-----BEGIN CERTIFICATE-----\nleaf\n-----END CERTIFICATE-----\n"
+        );
+        assert_eq!(
+            chain,
+            "-----BEGIN CERTIFICATE-----\nroot\n-----END CERTIFICATE-----\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+use anyhow::{anyhow, Result};
+use policy_evaluator::{
+    policy_fetcher::{
+        policy::Policy, registry::config::DockerConfig, sources::Sources, store::Store,
+    },
+    policy_metadata::Metadata,
+};
+use prettytable::{format, Table};
+use sha2::{Digest, Sha256};
+use std::{fmt, path::Path, time::Duration};
+
+use crate::{
+    oci::{
+        ImageReference, OciClient, DOCKER_IMAGE_MANIFEST_MEDIA_TYPE, OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+    },
+    pull, rm,
+};
+
+// The temporary files of `pull` younger than this are assumed to belong
+// to a pull still in progress
+const INTERRUPTED_PULL_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// The outcome of the verification of a policy of the local store
+#[derive(Debug, PartialEq)]
+enum Status {
+    Ok,
+    /// The Wasm module and its metadata are valid, but the module cannot be
+    /// compared against the reference the policy has been pulled from
+    Unverifiable(String),
+    /// The Wasm module cannot be read, is not valid, or doesn't match the
+    /// reference the policy has been pulled from
+    Corrupted(String),
+    /// The Wasm module is valid, but its Kubewarden metadata cannot be read
+    InvalidMetadata(String),
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Ok => write!(f, "ok"),
+            Status::Unverifiable(reason) => write!(f, "unverifiable: {}", reason),
+            Status::Corrupted(reason) => write!(f, "corrupted: {}", reason),
+            Status::InvalidMetadata(reason) => write!(f, "invalid metadata: {}", reason),
+        }
+    }
+}
+
+struct VerifiedPolicy {
+    policy: Policy,
+    /// The SHA-256 digest of the Wasm module, when it can be read
+    digest: Option<String>,
+    status: Status,
+    pruned: bool,
+}
+
+/// Verifies the integrity of all the policies of the local store,
+/// printing a summary table. When `prune` is set, the corrupted policies
+/// are removed from the store.
+///
+/// Fails when a policy of the store is corrupted, or has invalid metadata,
+/// and it has not been pruned. Unverifiable policies are reported, but
+/// don't cause a failure.
+pub(crate) async fn verify(
+    docker_config: Option<&DockerConfig>,
+    sources: Option<&Sources>,
+    prune: bool,
+) -> Result<()> {
+    let store = Store::default();
+    let mut verified_policies: Vec<VerifiedPolicy> = Vec::new();
+    for policy in store.list()? {
+        let (digest, mut status) =
+            verify_local_policy(&policy.local_path, INTERRUPTED_PULL_MIN_AGE);
+        if let (Some(digest), Status::Ok) = (&digest, &status) {
+            status = verify_reference(&policy.uri, digest, docker_config, sources).await;
+        }
+
+        let pruned = prune && matches!(status, Status::Corrupted(_));
+        if pruned {
+            rm::remove_policy_file(&store, &policy.local_path)
+                .map_err(|e| anyhow!("could not prune policy {}: {}", policy.uri, e))?;
+        }
+        verified_policies.push(VerifiedPolicy {
+            policy,
+            digest,
+            status,
+            pruned,
+        });
+    }
+
+    print_table(&verified_policies);
+
+    let corrupted = verified_policies
+        .iter()
+        .filter(|verified| matches!(verified.status, Status::Corrupted(_)))
+        .count();
+    let unverifiable = verified_policies
+        .iter()
+        .filter(|verified| matches!(verified.status, Status::Unverifiable(_)))
+        .count();
+    let invalid_metadata = verified_policies
+        .iter()
+        .filter(|verified| matches!(verified.status, Status::InvalidMetadata(_)))
+        .count();
+    let pruned = verified_policies
+        .iter()
+        .filter(|verified| verified.pruned)
+        .count();
+    println!(
+        "\n{} policies verified: {} corrupted, {} with invalid metadata, {} unverifiable, {} pruned",
+        verified_policies.len(),
+        corrupted,
+        invalid_metadata,
+        unverifiable,
+        pruned
+    );
+
+    if corrupted + invalid_metadata > pruned {
+        return Err(anyhow!(
+            "{} policies of the store failed the verification",
+            corrupted + invalid_metadata - pruned
+        ));
+    }
+    Ok(())
+}
+
+// Checks the Wasm module and the metadata of the policy stored at `path`,
+// without contacting the registry it has been pulled from. Temporary files
+// of `pull` are reported as corrupted only when older than
+// `interrupted_pull_min_age`, the younger ones can belong to a running pull
+fn verify_local_policy(
+    path: &Path,
+    interrupted_pull_min_age: Duration,
+) -> (Option<String>, Status) {
+    if is_pull_temporary_file(path) {
+        let age = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok());
+        return match age {
+            Some(age) if age >= interrupted_pull_min_age => (
+                None,
+                Status::Corrupted(String::from("leftover of an interrupted pull")),
+            ),
+            _ => (
+                None,
+                Status::Unverifiable(String::from(
+                    "temporary file of a pull that may be in progress",
+                )),
+            ),
+        };
+    }
+
+    let wasm = match std::fs::read(path) {
+        Ok(wasm) => wasm,
+        Err(e) => return (None, Status::Corrupted(format!("cannot be read: {}", e))),
+    };
+    let digest = format!("sha256:{:x}", Sha256::digest(&wasm));
+    if let Err(e) = parse_wasm(&wasm) {
+        return (
+            Some(digest),
+            Status::Corrupted(format!("invalid Wasm module: {}", e)),
+        );
+    }
+
+    let status = match Metadata::from_path(path) {
+        Ok(_) => Status::Ok,
+        Err(e) => Status::InvalidMetadata(e.to_string()),
+    };
+    (Some(digest), status)
+}
+
+// The temporary files written by `pull` are named `.<file name>.<pid>.tmp`,
+// they are left in the store when the pull is interrupted
+fn is_pull_temporary_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|file_name| file_name.to_str())
+        .map(|file_name| file_name.starts_with('.') && file_name.ends_with(".tmp"))
+        .unwrap_or(false)
+}
+
+fn parse_wasm(wasm: &[u8]) -> Result<()> {
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        payload?;
+    }
+    Ok(())
+}
+
+// The policies pulled by digest are compared against the Wasm layer of
+// the manifest they reference. Tags are mutable, hence the policies pulled
+// by tag, like the ones not coming from a registry, cannot be checked.
+async fn verify_reference(
+    uri: &str,
+    digest: &str,
+    docker_config: Option<&DockerConfig>,
+    sources: Option<&Sources>,
+) -> Status {
+    if !uri.starts_with("registry://") {
+        return Status::Unverifiable(String::from("not pulled from a registry"));
+    }
+    let image = match ImageReference::parse(uri) {
+        Ok(image) => image,
+        Err(e) => return Status::Unverifiable(e.to_string()),
+    };
+    let manifest_digest = match &image.digest {
+        Some(manifest_digest) => manifest_digest.clone(),
+        None => return Status::Unverifiable(String::from("pulled by tag")),
+    };
+
+    match pinned_layer_digest(&image, &manifest_digest, docker_config, sources).await {
+        Ok(layer_digest) if layer_digest == digest => Status::Ok,
+        Ok(layer_digest) => Status::Corrupted(format!(
+            "digest {} doesn't match the Wasm layer {} of the referenced manifest",
+            digest, layer_digest
+        )),
+        Err(e) => Status::Unverifiable(format!("cannot fetch the referenced manifest: {}", e)),
+    }
+}
+
+async fn pinned_layer_digest(
+    image: &ImageReference,
+    manifest_digest: &str,
+    docker_config: Option<&DockerConfig>,
+    sources: Option<&Sources>,
+) -> Result<String> {
+    let client = OciClient::new(image, sources, docker_config)?;
+    let (_, manifest) = client
+        .fetch_manifest(
+            image,
+            manifest_digest,
+            &[
+                OCI_IMAGE_MANIFEST_MEDIA_TYPE,
+                DOCKER_IMAGE_MANIFEST_MEDIA_TYPE,
+            ],
+        )
+        .await?
+        .ok_or_else(|| anyhow!("Cannot find the manifest {}", manifest_digest))?;
+    pull::policy_layer_digest(&manifest)
+}
+
+fn print_table(verified_policies: &[VerifiedPolicy]) {
+    if verified_policies.is_empty() {
+        return;
+    }
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["Policy", "SHA-256", "Status", "Pruned"]);
+    for verified in verified_policies {
+        let mut sha256sum = verified
+            .digest
+            .as_deref()
+            .and_then(|digest| digest.strip_prefix("sha256:"))
+            .map(String::from)
+            .unwrap_or_else(|| String::from("-"));
+        sha256sum.truncate(12);
+
+        table.add_row(row![
+            format!("{}", verified.policy),
+            sha256sum,
+            verified.status,
+            if verified.pruned { "yes" } else { "no" },
+        ]);
+    }
+    table.printstd();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_local_policy() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let valid = dir.path().join("valid.wasm");
+        std::fs::write(&valid, b"\0asm\x01\0\0\0")?;
+        let (digest, status) = verify_local_policy(&valid, INTERRUPTED_PULL_MIN_AGE);
+        assert_eq!(
+            digest,
+            Some(format!("sha256:{:x}", Sha256::digest(b"\0asm\x01\0\0\0")))
+        );
+        assert_eq!(status, Status::Ok);
+
+        let truncated = dir.path().join("truncated.wasm");
+        // a type section declaring more bytes than the available ones
+        std::fs::write(&truncated, b"\0asm\x01\0\0\0\x01\x04\x01")?;
+        assert!(matches!(
+            verify_local_policy(&truncated, INTERRUPTED_PULL_MIN_AGE),
+            (Some(_), Status::Corrupted(_))
+        ));
+
+        let not_wasm = dir.path().join("not-wasm.wasm");
+        std::fs::write(&not_wasm, b"<html></html>")?;
+        assert!(matches!(
+            verify_local_policy(&not_wasm, INTERRUPTED_PULL_MIN_AGE),
+            (Some(_), Status::Corrupted(_))
+        ));
+
+        assert!(matches!(
+            verify_local_policy(&dir.path().join("missing.wasm"), INTERRUPTED_PULL_MIN_AGE),
+            (None, Status::Corrupted(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_pull_temporary_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let tmp_file = dir.path().join(".psp:v0.1.0.1234.tmp");
+        std::fs::write(&tmp_file, b"\0asm")?;
+
+        // a recent file can belong to a pull still in progress
+        assert!(matches!(
+            verify_local_policy(&tmp_file, INTERRUPTED_PULL_MIN_AGE),
+            (None, Status::Unverifiable(_))
+        ));
+        assert!(matches!(
+            verify_local_policy(&tmp_file, Duration::from_secs(0)),
+            (None, Status::Corrupted(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_pull_temporary_file() {
+        assert!(is_pull_temporary_file(Path::new(
+            "/store/registry/ghcr.io/psp/.psp:v0.1.0.1234.tmp"
+        )));
+        assert!(!is_pull_temporary_file(Path::new(
+            "/store/registry/ghcr.io/psp/psp:v0.1.0"
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_verify_reference_without_digest() {
+        assert!(matches!(
+            verify_reference(
+                "registry://ghcr.io/kubewarden/policies/psp:v0.1.0",
+                "sha256:0000",
+                None,
+                None
+            )
+            .await,
+            Status::Unverifiable(_)
+        ));
+        assert!(matches!(
+            verify_reference("https://example.com/policy.wasm", "sha256:0000", None, None).await,
+            Status::Unverifiable(_)
+        ));
+    }
+}
@@ -0,0 +1,287 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// A template used to format the output of `inspect` and `run`, using a
+/// subset of the Go template syntax:
+///
+/// * `{{ .field.nested }}`: the value of a field, `{{ . }}` is the whole object
+/// * `{{ .list.0 }}`: an item of a list
+/// * `{{ index .field "key" }}`: a field whose name contains dots or spaces
+///
+/// Strings are printed as they are, other values as JSON. Rendering fails
+/// when a field does not exist.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    /// The keys to be followed, starting from the root object
+    Field(Vec<String>),
+}
+
+impl Template {
+    /// Parses the template, failing when it is not valid
+    pub(crate) fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(String::from(&rest[..start])));
+            }
+            let offset = template.len() - rest.len() + start;
+            let action_end = rest[start..].find("}}").ok_or_else(|| {
+                anyhow!(
+                    "template error: the action starting at offset {} is not closed",
+                    offset
+                )
+            })?;
+            let action = &rest[start + 2..start + action_end];
+            parts.push(Part::Field(parse_action(action).map_err(|e| {
+                anyhow!(
+                    "template error: invalid action '{{{{{}}}}}' at offset {}: {}",
+                    action,
+                    offset,
+                    e
+                )
+            })?));
+            rest = &rest[start + action_end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(String::from(rest)));
+        }
+
+        Ok(Template { parts })
+    }
+
+    /// Renders the template, failing when one of its fields does not exist
+    /// inside of `context`
+    pub(crate) fn render(&self, context: &Value) -> Result<String> {
+        let mut rendered = String::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Field(keys) => {
+                    let value = keys
+                        .iter()
+                        .try_fold(context, |value, key| match value {
+                            Value::Object(object) => object.get(key),
+                            Value::Array(items) => {
+                                key.parse::<usize>().ok().and_then(|i| items.get(i))
+                            }
+                            _ => None,
+                        })
+                        .ok_or_else(|| {
+                            anyhow!("template error: field {} not found", field_name(keys))
+                        })?;
+                    match value {
+                        Value::String(string) => rendered.push_str(string),
+                        value => rendered.push_str(&value.to_string()),
+                    }
+                }
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+// The field as it would be written inside of a template
+fn field_name(keys: &[String]) -> String {
+    if keys.is_empty() {
+        return String::from(".");
+    }
+    keys.iter()
+        .map(|key| {
+            if key.contains(|c: char| c == '.' || c.is_whitespace()) {
+                format!("[{}]", Value::String(key.clone()))
+            } else {
+                format!(".{}", key)
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+}
+
+// Splits an action into words and quoted strings. Quoted strings follow
+// the JSON syntax, hence they can contain spaces and escaped quotes
+fn tokenize(action: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = action.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut escaped = false;
+            let end = loop {
+                match chars.next() {
+                    Some((i, '"')) if !escaped => break i,
+                    Some((_, '\\')) if !escaped => escaped = true,
+                    Some(_) => escaped = false,
+                    None => return Err(anyhow!("the string at {} is not closed", start)),
+                }
+            };
+            let string = &action[start..=end];
+            tokens.push(Token::Quoted(
+                serde_json::from_str(string)
+                    .map_err(|e| anyhow!("invalid string {}: {}", string, e))?,
+            ));
+        } else {
+            let mut end = action.len();
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    end = i;
+                    break;
+                }
+                chars.next();
+            }
+            tokens.push(Token::Word(String::from(&action[start..end])));
+        }
+    }
+    Ok(tokens)
+}
+
+// Parses the contents of a `{{ }}` action into the keys of the field
+fn parse_action(action: &str) -> Result<Vec<String>> {
+    let tokens = tokenize(action)?;
+    match tokens.as_slice() {
+        [Token::Word(path)] => parse_path(path),
+        [Token::Word(index), Token::Word(path), keys @ ..]
+            if index == "index" && !keys.is_empty() =>
+        {
+            let mut field = parse_path(path)?;
+            for key in keys {
+                match key {
+                    Token::Quoted(key) => field.push(key.clone()),
+                    Token::Word(_) => {
+                        return Err(anyhow!("the keys of index must be quoted strings"))
+                    }
+                }
+            }
+            Ok(field)
+        }
+        [] => Err(anyhow!("the action is empty")),
+        _ => Err(anyhow!(
+            "only fields, like `.mutating`, and `index` are supported"
+        )),
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<String>> {
+    if path == "." {
+        return Ok(Vec::new());
+    }
+    let path = path
+        .strip_prefix('.')
+        .ok_or_else(|| anyhow!("fields must start with a dot"))?;
+    path.split('.')
+        .map(|key| {
+            if key.is_empty() {
+                Err(anyhow!("field names cannot be empty"))
+            } else {
+                Ok(String::from(key))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let context = json!({
+            "mutating": false,
+            "annotations": {
+                "title": "psp-capabilities",
+                "io.kubewarden.policy.title": "psp-capabilities",
+                "org.example.owner team": "security",
+            },
+            "rules": [{"operations": ["CREATE", "UPDATE"]}],
+            "status": null,
+        });
+
+        let template = Template::parse(
+            r#"{{ .annotations.title }} mutating={{.mutating}} {{ index .annotations "io.kubewarden.policy.title" }} {{ .rules.0.operations }} {{ .status }}"#,
+        )?;
+        assert_eq!(
+            template.render(&context)?,
+            r#"psp-capabilities mutating=false psp-capabilities ["CREATE","UPDATE"] null"#
+        );
+        assert_eq!(
+            Template::parse(r#"{{ index .annotations "org.example.owner team" }}"#)?
+                .render(&context)?,
+            "security"
+        );
+        assert_eq!(
+            Template::parse("{{ . }}")?.render(&json!({"allowed": true}))?,
+            r#"{"allowed":true}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_unknown_field() -> Result<()> {
+        let context = json!({"annotations": {}, "rules": []});
+
+        for (template, field) in &[
+            ("{{ .missing.field }}", ".missing.field"),
+            ("{{ .rules.0 }}", ".rules.0"),
+            (
+                r#"{{ index .annotations "io.kubewarden.policy.title" }}"#,
+                r#".annotations["io.kubewarden.policy.title"]"#,
+            ),
+        ] {
+            let error = Template::parse(template)?
+                .render(&context)
+                .expect_err("the field should not be found");
+            assert_eq!(
+                error.to_string(),
+                format!("template error: field {} not found", field)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_tokenize() -> Result<()> {
+        assert_eq!(
+            tokenize(r#" index .annotations "a key" "with \"quotes\"" "#)?,
+            vec![
+                Token::Word(String::from("index")),
+                Token::Word(String::from(".annotations")),
+                Token::Quoted(String::from("a key")),
+                Token::Quoted(String::from(r#"with "quotes""#)),
+            ]
+        );
+        assert!(tokenize(r#"index .annotations "not closed"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_template() {
+        for template in &[
+            "{{ .title",
+            "{{ }}",
+            "{{ title }}",
+            "{{ .annotations..title }}",
+            "{{ index .annotations title }}",
+            "{{ if .mutating }}",
+        ] {
+            assert!(
+                Template::parse(template).is_err(),
+                "'{}' should not be valid",
+                template
+            );
+        }
+    }
+}
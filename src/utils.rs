@@ -1,15 +1,41 @@
 use anyhow::{anyhow, Result};
+use policy_evaluator::constants::KUBEWARDEN_CUSTOM_SECTION_METADATA;
 use policy_evaluator::policy_evaluator::PolicyExecutionMode;
-use policy_evaluator::policy_fetcher::store::Store;
+use policy_evaluator::policy_fetcher::store::{PolicyPath, Store};
 use regex::Regex;
 use serde_json::json;
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+use tempfile::TempPath;
 use url::Url;
 
+use crate::oci_layout;
+
+/// Rewrites `oci://` references, the scheme used by tools like ORAS and
+/// Helm, to the `registry://` scheme used by kwctl. Any other uri is
+/// returned as it is.
+pub(crate) fn normalize_scheme(uri: &str) -> String {
+    match uri.strip_prefix("oci://") {
+        Some(reference) => format!("registry://{}", reference),
+        None => String::from(uri),
+    }
+}
+
 pub(crate) fn map_path_to_uri(uri: &str) -> Result<String> {
     let uri_has_schema = Regex::new(r"^\w+://").unwrap();
-    if uri_has_schema.is_match(uri) {
-        return Ok(String::from(uri));
+    if uri_has_schema.is_match(uri) || oci_layout::is_layout_uri(uri) {
+        return Ok(normalize_scheme(uri));
+    }
+    // references pinned by digest (e.g. `ghcr.io/kubewarden/policies/psp@sha256:...`)
+    // are policies stored inside of a registry, unless a file with the
+    // same name exists
+    let digest_reference =
+        Regex::new(r"^([^/@]+\.[^/@]+|[^/@]+:\d+|localhost)/[^@]+@sha256:[a-f0-9]{64}$").unwrap();
+    if digest_reference.is_match(uri) && !Path::new(uri).exists() {
+        return Ok(format!("registry://{}", uri));
     }
     if PathBuf::from(uri).is_absolute() {
         Ok(format!("file://{}", uri))
@@ -25,16 +51,52 @@ pub(crate) fn map_path_to_uri(uri: &str) -> Result<String> {
     }
 }
 
-pub(crate) fn wasm_path(uri: &str) -> Result<PathBuf> {
+/// Location of the Wasm module of a policy
+pub(crate) enum WasmPath {
+    Local(PathBuf),
+    /// Module extracted from an OCI layout, removed once dropped
+    Temporary(TempPath),
+}
+
+impl Deref for WasmPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        match self {
+            WasmPath::Local(path) => path,
+            WasmPath::Temporary(path) => path,
+        }
+    }
+}
+
+impl AsRef<Path> for WasmPath {
+    fn as_ref(&self) -> &Path {
+        self
+    }
+}
+
+pub(crate) fn wasm_path(uri: &str) -> Result<WasmPath> {
+    if oci_layout::is_layout_uri(uri) {
+        return oci_layout::extract_wasm(uri).map(WasmPath::Temporary);
+    }
     let url = Url::parse(uri)?;
     match url.scheme() {
         "file" => url
             .to_file_path()
+            .map(WasmPath::Local)
             .map_err(|err| anyhow!("cannot retrieve path from uri {}: {:?}", url, err)),
         "http" | "https" | "registry" => {
-            let policies = Store::default().list()?;
+            // the path of the policy inside of the store is derived from
+            // its uri, including the tag or the digest of the reference
+            let store = Store::default();
+            let policy_path = store.policy_full_path(uri, PolicyPath::PrefixAndFilename)?;
+            if policy_path.exists() {
+                return Ok(WasmPath::Local(policy_path));
+            }
+
+            let policies = store.list()?;
             let policy = policies.iter().find(|policy| policy.uri == uri).ok_or_else(|| anyhow!("Cannot find policy '{uri}' inside of the local store.\nTry executing `kwctl pull {uri}`", uri = uri))?;
-            Ok(policy.local_path.clone())
+            Ok(WasmPath::Local(policy.local_path.clone()))
         }
         _ => Err(anyhow!("unknown scheme: {}", url.scheme())),
     }
@@ -54,6 +116,20 @@ pub(crate) fn new_policy_execution_mode_from_str(name: &str) -> Result<PolicyExe
     Ok(execution_mode)
 }
 
+/// Returns the contents of the custom section of the Wasm module holding
+/// the Kubewarden metadata, as they are stored
+pub(crate) fn raw_metadata(wasm: &[u8]) -> Result<Option<&[u8]>> {
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        if let wasmparser::Payload::CustomSection { name, data, .. } = payload? {
+            if name == KUBEWARDEN_CUSTOM_SECTION_METADATA {
+                return Ok(Some(data));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -70,6 +146,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_map_path_to_uri_oci_scheme() -> Result<()> {
+        assert_eq!(
+            map_path_to_uri("oci://ghcr.io/kubewarden/policies/psp-capabilities:v0.1.6")?,
+            "registry://ghcr.io/kubewarden/policies/psp-capabilities:v0.1.6",
+        );
+        assert_eq!(
+            map_path_to_uri("oci://localhost:5000/psp@sha256:1234")?,
+            "registry://localhost:5000/psp@sha256:1234",
+        );
+        assert_eq!(
+            normalize_scheme("registry://ghcr.io/kubewarden/policies/psp:v0.1.6"),
+            "registry://ghcr.io/kubewarden/policies/psp:v0.1.6",
+        );
+        assert_eq!(
+            normalize_scheme("https://example.com/psp.wasm"),
+            "https://example.com/psp.wasm",
+        );
+        assert_eq!(normalize_scheme("psp.wasm"), "psp.wasm");
+
+        Ok(())
+    }
+
     #[test]
     fn test_map_path_to_uri_missing_scheme() -> Result<()> {
         assert_eq!(
@@ -92,6 +191,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_map_path_to_uri_digest_reference() -> Result<()> {
+        let digest = "sha256:61ef63621fa5be8e422881d96d05edfef810992fbf9468e35d1fa5ae815bd97c";
+        assert_eq!(
+            map_path_to_uri(&format!(
+                "ghcr.io/kubewarden/policies/psp-capabilities@{}",
+                digest
+            ))?,
+            format!(
+                "registry://ghcr.io/kubewarden/policies/psp-capabilities@{}",
+                digest
+            ),
+        );
+        assert_eq!(
+            map_path_to_uri(&format!("localhost:5000/psp@{}", digest))?,
+            format!("registry://localhost:5000/psp@{}", digest),
+        );
+        // relative paths are still local files
+        assert!(map_path_to_uri(&format!("policies/psp@{}", digest))?.starts_with("file://"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_map_path_to_uri_local_scheme() -> Result<()> {
         assert_eq!(
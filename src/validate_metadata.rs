@@ -0,0 +1,137 @@
+use anyhow::{anyhow, Result};
+use policy_evaluator::{constants::*, policy_metadata::Metadata};
+use validator::Validate;
+
+pub(crate) const DEFAULT_REQUIRED_ANNOTATIONS: &[&str] = &[
+    KUBEWARDEN_ANNOTATION_POLICY_TITLE,
+    KUBEWARDEN_ANNOTATION_POLICY_DESCRIPTION,
+    KUBEWARDEN_ANNOTATION_POLICY_AUTHOR,
+    KUBEWARDEN_ANNOTATION_POLICY_URL,
+    KUBEWARDEN_ANNOTATION_POLICY_LICENSE,
+];
+
+pub(crate) const KUBEWARDEN_ANNOTATION_PREFIX: &str = "io.kubewarden.policy.";
+
+/// Ensures the metadata of the policy is valid and that all the
+/// `required_annotations` are defined with a non-empty value.
+///
+/// Annotations can be referenced either by their full name
+/// (`io.kubewarden.policy.title`) or by their short one (`title`).
+pub(crate) fn validate_metadata(uri: &str, required_annotations: &[String]) -> Result<()> {
+    let uri = crate::utils::map_path_to_uri(uri)?;
+    let wasm_path = crate::utils::wasm_path(uri.as_str())?;
+    let metadata = Metadata::from_path(&wasm_path)
+        .map_err(|e| anyhow!("Error parsing policy metadata: {}", e))?
+        .ok_or_else(||
+            anyhow!(
+                "No Kubewarden metadata found inside of '{}'.\nPolicies can be annotated with the `kwctl annotate` command.",
+                uri)
+        )?;
+
+    metadata
+        .validate()
+        .map_err(|e| anyhow!("Metadata is invalid: {:?}", e))?;
+
+    let missing = missing_annotations(&metadata, required_annotations);
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "The policy metadata does not define the following annotations: {}",
+            missing.join(", ")
+        ));
+    }
+
+    println!("Policy metadata is valid");
+    Ok(())
+}
+
+fn missing_annotations(metadata: &Metadata, required_annotations: &[String]) -> Vec<String> {
+    let annotations = metadata.annotations.clone().unwrap_or_default();
+    required_annotations
+        .iter()
+        .map(|annotation| full_annotation_name(annotation))
+        .filter(|annotation| {
+            annotations
+                .get(annotation)
+                .map_or(true, |value| value.trim().is_empty())
+        })
+        .collect()
+}
+
+fn full_annotation_name(annotation: &str) -> String {
+    if annotation.contains('.') {
+        String::from(annotation)
+    } else {
+        format!("{}{}", KUBEWARDEN_ANNOTATION_PREFIX, annotation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn mock_metadata_with_annotations(annotations: Vec<(&str, &str)>) -> Metadata {
+        Metadata {
+            annotations: Some(
+                annotations
+                    .iter()
+                    .map(|(key, value)| (String::from(*key), String::from(*value)))
+                    .collect::<HashMap<String, String>>(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_missing_annotations_with_default_annotations() {
+        let metadata = mock_metadata_with_annotations(vec![
+            (KUBEWARDEN_ANNOTATION_POLICY_TITLE, "psp-capabilities"),
+            (KUBEWARDEN_ANNOTATION_POLICY_DESCRIPTION, "  "),
+            (KUBEWARDEN_ANNOTATION_POLICY_AUTHOR, "Kubewarden developers"),
+            (
+                KUBEWARDEN_ANNOTATION_POLICY_URL,
+                "https://github.com/kubewarden",
+            ),
+        ]);
+        let required: Vec<String> = DEFAULT_REQUIRED_ANNOTATIONS
+            .iter()
+            .map(|annotation| String::from(*annotation))
+            .collect();
+
+        assert_eq!(
+            missing_annotations(&metadata, &required),
+            vec![
+                String::from(KUBEWARDEN_ANNOTATION_POLICY_DESCRIPTION),
+                String::from(KUBEWARDEN_ANNOTATION_POLICY_LICENSE),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_annotations_with_short_names() {
+        let metadata = mock_metadata_with_annotations(vec![
+            (KUBEWARDEN_ANNOTATION_POLICY_TITLE, "psp-capabilities"),
+            ("org.example.team", "security"),
+        ]);
+
+        assert!(missing_annotations(
+            &metadata,
+            &[String::from("title"), String::from("org.example.team")]
+        )
+        .is_empty());
+        assert_eq!(
+            missing_annotations(&metadata, &[String::from("license")]),
+            vec![String::from(KUBEWARDEN_ANNOTATION_POLICY_LICENSE)]
+        );
+    }
+
+    #[test]
+    fn test_missing_annotations_when_metadata_has_no_annotations() {
+        let metadata = Metadata::default();
+
+        assert_eq!(
+            missing_annotations(&metadata, &[String::from("title")]),
+            vec![String::from(KUBEWARDEN_ANNOTATION_POLICY_TITLE)]
+        );
+    }
+}